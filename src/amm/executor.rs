@@ -1,23 +1,29 @@
+use super::pool_selection::{PoolCandidate, PoolRejection, PoolSelection, PoolSelector};
 use crate::api_v3::response::{ApiV3PoolsPage, ApiV3StandardPool, ApiV3StandardPoolKeys};
 use crate::api_v3::{ApiV3Client, PoolFetchParams, PoolSort, PoolSortOrder, PoolType};
-use crate::builder::SwapInstructionsBuilder;
+use crate::builder::{SimulatedTokenBalance, SwapInstructionsBuilder, SwapPlan};
+use crate::executor::{SwapExecutor, SwapQuote};
+use crate::logs::{parse_simulation_ray_logs, RayLog};
+use crate::priority_fee::{PriorityFeeEstimator, RpcPriorityFeeEstimator};
 use crate::types::{
-    ComputeUnitLimits, PriorityFeeConfig, SwapConfig, SwapConfigOverrides, SwapInput,
+    ComputeUnitLimits, DurableNonce, PriorityFeeConfig, SwapConfig, SwapConfigOverrides, SwapInput,
 };
 use std::sync::Arc;
 
-use anyhow::{anyhow, Context};
-use arrayref::array_ref;
+use crate::error::{Result, SwapError};
+use crate::transfer_fee::SwapTransferFees;
+use async_trait::async_trait;
 use raydium_library::amm::AmmKeys;
 use safe_transmute::{transmute_one_pedantic, transmute_to_bytes};
 use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::account::Account;
 use solana_sdk::account_info::IntoAccountInfo;
 use solana_sdk::instruction::Instruction;
 use solana_sdk::program_pack::Pack;
 use solana_sdk::transaction::VersionedTransaction;
 use solana_sdk::{pubkey, pubkey::Pubkey};
 
-const RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID: Pubkey =
+pub const RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID: Pubkey =
     pubkey!("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8");
 // // https://api-v3.raydium.io/pools/info/mint?mint1=So11111111111111111111111111111111111111112&mint2=EKpQGSJtjMFqKZ9KQanSqYXRcF8fBopzLHYxdM65zcjm&poolType=standard&poolSortField=liquidity&sortType=desc&pageSize=100&page=1
 
@@ -26,7 +32,9 @@ pub struct RaydiumAmm {
     client: Arc<RpcClient>,
     api: ApiV3Client,
     config: SwapConfig,
+    priority_fee_estimator: Arc<dyn PriorityFeeEstimator>,
     load_keys_by_api: bool,
+    pool_selector: PoolSelector,
 }
 
 // todo: Builder pattern for this
@@ -35,7 +43,21 @@ pub struct RaydiumAmmExecutorOpts {
     pub priority_fee: Option<PriorityFeeConfig>,
     pub cu_limits: Option<ComputeUnitLimits>,
     pub wrap_and_unwrap_sol: Option<bool>,
+    /// Builds legacy transactions instead of v0 transactions. Defaults to false
+    pub as_legacy_transaction: Option<bool>,
+    /// Builds transactions with a durable nonce instead of a recent blockhash
+    pub durable_nonce: Option<DurableNonce>,
+    /// Estimates the cu price of dynamic priority fees. Defaults to the rpc node's recent
+    /// prioritization fees
+    pub priority_fee_estimator: Option<Arc<dyn PriorityFeeEstimator>>,
+    /// Fails exact-in swaps whose simulation pays less than the output threshold into the output
+    /// account, after transfer fees. Defaults to false
+    pub check_simulated_output: Option<bool>,
     pub load_keys_by_api: Option<bool>,
+    /// Pools with less liquidity (in USD) are skipped when discovering pools for a mint pair
+    pub min_tvl: Option<f64>,
+    /// Pools opening within this many seconds are quoted instead of rejected. Defaults to 0
+    pub open_within_secs: Option<u64>,
 }
 
 impl RaydiumAmm {
@@ -44,83 +66,144 @@ impl RaydiumAmm {
             priority_fee,
             cu_limits,
             wrap_and_unwrap_sol,
+            as_legacy_transaction,
+            durable_nonce,
+            priority_fee_estimator,
+            check_simulated_output,
             load_keys_by_api,
+            min_tvl,
+            open_within_secs,
         } = config;
         Self {
+            priority_fee_estimator: priority_fee_estimator
+                .unwrap_or_else(|| Arc::new(RpcPriorityFeeEstimator::new(Arc::clone(&client)))),
             client,
             api,
             load_keys_by_api: load_keys_by_api.unwrap_or(true),
+            pool_selector: PoolSelector {
+                program_id: RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
+                min_tvl,
+                open_within_secs: open_within_secs.unwrap_or_default(),
+            },
             config: SwapConfig {
                 priority_fee,
                 cu_limits,
                 wrap_and_unwrap_sol,
-                as_legacy_transaction: Some(true),
+                as_legacy_transaction,
+                durable_nonce,
+                check_simulated_output,
             },
         }
     }
 
-    pub async fn quote(&self, swap_input: &SwapInput) -> anyhow::Result<RaydiumAmmQuote> {
+    pub async fn quote(&self, swap_input: &SwapInput) -> Result<RaydiumAmmQuote> {
         if swap_input.input_token_mint == swap_input.output_token_mint {
-            return Err(anyhow!(
+            return Err(SwapError::InvalidInput(format!(
                 "Input token cannot equal output token {}",
                 swap_input.input_token_mint
-            ));
+            )));
         }
 
-        let mut pool_id = swap_input.market;
-        if pool_id.is_none() {
-            let response: ApiV3PoolsPage<ApiV3StandardPool> = self
-                .api
-                .fetch_pool_by_mints(
+        // pool open times are checked against the cluster clock rather than the local one
+        let now = crate::utils::get_cluster_unix_timestamp(&self.client)
+            .await?
+            .max(0) as u64;
+        let mut selection = match swap_input.market {
+            Some(pool_id) => PoolSelection {
+                candidates: vec![pool_id],
+                rejected: vec![],
+            },
+            None => {
+                let response: ApiV3PoolsPage<ApiV3StandardPool> = self
+                    .api
+                    .fetch_pool_by_mints(
+                        &swap_input.input_token_mint,
+                        Some(&swap_input.output_token_mint),
+                        &PoolFetchParams {
+                            pool_type: PoolType::Standard,
+                            pool_sort: PoolSort::Liquidity,
+                            sort_type: PoolSortOrder::Descending,
+                            page_size: 10,
+                            page: 1,
+                        },
+                    )
+                    .await?;
+                self.pool_selector.select(
+                    response.pools.iter().map(PoolCandidate::from),
                     &swap_input.input_token_mint,
-                    Some(&swap_input.output_token_mint),
-                    &PoolFetchParams {
-                        pool_type: PoolType::Standard,
-                        pool_sort: PoolSort::Liquidity,
-                        sort_type: PoolSortOrder::Descending,
-                        page_size: 10,
-                        page: 1,
-                    },
+                    &swap_input.output_token_mint,
+                    now,
                 )
-                .await?;
-            pool_id = response.pools.into_iter().find_map(|pool| {
-                if pool.mint_a.address == swap_input.input_token_mint
-                    && pool.mint_b.address == swap_input.output_token_mint
-                    || pool.mint_a.address == swap_input.output_token_mint
-                        && pool.mint_b.address == swap_input.input_token_mint
-                        && pool.program_id == RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID
-                {
-                    Some(pool.id)
-                } else {
-                    None
+            }
+        };
+
+        // candidates are in order of liquidity, quote the first one that is live on-chain
+        for pool_id in std::mem::take(&mut selection.candidates) {
+            let (amm_keys, market_keys, accounts) = match self.load_accounts(&pool_id).await {
+                Ok(loaded) => loaded,
+                Err(e) if e.is_transient() => return Err(e),
+                Err(e) => {
+                    selection.reject(pool_id, PoolRejection::LoadFailed(format!("{:#}", e)));
+                    continue;
                 }
-            });
+            };
+            if let Err(reason) = self.check_tradable(&accounts, now) {
+                // an explicitly requested market has no alternatives to fall back to
+                if swap_input.market.is_some() {
+                    return Err(SwapError::PoolNotTradable {
+                        pool: pool_id,
+                        reason,
+                    });
+                }
+                selection.reject(pool_id, reason);
+                continue;
+            }
+            return Self::quote_from_accounts(swap_input, amm_keys, market_keys, &accounts);
         }
 
-        let Some(pool_id) = pool_id else {
-            return Err(anyhow!("Failed to get market for swap"));
-        };
+        Err(selection
+            .into_error(swap_input.input_token_mint, swap_input.output_token_mint)
+            .into())
+    }
+
+    /// Checks that a loaded pool permits swaps and is open at the cluster unix timestamp `now`,
+    /// allowing pools that open within the configured window
+    pub fn check_tradable(
+        &self,
+        accounts: &RaydiumAmmAccounts,
+        now: u64,
+    ) -> Result<(), PoolRejection> {
+        let amm = bytemuck::try_pod_read_unaligned::<super::amm_info::AmmInfo>(&accounts.amm.data)
+            .map_err(|e| PoolRejection::LoadFailed(format!("Invalid amm account data: {}", e)))?;
+        self.pool_selector
+            .check_tradable(amm.status, amm.state_data.pool_open_time, now)
+    }
 
+    /// Loads the keys and accounts needed to quote against the amm pool `pool_id`
+    pub async fn load_accounts(
+        &self,
+        pool_id: &Pubkey,
+    ) -> Result<(AmmKeys, MarketKeys, RaydiumAmmAccounts)> {
         let (amm_keys, market_keys) = if self.load_keys_by_api {
             let response = self
                 .api
                 .fetch_pool_keys_by_ids::<ApiV3StandardPoolKeys>(
-                    [&pool_id].into_iter().map(|id| id.to_string()).collect(),
+                    [pool_id].into_iter().map(|id| id.to_string()).collect(),
                 )
                 .await?;
-            let keys = response.first().context(format!(
-                "Failed to get pool keys for raydium standard pool {}",
-                pool_id
-            ))?;
+            let keys = response.first().ok_or_else(|| {
+                SwapError::PoolNotFound(format!("no keys for raydium standard pool {}", pool_id))
+            })?;
 
             (AmmKeys::try_from(keys)?, MarketKeys::try_from(keys)?)
         } else {
             let amm_keys = raydium_library::amm::utils::load_amm_keys(
                 &self.client,
                 &RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
-                &pool_id,
+                pool_id,
             )
-            .await?;
+            .await
+            .map_err(|e| SwapError::from_library(e, SwapError::PoolNotFound))?;
 
             let market_keys = MarketKeys::from(
                 &raydium_library::amm::openbook::get_keys_for_market(
@@ -128,7 +211,8 @@ impl RaydiumAmm {
                     &amm_keys.market_program,
                     &amm_keys.market,
                 )
-                .await?,
+                .await
+                .map_err(|e| SwapError::from_library(e, SwapError::DecodeError))?,
             );
 
             (amm_keys, market_keys)
@@ -137,7 +221,7 @@ impl RaydiumAmm {
         // reload accounts data to calculate amm pool vault amount
         // get multiple accounts at the same time to ensure data consistency
         let load_pubkeys = vec![
-            pool_id,
+            *pool_id,
             amm_keys.amm_target,
             amm_keys.amm_pc_vault,
             amm_keys.amm_coin_vault,
@@ -146,32 +230,61 @@ impl RaydiumAmm {
             market_keys.event_queue,
         ];
         let rsps = crate::utils::get_multiple_account_data(&self.client, &load_pubkeys).await?;
-        let accounts = array_ref![rsps, 0, 7];
-        let [amm_account, amm_target_account, amm_pc_vault_account, amm_coin_vault_account, amm_open_orders_account, market_account, market_event_q_account] =
-            accounts;
+        let accounts = RaydiumAmmAccounts::try_from(rsps)?;
+
+        Ok((amm_keys, market_keys, accounts))
+    }
+
+    /// Calculates a quote from already loaded pool accounts without any network requests.
+    pub fn quote_from_accounts(
+        swap_input: &SwapInput,
+        amm_keys: AmmKeys,
+        market_keys: MarketKeys,
+        accounts: &RaydiumAmmAccounts,
+    ) -> Result<RaydiumAmmQuote> {
+        if swap_input.input_token_mint == swap_input.output_token_mint {
+            return Err(SwapError::InvalidInput(format!(
+                "Input token cannot equal output token {}",
+                swap_input.input_token_mint
+            )));
+        }
+
         let amm: raydium_amm::state::AmmInfo = transmute_one_pedantic::<super::amm_info::AmmInfo>(
-            transmute_to_bytes(&amm_account.as_ref().unwrap().clone().data),
+            transmute_to_bytes(&accounts.amm.data),
         )
-        .map_err(|e| e.without_src())?
+        .map_err(|e| SwapError::DecodeError(format!("amm account: {}", e.without_src())))?
         .into();
-        let _amm_target: raydium_amm::state::TargetOrders =
-            transmute_one_pedantic::<raydium_amm::state::TargetOrders>(transmute_to_bytes(
-                &amm_target_account.as_ref().unwrap().clone().data,
-            ))
-            .map_err(|e| e.without_src())?;
-        let amm_pc_vault =
-            spl_token::state::Account::unpack(&amm_pc_vault_account.as_ref().unwrap().clone().data)
-                .unwrap();
-        let amm_coin_vault = spl_token::state::Account::unpack(
-            &amm_coin_vault_account.as_ref().unwrap().clone().data,
-        )
-        .unwrap();
+        let _amm_target: raydium_amm::state::TargetOrders = transmute_one_pedantic::<
+            raydium_amm::state::TargetOrders,
+        >(transmute_to_bytes(
+            &accounts.amm_target.data,
+        ))
+        .map_err(|e| SwapError::DecodeError(format!("amm target orders: {}", e.without_src())))?;
+        let amm_pc_vault = unpack_token_account(&accounts.amm_pc_vault, "amm pc vault")?;
+        let amm_coin_vault = unpack_token_account(&accounts.amm_coin_vault, "amm coin vault")?;
+        // `AmmStatus::from_u64` panics on unknown values
+        if !raydium_amm::state::AmmStatus::valid_status(amm.status) {
+            return Err(SwapError::DecodeError(format!(
+                "amm account: invalid status {}",
+                amm.status
+            )));
+        }
         let (amm_pool_pc_vault_amount, amm_pool_coin_vault_amount) =
             if raydium_amm::state::AmmStatus::from_u64(amm.status).orderbook_permission() {
-                let amm_open_orders_account =
-                    &mut amm_open_orders_account.as_ref().unwrap().clone();
-                let market_account = &mut market_account.as_ref().unwrap().clone();
-                let market_event_q_account = &mut market_event_q_account.as_ref().unwrap().clone();
+                check_owner(
+                    &accounts.amm_open_orders,
+                    &amm.market_program,
+                    "amm open orders",
+                )?;
+                check_owner(&accounts.market, &amm.market_program, "market")?;
+                check_owner(
+                    &accounts.market_event_queue,
+                    &amm.market_program,
+                    "market event queue",
+                )?;
+                let amm_open_orders_account = &mut accounts.amm_open_orders.clone();
+                let market_account = &mut accounts.market.clone();
+                let market_event_q_account = &mut accounts.market_event_queue.clone();
                 let amm_open_orders_info =
                     (&amm.open_orders, amm_open_orders_account).into_account_info();
                 let market_account_info = (&amm.market, market_account).into_account_info();
@@ -228,11 +341,37 @@ impl RaydiumAmm {
             && swap_input.output_token_mint == amm_keys.amm_pc_mint
         {
             (raydium_library::amm::utils::SwapDirection::Coin2PC, true)
-        } else {
+        } else if swap_input.input_token_mint == amm_keys.amm_pc_mint
+            && swap_input.output_token_mint == amm_keys.amm_coin_mint
+        {
             (raydium_library::amm::utils::SwapDirection::PC2Coin, false)
+        } else {
+            return Err(SwapError::InvalidInput(format!(
+                "Amm pool {} does not trade {} for {}",
+                amm_keys.amm_pool, swap_input.input_token_mint, swap_input.output_token_mint
+            )));
         };
 
         let amount_specified_is_input = swap_input.mode.amount_specified_is_input();
+        // guard the divisions and subtractions in the library's swap math, which panic rather
+        // than error
+        if amm.fees.swap_fee_denominator == 0 {
+            return Err(SwapError::InvalidPoolState(format!(
+                "amm pool {} has a zero swap fee denominator",
+                amm_keys.amm_pool
+            )));
+        }
+        let output_vault_amount = if coin_to_pc {
+            amm_pool_pc_vault_amount
+        } else {
+            amm_pool_coin_vault_amount
+        };
+        if !amount_specified_is_input && swap_input.amount >= output_vault_amount {
+            return Err(SwapError::InsufficientLiquidity(format!(
+                "amm pool {} holds {} of the output token, {} requested",
+                amm_keys.amm_pool, output_vault_amount, swap_input.amount
+            )));
+        }
         let (other_amount, other_amount_threshold) = raydium_library::amm::swap_with_slippage(
             amm_pool_pc_vault_amount,
             amm_pool_coin_vault_amount,
@@ -242,7 +381,8 @@ impl RaydiumAmm {
             swap_input.amount,
             amount_specified_is_input,
             swap_input.slippage_bps as u64,
-        )?;
+        )
+        .map_err(|e| SwapError::from_library(e, SwapError::Math))?;
         log::debug!(
             "raw quote: {}. raw other_amount_threshold: {}",
             other_amount,
@@ -250,7 +390,7 @@ impl RaydiumAmm {
         );
 
         Ok(RaydiumAmmQuote {
-            market: pool_id,
+            market: amm_keys.amm_pool,
             input_mint: swap_input.input_token_mint,
             output_mint: swap_input.output_token_mint,
             amount: swap_input.amount,
@@ -267,6 +407,8 @@ impl RaydiumAmm {
             } else {
                 amm.coin_decimals
             } as u8,
+            // amm v4 pools only hold spl-token mints
+            transfer_fees: SwapTransferFees::default(),
             amm_keys,
             market_keys,
         })
@@ -277,8 +419,10 @@ impl RaydiumAmm {
         input_pubkey: Pubkey,
         output: RaydiumAmmQuote,
         overrides: Option<&SwapConfigOverrides>,
-    ) -> anyhow::Result<Vec<solana_sdk::instruction::Instruction>> {
-        let builder = self.make_swap(input_pubkey, output, overrides).await?;
+    ) -> Result<Vec<solana_sdk::instruction::Instruction>> {
+        let builder = self
+            .make_swap(input_pubkey, output, overrides, false)
+            .await?;
         builder.build_instructions()
     }
 
@@ -287,9 +431,42 @@ impl RaydiumAmm {
         input_pubkey: Pubkey,
         output: RaydiumAmmQuote,
         overrides: Option<&SwapConfigOverrides>,
-    ) -> anyhow::Result<VersionedTransaction> {
-        let builder = self.make_swap(input_pubkey, output, overrides).await?;
-        builder.build_transaction(Some(&input_pubkey), None)
+    ) -> Result<VersionedTransaction> {
+        let fee_payer = overrides.and_then(|o| o.fee_payer).unwrap_or(input_pubkey);
+        let builder = self
+            .make_swap(input_pubkey, output, overrides, false)
+            .await?;
+        builder.build_transaction(Some(&fee_payer), None)
+    }
+
+    /// Builds the swap transaction and simulates it, reading the user's input and output token
+    /// balances before and after. Fails with [`SwapError::Simulation`] if the swap would fail,
+    /// e.g. on a stale quote, a missing balance or a frozen account, before any fees are paid.
+    pub async fn simulate_swap(
+        &self,
+        input_pubkey: Pubkey,
+        output: RaydiumAmmQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<RaydiumAmmSimulation> {
+        let builder = self
+            .make_swap(input_pubkey, output.clone(), overrides, true)
+            .await?;
+        let simulation = builder
+            .simulation
+            .as_ref()
+            .ok_or_else(|| SwapError::Transaction("Swap was not simulated".to_string()))?;
+        let ray_logs = parse_simulation_ray_logs(simulation)?;
+
+        let (amount_in, amount_out) =
+            simulated_amounts(&builder.simulated_token_balances, &ray_logs)?;
+
+        Ok(RaydiumAmmSimulation {
+            quote: output,
+            amount_in,
+            amount_out,
+            compute_units: simulation.units_consumed,
+            ray_logs,
+        })
     }
 
     pub fn update_config(&mut self, config: &SwapConfig) {
@@ -301,62 +478,95 @@ impl RaydiumAmm {
         input_pubkey: Pubkey,
         output: RaydiumAmmQuote,
         overrides: Option<&SwapConfigOverrides>,
-    ) -> anyhow::Result<SwapInstructionsBuilder> {
-        let priority_fee = overrides
-            .and_then(|o| o.priority_fee)
-            .or(self.config.priority_fee);
-        let cu_limits = overrides
-            .and_then(|o| o.cu_limits)
-            .or(self.config.cu_limits);
-        let wrap_and_unwrap_sol = overrides
-            .and_then(|o| o.wrap_and_unwrap_sol)
-            .or(self.config.wrap_and_unwrap_sol)
-            .unwrap_or(true);
-
-        let mut builder = SwapInstructionsBuilder::default();
-        let _associated_accounts = builder.handle_token_wrapping_and_accounts_creation(
-            input_pubkey,
-            wrap_and_unwrap_sol,
-            if output.amount_specified_is_input {
+        simulate_balances: bool,
+    ) -> Result<SwapInstructionsBuilder> {
+        let config = self.config.resolve(input_pubkey, overrides);
+        let (input_token_program, output_token_program) = output.token_programs()?;
+        let plan = SwapPlan {
+            user: input_pubkey,
+            input_mint: output.input_mint,
+            output_mint: output.output_mint,
+            input_token_program,
+            output_token_program,
+            max_amount_in: if output.amount_specified_is_input {
                 output.amount
             } else {
-                output.other_amount
+                output.other_amount_threshold
             },
-            output.input_mint,
-            output.output_mint,
-            spl_token::ID,
-            spl_token::ID,
-            None,
-        )?;
-        let instruction = swap_instruction(
-            &RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
-            &output.amm_keys,
-            &output.market_keys,
-            &input_pubkey,
-            &spl_associated_token_account::get_associated_token_address(
-                &input_pubkey,
-                &output.input_mint,
-            ),
-            &spl_associated_token_account::get_associated_token_address(
-                &input_pubkey,
-                &output.output_mint,
-            ),
-            output.amount,
-            output.other_amount_threshold,
-            output.amount_specified_is_input,
-        )?;
-        builder.swap_instruction = Some(instruction);
-
-        let compute_units = builder
-            .handle_compute_units_params(cu_limits, &self.client, input_pubkey)
-            .await?;
-        builder.handle_priority_fee_params(priority_fee, compute_units, input_pubkey)?;
+            pool_ids: &[output.market],
+            min_amount_out: output
+                .amount_specified_is_input
+                .then_some(output.other_amount_threshold),
+        };
+        SwapInstructionsBuilder::build_swap(
+            &config,
+            plan,
+            &self.api,
+            &self.client,
+            self.priority_fee_estimator.as_ref(),
+            |builder, associated_accounts| {
+                builder.swap_instructions.push(output.swap_instruction(
+                    &input_pubkey,
+                    &associated_accounts.input_ata,
+                    &associated_accounts.output_ata,
+                )?);
+                if simulate_balances {
+                    builder.simulated_token_accounts = vec![
+                        associated_accounts.input_ata,
+                        associated_accounts.output_ata,
+                    ];
+                }
+                Ok(())
+            },
+        )
+        .await
+    }
+}
 
-        Ok(builder)
+#[async_trait]
+impl SwapExecutor for RaydiumAmm {
+    fn program_id(&self) -> Pubkey {
+        RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID
     }
+
+    async fn quote(&self, swap_input: &SwapInput) -> Result<SwapQuote> {
+        Ok(RaydiumAmm::quote(self, swap_input).await?.into())
+    }
+
+    async fn swap_instructions(
+        &self,
+        input_pubkey: Pubkey,
+        quote: SwapQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<Vec<Instruction>> {
+        RaydiumAmm::swap_instructions(self, input_pubkey, quote.try_into()?, overrides).await
+    }
+
+    async fn swap_transaction(
+        &self,
+        input_pubkey: Pubkey,
+        quote: SwapQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<VersionedTransaction> {
+        RaydiumAmm::swap_transaction(self, input_pubkey, quote.try_into()?, overrides).await
+    }
+}
+
+/// A simulated amm v4 swap, next to the quote it was built from
+#[derive(Debug, Clone)]
+pub struct RaydiumAmmSimulation {
+    pub quote: RaydiumAmmQuote,
+    /// The input tokens the swap spent
+    pub amount_in: u64,
+    /// The output tokens the swap paid out
+    pub amount_out: u64,
+    /// The compute units the swap consumed
+    pub compute_units: Option<u64>,
+    /// The amm's `ray_log` records of the simulation
+    pub ray_logs: Vec<RayLog>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RaydiumAmmQuote {
     /// The address of the amm pool
     pub market: Pubkey,
@@ -376,12 +586,43 @@ pub struct RaydiumAmmQuote {
     pub input_mint_decimals: u8,
     /// The output mint decimals
     pub output_mint_decimals: u8,
+    /// Token-2022 transfer fees withheld from the swap. `amount` and `other_amount` are what the
+    /// user sends and receives, fees included
+    pub transfer_fees: SwapTransferFees,
     /// Amm keys
     pub amm_keys: AmmKeys,
     /// Market keys
     pub market_keys: MarketKeys,
 }
 
+impl RaydiumAmmQuote {
+    /// Builds the swap instruction for this quote between the user's token accounts
+    pub fn swap_instruction(
+        &self,
+        user_owner: &Pubkey,
+        user_source: &Pubkey,
+        user_destination: &Pubkey,
+    ) -> Result<Instruction> {
+        swap_instruction(
+            &RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
+            &self.amm_keys,
+            &self.market_keys,
+            user_owner,
+            user_source,
+            user_destination,
+            self.amount,
+            self.other_amount_threshold,
+            self.amount_specified_is_input,
+        )
+    }
+
+    /// Returns the (input, output) token programs of the swap. Amm v4 pools only hold spl-token
+    /// mints, token-2022 mints trade on cpmm and clmm pools.
+    pub fn token_programs(&self) -> Result<(Pubkey, Pubkey)> {
+        Ok((spl_token::ID, spl_token::ID))
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct MarketKeys {
     pub event_queue: Pubkey,
@@ -392,6 +633,97 @@ pub struct MarketKeys {
     pub vault_signer_key: Pubkey,
 }
 
+/// Snapshot of the accounts needed to quote against an amm pool
+#[derive(Debug, Clone)]
+pub struct RaydiumAmmAccounts {
+    /// The amm pool account
+    pub amm: Account,
+    /// The amm target orders account
+    pub amm_target: Account,
+    /// The amm pc vault
+    pub amm_pc_vault: Account,
+    /// The amm coin vault
+    pub amm_coin_vault: Account,
+    /// The amm open orders account
+    pub amm_open_orders: Account,
+    /// The openbook market account
+    pub market: Account,
+    /// The openbook market event queue
+    pub market_event_queue: Account,
+}
+
+impl TryFrom<Vec<Option<Account>>> for RaydiumAmmAccounts {
+    type Error = SwapError;
+
+    /// Expects accounts in the order: amm, target orders, pc vault, coin vault, open orders,
+    /// market, market event queue
+    fn try_from(accounts: Vec<Option<Account>>) -> Result<Self, Self::Error> {
+        let [amm, amm_target, amm_pc_vault, amm_coin_vault, amm_open_orders, market, market_event_queue]: [Option<Account>; 7] =
+            accounts
+                .try_into()
+                .map_err(|v: Vec<_>| {
+                    SwapError::InvalidInput(format!("Expected 7 amm accounts, got {}", v.len()))
+                })?;
+        let missing = |name: &str| SwapError::AccountMissing(name.to_string());
+        Ok(RaydiumAmmAccounts {
+            amm: amm.ok_or_else(|| missing("amm"))?,
+            amm_target: amm_target.ok_or_else(|| missing("amm target orders"))?,
+            amm_pc_vault: amm_pc_vault.ok_or_else(|| missing("amm pc vault"))?,
+            amm_coin_vault: amm_coin_vault.ok_or_else(|| missing("amm coin vault"))?,
+            amm_open_orders: amm_open_orders.ok_or_else(|| missing("amm open orders"))?,
+            market: market.ok_or_else(|| missing("market"))?,
+            market_event_queue: market_event_queue.ok_or_else(|| missing("market event queue"))?,
+        })
+    }
+}
+
+/// The amounts a simulated swap spent and paid out, from the `[input, output]` token account
+/// `balances`. Wrapped sol accounts are closed by the swap, so their amounts are read from the
+/// amm's swap log instead.
+fn simulated_amounts(
+    balances: &[SimulatedTokenBalance],
+    ray_logs: &[RayLog],
+) -> Result<(u64, u64)> {
+    let logged_amounts = ray_logs.iter().find_map(RayLog::swap_amounts);
+    let logged_amount = |amount: fn((u64, u64)) -> u64| {
+        logged_amounts.map(amount).ok_or_else(|| {
+            SwapError::Transaction("Simulated swap without a swap ray_log".to_string())
+        })
+    };
+    let [input_balance, output_balance] = balances else {
+        return Err(SwapError::Transaction(
+            "Swap token accounts were not simulated".to_string(),
+        ));
+    };
+    let amount_in = match input_balance.after {
+        Some(after) => input_balance.before.saturating_sub(after),
+        None => logged_amount(|(amount_in, _)| amount_in)?,
+    };
+    let amount_out = match output_balance.after {
+        Some(after) => after.saturating_sub(output_balance.before),
+        None => logged_amount(|(_, amount_out)| amount_out)?,
+    };
+    Ok((amount_in, amount_out))
+}
+
+/// Unpacks a spl-token account, naming the account in the error
+fn unpack_token_account(account: &Account, name: &str) -> Result<spl_token::state::Account> {
+    spl_token::state::Account::unpack(&account.data)
+        .map_err(|e| SwapError::DecodeError(format!("{}: {}", name, e)))
+}
+
+/// Checks that an openbook account is owned by the market program before it is handed to the
+/// dex loaders
+fn check_owner(account: &Account, market_program: &Pubkey, name: &str) -> Result<()> {
+    if account.owner != *market_program {
+        return Err(SwapError::InvalidPoolState(format!(
+            "{}: owned by {}, expected market program {}",
+            name, account.owner, market_program
+        )));
+    }
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 fn swap_instruction(
     amm_program: &Pubkey,
@@ -403,7 +735,7 @@ fn swap_instruction(
     amount_specified: u64,
     other_amount_threshold: u64,
     swap_base_in: bool,
-) -> anyhow::Result<Instruction> {
+) -> Result<Instruction> {
     let swap_instruction = if swap_base_in {
         raydium_amm::instruction::swap_base_in(
             amm_program,
@@ -478,50 +810,340 @@ impl From<&crate::api_v3::response::pools::standard::MarketKeys> for MarketKeys
     }
 }
 impl TryFrom<&crate::api_v3::response::ApiV3StandardPoolKeys> for MarketKeys {
-    type Error = anyhow::Error;
+    type Error = SwapError;
 
     fn try_from(
         keys: &crate::api_v3::response::ApiV3StandardPoolKeys,
     ) -> Result<Self, Self::Error> {
-        let keys = keys
-            .keys
-            .market
-            .as_ref()
-            .context("market keys should be present for amm")?;
-        Ok(MarketKeys::from(keys))
+        let market_keys =
+            keys.keys.market.as_ref().ok_or_else(|| {
+                SwapError::AccountMissing(format!("market of amm pool {}", keys.id))
+            })?;
+        Ok(MarketKeys::from(market_keys))
     }
 }
 
 impl TryFrom<&crate::api_v3::response::ApiV3StandardPoolKeys> for AmmKeys {
-    type Error = anyhow::Error;
+    type Error = SwapError;
 
     fn try_from(
         keys: &crate::api_v3::response::ApiV3StandardPoolKeys,
     ) -> Result<Self, Self::Error> {
-        let market_keys = keys
-            .keys
-            .market
-            .as_ref()
-            .context("market keys should be present for amm")?;
+        let market_keys =
+            keys.keys.market.as_ref().ok_or_else(|| {
+                SwapError::AccountMissing(format!("market of amm pool {}", keys.id))
+            })?;
         Ok(AmmKeys {
             amm_pool: keys.id,
             amm_coin_mint: keys.mint_a.address,
             amm_pc_mint: keys.mint_b.address,
             amm_authority: keys.keys.authority,
-            amm_target: keys
-                .keys
-                .target_orders
-                .context("target orders should be present for amm")?,
+            amm_target: keys.keys.target_orders.ok_or_else(|| {
+                SwapError::AccountMissing(format!("target orders of amm pool {}", keys.id))
+            })?,
             amm_coin_vault: keys.vault.a,
             amm_pc_vault: keys.vault.b,
             amm_lp_mint: keys.keys.mint_lp.address,
-            amm_open_order: keys
-                .keys
-                .open_orders
-                .context("open orders should be present for amm")?,
+            amm_open_order: keys.keys.open_orders.ok_or_else(|| {
+                SwapError::AccountMissing(format!("open orders of amm pool {}", keys.id))
+            })?,
             market_program: market_keys.market_program_id,
             market: market_keys.market_id,
             nonce: 0, // random
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SwapExecutionMode;
+
+    const COIN_VAULT_AMOUNT: u64 = 1_000_000_000_000;
+    const PC_VAULT_AMOUNT: u64 = 150_000_000_000;
+
+    fn token_account(mint: Pubkey, amount: u64) -> Account {
+        let mut account = Account::new(0, spl_token::state::Account::LEN, &spl_token::ID);
+        spl_token::state::Account::pack(
+            spl_token::state::Account {
+                mint,
+                owner: Pubkey::new_unique(),
+                amount,
+                state: spl_token::state::AccountState::Initialized,
+                ..Default::default()
+            },
+            &mut account.data,
+        )
+        .unwrap();
+        account
+    }
+
+    fn swap_only_pool() -> (AmmKeys, MarketKeys, RaydiumAmmAccounts) {
+        let amm_keys = AmmKeys {
+            amm_pool: Pubkey::new_unique(),
+            amm_coin_mint: Pubkey::new_unique(),
+            amm_pc_mint: Pubkey::new_unique(),
+            amm_authority: Pubkey::new_unique(),
+            amm_target: Pubkey::new_unique(),
+            amm_coin_vault: Pubkey::new_unique(),
+            amm_pc_vault: Pubkey::new_unique(),
+            amm_lp_mint: Pubkey::new_unique(),
+            amm_open_order: Pubkey::new_unique(),
+            market_program: Pubkey::new_unique(),
+            market: Pubkey::new_unique(),
+            nonce: 0,
+        };
+        let market_keys = MarketKeys {
+            event_queue: Pubkey::new_unique(),
+            bids: Pubkey::new_unique(),
+            asks: Pubkey::new_unique(),
+            coin_vault: Pubkey::new_unique(),
+            pc_vault: Pubkey::new_unique(),
+            vault_signer_key: Pubkey::new_unique(),
+        };
+
+        let mut amm_info = super::super::amm_info::AmmInfo {
+            status: raydium_amm::state::AmmStatus::SwapOnly as u64,
+            coin_decimals: 9,
+            pc_decimals: 6,
+            coin_vault: amm_keys.amm_coin_vault,
+            pc_vault: amm_keys.amm_pc_vault,
+            coin_vault_mint: amm_keys.amm_coin_mint,
+            pc_vault_mint: amm_keys.amm_pc_mint,
+            open_orders: amm_keys.amm_open_order,
+            market: amm_keys.market,
+            market_program: amm_keys.market_program,
+            target_orders: amm_keys.amm_target,
+            ..Default::default()
+        };
+        amm_info.fees.swap_fee_numerator = 25;
+        amm_info.fees.swap_fee_denominator = 10_000;
+        let mut amm = Account::new(
+            0,
+            std::mem::size_of::<super::super::amm_info::AmmInfo>(),
+            &RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
+        );
+        amm.data = bytemuck::bytes_of(&amm_info).to_vec();
+
+        let accounts = RaydiumAmmAccounts {
+            amm,
+            amm_target: Account::new(
+                0,
+                std::mem::size_of::<raydium_amm::state::TargetOrders>(),
+                &RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
+            ),
+            amm_pc_vault: token_account(amm_keys.amm_pc_mint, PC_VAULT_AMOUNT),
+            amm_coin_vault: token_account(amm_keys.amm_coin_mint, COIN_VAULT_AMOUNT),
+            amm_open_orders: Account::default(),
+            market: Account::default(),
+            market_event_queue: Account::default(),
+        };
+
+        (amm_keys, market_keys, accounts)
+    }
+
+    #[test]
+    pub fn quote_from_accounts_exact_in() {
+        let (amm_keys, market_keys, accounts) = swap_only_pool();
+        let swap_input = SwapInput {
+            input_token_mint: amm_keys.amm_coin_mint,
+            output_token_mint: amm_keys.amm_pc_mint,
+            slippage_bps: 100,
+            amount: 1_000_000_000,
+            mode: SwapExecutionMode::ExactIn,
+            market: Some(amm_keys.amm_pool),
+        };
+
+        let quote =
+            RaydiumAmm::quote_from_accounts(&swap_input, amm_keys, market_keys, &accounts).unwrap();
+
+        let amount_in_after_fee = swap_input.amount - swap_input.amount * 25 / 10_000;
+        let expected_out = (PC_VAULT_AMOUNT as u128 * amount_in_after_fee as u128
+            / (COIN_VAULT_AMOUNT as u128 + amount_in_after_fee as u128))
+            as u64;
+        assert_eq!(quote.market, amm_keys.amm_pool);
+        assert_eq!(quote.other_amount, expected_out);
+        assert_eq!(quote.other_amount_threshold, expected_out * 9_900 / 10_000);
+        assert_eq!(quote.input_mint_decimals, 9);
+        assert_eq!(quote.output_mint_decimals, 6);
+    }
+
+    #[test]
+    pub fn quote_from_accounts_rejects_same_mint() {
+        let (amm_keys, market_keys, accounts) = swap_only_pool();
+        let swap_input = SwapInput {
+            input_token_mint: amm_keys.amm_coin_mint,
+            output_token_mint: amm_keys.amm_coin_mint,
+            slippage_bps: 100,
+            amount: 1_000_000_000,
+            mode: SwapExecutionMode::ExactIn,
+            market: Some(amm_keys.amm_pool),
+        };
+
+        assert!(
+            RaydiumAmm::quote_from_accounts(&swap_input, amm_keys, market_keys, &accounts).is_err()
+        );
+    }
+
+    fn exact_in(amm_keys: &AmmKeys) -> SwapInput {
+        SwapInput {
+            input_token_mint: amm_keys.amm_coin_mint,
+            output_token_mint: amm_keys.amm_pc_mint,
+            slippage_bps: 100,
+            amount: 1_000_000_000,
+            mode: SwapExecutionMode::ExactIn,
+            market: Some(amm_keys.amm_pool),
+        }
+    }
+
+    fn set_status(accounts: &mut RaydiumAmmAccounts, status: u64) {
+        accounts.amm.data[..8].copy_from_slice(&status.to_le_bytes());
+    }
+
+    #[test]
+    pub fn accounts_name_the_missing_account() {
+        let names = [
+            "amm",
+            "amm target orders",
+            "amm pc vault",
+            "amm coin vault",
+            "amm open orders",
+            "market",
+            "market event queue",
+        ];
+        for (missing, name) in names.iter().enumerate() {
+            let accounts = (0..names.len())
+                .map(|i| (i != missing).then(Account::default))
+                .collect::<Vec<_>>();
+            match RaydiumAmmAccounts::try_from(accounts) {
+                Err(SwapError::AccountMissing(account)) => assert_eq!(account, *name),
+                other => panic!("expected {} to be missing, got {:?}", name, other),
+            }
+        }
+
+        assert!(matches!(
+            RaydiumAmmAccounts::try_from(vec![Some(Account::default())]),
+            Err(SwapError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    pub fn quote_from_accounts_rejects_truncated_accounts() {
+        let truncations: [(&str, fn(&mut RaydiumAmmAccounts) -> &mut Account); 4] = [
+            ("amm account", |accounts| &mut accounts.amm),
+            ("amm target orders", |accounts| &mut accounts.amm_target),
+            ("amm pc vault", |accounts| &mut accounts.amm_pc_vault),
+            ("amm coin vault", |accounts| &mut accounts.amm_coin_vault),
+        ];
+        for (name, account) in truncations {
+            let (amm_keys, market_keys, mut accounts) = swap_only_pool();
+            let data = &mut account(&mut accounts).data;
+            data.truncate(data.len() / 2);
+
+            match RaydiumAmm::quote_from_accounts(
+                &exact_in(&amm_keys),
+                amm_keys,
+                market_keys,
+                &accounts,
+            ) {
+                Err(SwapError::DecodeError(e)) => assert!(e.starts_with(name), "{}", e),
+                other => panic!("expected {} to fail decoding, got {:?}", name, other),
+            }
+        }
+    }
+
+    #[test]
+    pub fn quote_from_accounts_rejects_invalid_pool_state() {
+        let (amm_keys, market_keys, mut accounts) = swap_only_pool();
+        set_status(&mut accounts, 42);
+        assert!(matches!(
+            RaydiumAmm::quote_from_accounts(&exact_in(&amm_keys), amm_keys, market_keys, &accounts),
+            Err(SwapError::DecodeError(_))
+        ));
+
+        // an orderbook pool whose openbook accounts were not loaded
+        let (amm_keys, market_keys, mut accounts) = swap_only_pool();
+        set_status(
+            &mut accounts,
+            raydium_amm::state::AmmStatus::Initialized as u64,
+        );
+        match RaydiumAmm::quote_from_accounts(
+            &exact_in(&amm_keys),
+            amm_keys,
+            market_keys,
+            &accounts,
+        ) {
+            Err(SwapError::InvalidPoolState(e)) => {
+                assert!(e.starts_with("amm open orders"), "{}", e)
+            }
+            other => panic!("expected open orders to be rejected, got {:?}", other),
+        }
+    }
+
+    #[test]
+    pub fn quote_from_accounts_rejects_exact_out_above_reserves() {
+        let (amm_keys, market_keys, accounts) = swap_only_pool();
+        let swap_input = SwapInput {
+            amount: PC_VAULT_AMOUNT,
+            mode: SwapExecutionMode::ExactOut,
+            ..exact_in(&amm_keys)
+        };
+        assert!(matches!(
+            RaydiumAmm::quote_from_accounts(&swap_input, amm_keys, market_keys, &accounts),
+            Err(SwapError::InsufficientLiquidity(_))
+        ));
+    }
+
+    fn balance(before: u64, after: Option<u64>) -> SimulatedTokenBalance {
+        SimulatedTokenBalance {
+            account: Pubkey::new_unique(),
+            before,
+            after,
+        }
+    }
+
+    #[test]
+    pub fn simulated_amounts_prefer_token_balances() {
+        let mut swap_log = vec![3u8];
+        for field in [1_000u64, 900, 2, 5_000, 100_000, 200_000, 1_990] {
+            swap_log.extend_from_slice(&field.to_le_bytes());
+        }
+        let ray_logs = vec![RayLog::decode(&swap_log).unwrap().unwrap()];
+
+        assert_eq!(
+            simulated_amounts(
+                &[balance(5_000, Some(4_000)), balance(0, Some(1_985))],
+                &ray_logs
+            )
+            .unwrap(),
+            (1_000, 1_985)
+        );
+        // closed wrapped sol accounts fall back to the swap log
+        assert_eq!(
+            simulated_amounts(&[balance(5_000, None), balance(10, Some(2_000))], &ray_logs)
+                .unwrap(),
+            (1_000, 1_990)
+        );
+        assert_eq!(
+            simulated_amounts(&[balance(5_000, Some(4_000)), balance(0, None)], &ray_logs).unwrap(),
+            (1_000, 1_990)
+        );
+    }
+
+    #[test]
+    pub fn simulated_amounts_require_a_swap_log_for_closed_accounts() {
+        assert_eq!(
+            simulated_amounts(&[balance(5_000, Some(4_000)), balance(0, Some(1_985))], &[])
+                .unwrap(),
+            (1_000, 1_985)
+        );
+        assert!(matches!(
+            simulated_amounts(&[balance(5_000, None), balance(0, Some(1_985))], &[]),
+            Err(SwapError::Transaction(_))
+        ));
+        assert!(matches!(
+            simulated_amounts(&[balance(5_000, Some(4_000))], &[]),
+            Err(SwapError::Transaction(_))
+        ));
+    }
+}
@@ -1,2 +1,3 @@
 mod amm_info;
 pub mod executor;
+pub mod pool_selection;
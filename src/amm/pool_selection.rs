@@ -0,0 +1,403 @@
+//! Selection of the amm pool to swap against among the pools listed for a mint pair.
+//!
+//! Listed pools are filtered on what the api reports (mints, program id, open time, tvl) and the
+//! remaining candidates are checked against their on-chain status once loaded. Every rejected
+//! pool is kept with the reason so a failed selection can explain itself.
+
+use crate::api_v3::response::pools::ApiV3BasePool;
+use std::fmt;
+
+use solana_sdk::pubkey::Pubkey;
+
+/// The fields of a listed pool that selection looks at
+#[derive(Debug, Clone)]
+pub struct PoolCandidate {
+    pub id: Pubkey,
+    pub program_id: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    /// Unix timestamp the pool opens for swaps at, `None` if the api reported an invalid value
+    pub open_time: Option<u64>,
+    /// Liquidity in USD
+    pub tvl: f64,
+}
+
+impl<T> From<&ApiV3BasePool<T>> for PoolCandidate {
+    fn from(pool: &ApiV3BasePool<T>) -> Self {
+        Self {
+            id: pool.id,
+            program_id: pool.program_id,
+            mint_a: pool.mint_a.address,
+            mint_b: pool.mint_b.address,
+            open_time: pool.open_time.parse().ok(),
+            tvl: pool.tvl,
+        }
+    }
+}
+
+/// Why a pool was not selected
+#[derive(Debug, Clone, PartialEq)]
+pub enum PoolRejection {
+    /// The pool does not trade the requested mint pair
+    MintMismatch,
+    /// The pool is owned by another program
+    ProgramMismatch { program_id: Pubkey },
+    /// The api reported an open time that is not a unix timestamp
+    InvalidOpenTime,
+    /// The pool opens for swaps in the future
+    NotOpen { open_time: u64, now: u64 },
+    /// The pool's liquidity is below the configured minimum
+    TvlBelowMinimum { tvl: f64, min_tvl: f64 },
+    /// The pool's on-chain status does not permit swaps
+    SwapNotPermitted { status: u64 },
+    /// The pool's accounts could not be loaded
+    LoadFailed(String),
+}
+
+impl fmt::Display for PoolRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolRejection::MintMismatch => write!(f, "pool does not trade the mint pair"),
+            PoolRejection::ProgramMismatch { program_id } => {
+                write!(f, "pool is owned by program {}", program_id)
+            }
+            PoolRejection::InvalidOpenTime => write!(f, "pool has an invalid open time"),
+            PoolRejection::NotOpen { open_time, now } => write!(
+                f,
+                "pool opens at {}, {} seconds from now",
+                open_time,
+                open_time.saturating_sub(*now)
+            ),
+            PoolRejection::TvlBelowMinimum { tvl, min_tvl } => {
+                write!(f, "pool tvl {} is below the minimum of {}", tvl, min_tvl)
+            }
+            PoolRejection::SwapNotPermitted { status } => {
+                write!(f, "pool status {} does not permit swaps", status)
+            }
+            PoolRejection::LoadFailed(e) => write!(f, "failed to load pool: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for PoolRejection {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RejectedPool {
+    pub id: Pubkey,
+    pub reason: PoolRejection,
+}
+
+/// The outcome of filtering listed pools
+#[derive(Debug, Clone, Default)]
+pub struct PoolSelection {
+    /// Pools that passed the listing filters, in listing order
+    pub candidates: Vec<Pubkey>,
+    pub rejected: Vec<RejectedPool>,
+}
+
+impl PoolSelection {
+    pub fn reject(&mut self, id: Pubkey, reason: PoolRejection) {
+        self.rejected.push(RejectedPool { id, reason });
+    }
+
+    /// The error to report when none of the pools could be used
+    pub fn into_error(self, input_mint: Pubkey, output_mint: Pubkey) -> NoTradablePool {
+        NoTradablePool {
+            input_mint,
+            output_mint,
+            rejected: self.rejected,
+        }
+    }
+}
+
+/// None of the pools for a swap can be traded against
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoTradablePool {
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub rejected: Vec<RejectedPool>,
+}
+
+impl fmt::Display for NoTradablePool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.rejected.is_empty() {
+            return write!(
+                f,
+                "Failed to get market for swap: no pools listed for {} -> {}",
+                self.input_mint, self.output_mint
+            );
+        }
+        write!(
+            f,
+            "Failed to get market for swap {} -> {}, all pools were rejected: ",
+            self.input_mint, self.output_mint
+        )?;
+        for (i, rejected) in self.rejected.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}: {}", rejected.id, rejected.reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for NoTradablePool {}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PoolSelector {
+    /// Only pools owned by this program are selected
+    pub program_id: Pubkey,
+    /// Pools with less liquidity (in USD) are rejected
+    pub min_tvl: Option<f64>,
+    /// Pools opening within this many seconds are accepted, e.g. to be ready when a pool launches
+    pub open_within_secs: u64,
+}
+
+impl PoolSelector {
+    pub fn new(program_id: Pubkey) -> Self {
+        Self {
+            program_id,
+            min_tvl: None,
+            open_within_secs: 0,
+        }
+    }
+
+    /// Filters listed pools for a swap between the mints at unix timestamp `now`, keeping the
+    /// listing order of the pools that pass
+    pub fn select(
+        &self,
+        pools: impl IntoIterator<Item = PoolCandidate>,
+        input_mint: &Pubkey,
+        output_mint: &Pubkey,
+        now: u64,
+    ) -> PoolSelection {
+        let mut selection = PoolSelection::default();
+        for pool in pools {
+            match self.check_listing(&pool, input_mint, output_mint, now) {
+                Ok(()) => selection.candidates.push(pool.id),
+                Err(reason) => selection.reject(pool.id, reason),
+            }
+        }
+        selection
+    }
+
+    pub fn check_listing(
+        &self,
+        pool: &PoolCandidate,
+        input_mint: &Pubkey,
+        output_mint: &Pubkey,
+        now: u64,
+    ) -> Result<(), PoolRejection> {
+        let matches_mints = (pool.mint_a == *input_mint && pool.mint_b == *output_mint)
+            || (pool.mint_a == *output_mint && pool.mint_b == *input_mint);
+        if !matches_mints {
+            return Err(PoolRejection::MintMismatch);
+        }
+        if pool.program_id != self.program_id {
+            return Err(PoolRejection::ProgramMismatch {
+                program_id: pool.program_id,
+            });
+        }
+        self.check_open_time(pool.open_time.ok_or(PoolRejection::InvalidOpenTime)?, now)?;
+        if let Some(min_tvl) = self.min_tvl {
+            if pool.tvl < min_tvl {
+                return Err(PoolRejection::TvlBelowMinimum {
+                    tvl: pool.tvl,
+                    min_tvl,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the on-chain state of a loaded amm pool against the cluster's unix timestamp
+    pub fn check_tradable(
+        &self,
+        status: u64,
+        pool_open_time: u64,
+        now: u64,
+    ) -> Result<(), PoolRejection> {
+        Self::check_status(status)?;
+        self.check_open_time(pool_open_time, now)
+    }
+
+    /// Checks that an amm pool status permits swaps
+    pub fn check_status(status: u64) -> Result<(), PoolRejection> {
+        if !raydium_amm::state::AmmStatus::valid_status(status)
+            || !raydium_amm::state::AmmStatus::from_u64(status).swap_permission()
+        {
+            return Err(PoolRejection::SwapNotPermitted { status });
+        }
+        Ok(())
+    }
+
+    fn check_open_time(&self, open_time: u64, now: u64) -> Result<(), PoolRejection> {
+        if open_time > now.saturating_add(self.open_within_secs) {
+            return Err(PoolRejection::NotOpen { open_time, now });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amm::executor::RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID;
+    use crate::cpmm::executor::RAYDIUM_CPMM_PROGRAM_ID;
+
+    fn candidate(program_id: Pubkey, mint_a: Pubkey, mint_b: Pubkey) -> PoolCandidate {
+        PoolCandidate {
+            id: Pubkey::new_unique(),
+            program_id,
+            mint_a,
+            mint_b,
+            open_time: Some(0),
+            tvl: 1_000_000.0,
+        }
+    }
+
+    #[test]
+    pub fn select_rejects_other_programs_in_both_orderings() {
+        let (input_mint, output_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let forward_cpmm = candidate(RAYDIUM_CPMM_PROGRAM_ID, input_mint, output_mint);
+        let reversed_cpmm = candidate(RAYDIUM_CPMM_PROGRAM_ID, output_mint, input_mint);
+        let amm = candidate(
+            RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
+            output_mint,
+            input_mint,
+        );
+        let other_pair = candidate(
+            RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
+            input_mint,
+            Pubkey::new_unique(),
+        );
+
+        let selection = PoolSelector::new(RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID).select(
+            [
+                forward_cpmm.clone(),
+                reversed_cpmm.clone(),
+                amm.clone(),
+                other_pair.clone(),
+            ],
+            &input_mint,
+            &output_mint,
+            1_000,
+        );
+        assert_eq!(selection.candidates, vec![amm.id]);
+        assert_eq!(
+            selection.rejected,
+            vec![
+                RejectedPool {
+                    id: forward_cpmm.id,
+                    reason: PoolRejection::ProgramMismatch {
+                        program_id: RAYDIUM_CPMM_PROGRAM_ID
+                    },
+                },
+                RejectedPool {
+                    id: reversed_cpmm.id,
+                    reason: PoolRejection::ProgramMismatch {
+                        program_id: RAYDIUM_CPMM_PROGRAM_ID
+                    },
+                },
+                RejectedPool {
+                    id: other_pair.id,
+                    reason: PoolRejection::MintMismatch,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    pub fn select_rejects_unopened_and_shallow_pools() {
+        let (input_mint, output_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let selector = PoolSelector {
+            min_tvl: Some(10_000.0),
+            ..PoolSelector::new(RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID)
+        };
+        let mut unopened = candidate(
+            RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
+            input_mint,
+            output_mint,
+        );
+        unopened.open_time = Some(2_000);
+        let mut invalid_open_time = candidate(
+            RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
+            input_mint,
+            output_mint,
+        );
+        invalid_open_time.open_time = None;
+        let mut shallow = candidate(
+            RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
+            input_mint,
+            output_mint,
+        );
+        shallow.tvl = 500.0;
+
+        let now = 1_000;
+        assert_eq!(
+            selector.check_listing(&unopened, &input_mint, &output_mint, now),
+            Err(PoolRejection::NotOpen {
+                open_time: 2_000,
+                now
+            })
+        );
+        assert_eq!(
+            selector.check_listing(&invalid_open_time, &input_mint, &output_mint, now),
+            Err(PoolRejection::InvalidOpenTime)
+        );
+        assert_eq!(
+            selector.check_listing(&shallow, &input_mint, &output_mint, now),
+            Err(PoolRejection::TvlBelowMinimum {
+                tvl: 500.0,
+                min_tvl: 10_000.0
+            })
+        );
+
+        let selection = selector.select([unopened, shallow], &input_mint, &output_mint, now);
+        assert!(selection.candidates.is_empty());
+        let error = selection.into_error(input_mint, output_mint).to_string();
+        assert!(error.contains("opens at 2000"));
+        assert!(error.contains("below the minimum"));
+    }
+
+    #[test]
+    pub fn check_status_requires_swap_permission() {
+        use raydium_amm::state::AmmStatus;
+        assert!(PoolSelector::check_status(AmmStatus::Initialized as u64).is_ok());
+        assert!(PoolSelector::check_status(AmmStatus::SwapOnly as u64).is_ok());
+        assert_eq!(
+            PoolSelector::check_status(AmmStatus::Disabled as u64),
+            Err(PoolRejection::SwapNotPermitted {
+                status: AmmStatus::Disabled as u64
+            })
+        );
+        assert!(PoolSelector::check_status(AmmStatus::WithdrawOnly as u64).is_err());
+        assert!(PoolSelector::check_status(42).is_err());
+    }
+
+    #[test]
+    pub fn check_tradable_allows_opening_within_window() {
+        use raydium_amm::state::AmmStatus;
+        let status = AmmStatus::SwapOnly as u64;
+        let mut selector = PoolSelector::new(RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID);
+        assert!(selector.check_tradable(status, 1_000, 1_000).is_ok());
+        assert_eq!(
+            selector.check_tradable(status, 1_030, 1_000),
+            Err(PoolRejection::NotOpen {
+                open_time: 1_030,
+                now: 1_000
+            })
+        );
+        assert_eq!(
+            selector.check_tradable(AmmStatus::Disabled as u64, 0, 1_000),
+            Err(PoolRejection::SwapNotPermitted {
+                status: AmmStatus::Disabled as u64
+            })
+        );
+
+        selector.open_within_secs = 30;
+        assert!(selector.check_tradable(status, 1_030, 1_000).is_ok());
+        assert!(selector.check_tradable(status, 1_031, 1_000).is_err());
+    }
+}
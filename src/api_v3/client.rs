@@ -1,5 +1,6 @@
-use super::response::{ApiV3PoolsPage, ApiV3Token, ApiV3TokenList};
+use super::response::{ApiV3PoolKeys, ApiV3PoolsPage, ApiV3Token, ApiV3TokenList};
 use super::{handle_response_or_error, PoolFetchParams};
+use crate::error::SwapError;
 use serde::de::DeserializeOwned;
 use solana_sdk::pubkey::Pubkey;
 
@@ -25,14 +26,14 @@ impl ApiV3Client {
         }
     }
 
-    pub async fn get_token_list(&self) -> Result<ApiV3TokenList, anyhow::Error> {
+    pub async fn get_token_list(&self) -> Result<ApiV3TokenList, SwapError> {
         let url = format!("{}/mint/list", &self.base_url);
         Ok(handle_response_or_error(reqwest::get(url).await?)
             .await?
             .data)
     }
 
-    pub async fn get_jup_token_list(&self) -> Result<Vec<ApiV3Token>, anyhow::Error> {
+    pub async fn get_jup_token_list(&self) -> Result<Vec<ApiV3Token>, SwapError> {
         Ok(
             reqwest::get("https://tokens.jup.ag/tokens?tags=lst,community")
                 .await?
@@ -41,10 +42,7 @@ impl ApiV3Client {
         )
     }
 
-    pub async fn get_token_info(
-        &self,
-        mints: Vec<String>,
-    ) -> Result<Vec<ApiV3Token>, anyhow::Error> {
+    pub async fn get_token_info(&self, mints: Vec<String>) -> Result<Vec<ApiV3Token>, SwapError> {
         let mints = mints.join(",");
         let url = format!("{}/mint/ids?mints={}", &self.base_url, mints);
         Ok(handle_response_or_error(reqwest::get(url).await?)
@@ -55,7 +53,7 @@ impl ApiV3Client {
     pub async fn get_pool_list<T: DeserializeOwned>(
         &self,
         params: &PoolFetchParams,
-    ) -> Result<ApiV3PoolsPage<T>, anyhow::Error> {
+    ) -> Result<ApiV3PoolsPage<T>, SwapError> {
         let url = format!(
             "{}/pools/info/list?poolType={}&poolSortField={}&sortType={}&page={}&pageSize={}",
             &self.base_url,
@@ -73,7 +71,7 @@ impl ApiV3Client {
     pub async fn fetch_pools_by_ids<T: DeserializeOwned>(
         &self,
         ids: Vec<String>,
-    ) -> Result<Vec<T>, anyhow::Error> {
+    ) -> Result<Vec<T>, SwapError> {
         let ids = ids.join(",");
         let url = format!("{}/pools/info/ids?ids={}", &self.base_url, ids);
         Ok(handle_response_or_error(reqwest::get(url).await?)
@@ -84,7 +82,7 @@ impl ApiV3Client {
     pub async fn fetch_pool_keys_by_ids<T: DeserializeOwned>(
         &self,
         ids: Vec<String>,
-    ) -> Result<Vec<T>, anyhow::Error> {
+    ) -> Result<Vec<T>, SwapError> {
         let ids = ids.join(",");
         let url = format!("{}/pools/key/ids?ids={}", &self.base_url, ids);
         Ok(handle_response_or_error(reqwest::get(url).await?)
@@ -92,12 +90,31 @@ impl ApiV3Client {
             .data)
     }
 
+    /// Returns the address lookup tables raydium maintains for the pools. Pools without one are
+    /// skipped.
+    pub async fn fetch_pool_lookup_tables(
+        &self,
+        pool_ids: &[Pubkey],
+    ) -> Result<Vec<Pubkey>, SwapError> {
+        if pool_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let keys: Vec<ApiV3PoolKeys> = self
+            .fetch_pool_keys_by_ids(pool_ids.iter().map(|id| id.to_string()).collect())
+            .await?;
+        Ok(keys
+            .into_iter()
+            .filter_map(|keys| keys.lookup_table_account)
+            .filter(|address| *address != Pubkey::default())
+            .collect())
+    }
+
     pub async fn fetch_pool_by_mints<T: DeserializeOwned>(
         &self,
         mint1: &Pubkey,
         mint2: Option<&Pubkey>,
         params: &PoolFetchParams,
-    ) -> Result<ApiV3PoolsPage<T>, anyhow::Error> {
+    ) -> Result<ApiV3PoolsPage<T>, SwapError> {
         let url = format!(
             "{}/pools/info/mint?mint1={}&mint2={}&poolType={}&poolSortField={}&sortType={}&pageSize={}&page={}",
             &self.base_url,
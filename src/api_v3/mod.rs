@@ -2,12 +2,12 @@ mod client;
 pub mod response;
 mod serde_helpers;
 
-use anyhow::Context;
+use crate::error::SwapError;
 pub use client::ApiV3Client;
 use response::ApiV3Response;
 use serde::{Deserialize, Serialize};
 
-pub type ApiV3Result<T> = Result<ApiV3Response<T>, anyhow::Error>;
+pub type ApiV3Result<T> = Result<ApiV3Response<T>, SwapError>;
 
 #[derive(Clone, Debug, Deserialize)]
 pub struct ApiV3ErrorResponse {
@@ -26,9 +26,7 @@ impl std::fmt::Display for ApiV3ErrorResponse {
 }
 impl std::error::Error for ApiV3ErrorResponse {}
 
-async fn handle_response_or_error<T>(
-    response: reqwest::Response,
-) -> Result<ApiV3Response<T>, anyhow::Error>
+async fn handle_response_or_error<T>(response: reqwest::Response) -> ApiV3Result<T>
 where
     T: serde::de::DeserializeOwned,
 {
@@ -37,7 +35,7 @@ where
     let success = json
         .get("success")
         .and_then(|v| v.as_bool())
-        .context("Invalid api response")?;
+        .ok_or_else(|| SwapError::DecodeError("api response without a success flag".to_string()))?;
 
     if success {
         Ok(serde_json::from_value::<ApiV3Response<T>>(json)?)
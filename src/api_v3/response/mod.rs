@@ -2,8 +2,8 @@ pub mod pools;
 pub mod token;
 
 use pools::{
-    ApiV3BasePool, ApiV3BasePoolKeys, _ApiV3ClmmPool, _ApiV3ClmmPoolKeys, _ApiV3StandardPool,
-    _ApiV3StandardPoolKeys,
+    ApiV3BasePool, ApiV3BasePoolKeys, _ApiV3ClmmPool, _ApiV3ClmmPoolKeys, _ApiV3CpmmPool,
+    _ApiV3CpmmPoolKeys, _ApiV3StandardPool, _ApiV3StandardPoolKeys,
 };
 use serde::{Deserialize, Serialize};
 pub use token::ApiV3Token;
@@ -42,3 +42,13 @@ pub type ApiV3StandardPoolsPage = ApiV3PoolsPage<ApiV3StandardPool>;
 pub type ApiV3ClmmPool = ApiV3BasePool<_ApiV3ClmmPool>;
 pub type ApiV3ClmmPoolKeys = ApiV3BasePoolKeys<_ApiV3ClmmPoolKeys>;
 pub type ApiV3ClmmPoolsPage = ApiV3PoolsPage<ApiV3ClmmPool>;
+
+pub type ApiV3CpmmPool = ApiV3BasePool<_ApiV3CpmmPool>;
+pub type ApiV3CpmmPoolKeys = ApiV3BasePoolKeys<_ApiV3CpmmPoolKeys>;
+pub type ApiV3CpmmPoolsPage = ApiV3PoolsPage<ApiV3CpmmPool>;
+
+/// A pool of any kind with only the fields common to every pool type decoded. The program id
+/// tells the pool kinds apart.
+pub type ApiV3Pool = ApiV3BasePool<serde_json::Value>;
+pub type ApiV3AnyPoolsPage = ApiV3PoolsPage<ApiV3Pool>;
+pub type ApiV3PoolKeys = ApiV3BasePoolKeys<serde_json::Value>;
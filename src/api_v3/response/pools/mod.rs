@@ -5,4 +5,5 @@ pub mod standard;
 
 pub use base::{ApiV3BasePool, ApiV3BasePoolKeys};
 pub use clmm::{_ApiV3ClmmPool, _ApiV3ClmmPoolKeys};
+pub use cpmm::{_ApiV3CpmmPool, _ApiV3CpmmPoolKeys};
 pub use standard::{_ApiV3StandardPool, _ApiV3StandardPoolKeys};
@@ -80,7 +80,7 @@ pub enum ApiV3TokenTag {
 }
 
 impl std::str::FromStr for ApiV3TokenTag {
-    type Err = anyhow::Error;
+    type Err = std::convert::Infallible;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
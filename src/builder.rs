@@ -1,14 +1,32 @@
-use crate::types::{ComputeUnitLimits, PriorityFeeConfig};
-use anyhow::Context;
+use crate::amm::executor::RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID;
+use crate::api_v3::ApiV3Client;
+use crate::error::{Result, SimulationFailure, SwapError};
+use crate::logs::{parse_simulation_ray_logs, RayLog};
+use crate::priority_fee::PriorityFeeEstimator;
+use crate::types::{
+    ComputeUnitLimits, ComputeUnitMargin, CuPrice, DurableNonce, DynamicPriorityFee,
+    PriorityFeeConfig, ResolvedSwapConfig, MAX_COMPUTE_UNIT_LIMIT,
+};
 use rand::Rng;
+use raydium_amm::error::AmmError;
+use solana_account_decoder::UiAccountEncoding;
 use solana_client::nonblocking::rpc_client::RpcClient;
-use solana_client::rpc_config::RpcSimulateTransactionConfig;
-use solana_program::message::{Message, VersionedMessage};
+use solana_client::rpc_config::{
+    RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig,
+};
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_program::message::{v0, Message, VersionedMessage};
+use solana_sdk::account::Account;
+use solana_sdk::address_lookup_table::state::AddressLookupTable;
+use solana_sdk::address_lookup_table::AddressLookupTableAccount;
 use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::decode_error::DecodeError;
 use solana_sdk::hash::Hash;
-use solana_sdk::instruction::Instruction;
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::nonce;
+use solana_sdk::packet::PACKET_DATA_SIZE;
 use solana_sdk::signature::Signature;
-use solana_sdk::transaction::VersionedTransaction;
+use solana_sdk::transaction::{TransactionError, VersionedTransaction};
 use solana_sdk::{pubkey, pubkey::Pubkey};
 
 /// Protocol defined: The default compute units set for a transaction
@@ -20,29 +38,164 @@ const MICRO_LAMPORTS_PER_LAMPORT: u64 = 1_000_000;
 pub struct SwapInstructionsBuilder {
     pub compute_budget_instructions: Vec<Instruction>,
     pub setup_instructions: Vec<Instruction>,
-    pub swap_instruction: Option<Instruction>,
-    pub cleanup_instruction: Option<Instruction>,
+    pub swap_instructions: Vec<Instruction>,
+    pub cleanup_instructions: Vec<Instruction>,
     pub address_lookup_table_addresses: Vec<Pubkey>,
+    /// The lookup tables resolved from `address_lookup_table_addresses`. Transactions are compiled
+    /// to v0 messages using these tables when set, and to legacy messages otherwise
+    pub address_lookup_tables: Option<Vec<AddressLookupTableAccount>>,
+    /// The durable nonce and its current value. When set, the transaction starts by advancing
+    /// the nonce and uses the nonce value as its blockhash
+    pub durable_nonce: Option<(DurableNonce, Hash)>,
+    /// The result of the compute unit simulation, with the program logs of the swap
+    pub simulation: Option<RpcSimulateTransactionResult>,
+    /// Token accounts whose balances are read before and after the simulation, into
+    /// `simulated_token_balances`
+    pub simulated_token_accounts: Vec<Pubkey>,
+    pub simulated_token_balances: Vec<SimulatedTokenBalance>,
+}
+
+/// Fails the simulation of an exact-in swap if `account` receives less than `threshold`.
+/// If the swap closes `account`, the amount is read from the amm's swap logs, and the simulation
+/// fails if there are none.
+#[derive(Copy, Clone, Debug)]
+pub struct SimulatedOutputCheck {
+    pub account: Pubkey,
+    pub threshold: u64,
+}
+
+/// A token account's balance before and after the simulated transaction
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SimulatedTokenBalance {
+    pub account: Pubkey,
+    pub before: u64,
+    /// `None` if the transaction closes the account
+    pub after: Option<u64>,
 }
 
 pub struct UserAssociatedTokenAccounts {
+    /// The account the swap spends from, the source token account override or the user's ATA
     pub input_ata: Pubkey,
+    /// The account the swap pays into, the destination token account override or the user's ATA
     pub output_ata: Pubkey,
 }
 
+/// A swap for [`SwapInstructionsBuilder::build_swap`] to build
+pub struct SwapPlan<'a> {
+    /// The swapping wallet
+    pub user: Pubkey,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    pub input_token_program: Pubkey,
+    pub output_token_program: Pubkey,
+    /// The most the swap spends, wrapped into the input account when swapping from SOL
+    pub max_amount_in: u64,
+    /// The pools the swap trades on, whose lookup tables compile v0 transactions
+    pub pool_ids: &'a [Pubkey],
+    /// The least the swap pays into the output account, checked by simulation when
+    /// `check_simulated_output` is set. `None` for exact-out swaps
+    pub min_amount_out: Option<u64>,
+}
+
 impl SwapInstructionsBuilder {
+    /// Builds the swap `plan` with `config`. Checks the user's token accounts, creates and wraps
+    /// them, adds the instructions of `swap_instructions` between them, then loads the lookup
+    /// tables and durable nonce and sets the compute units and priority fee.
+    pub async fn build_swap(
+        config: &ResolvedSwapConfig,
+        plan: SwapPlan<'_>,
+        api: &ApiV3Client,
+        rpc_client: &RpcClient,
+        priority_fee_estimator: &dyn PriorityFeeEstimator,
+        swap_instructions: impl FnOnce(&mut Self, &UserAssociatedTokenAccounts) -> Result<()>,
+    ) -> Result<Self> {
+        crate::utils::check_token_accounts(
+            rpc_client,
+            &plan.user,
+            &[
+                (
+                    config.source_token_account,
+                    plan.input_mint,
+                    plan.input_token_program,
+                ),
+                (
+                    config.destination_token_account,
+                    plan.output_mint,
+                    plan.output_token_program,
+                ),
+            ],
+        )
+        .await?;
+        let mut builder = Self::default();
+        let associated_accounts = builder.handle_token_wrapping_and_accounts_creation(
+            plan.user,
+            config.fee_payer,
+            config.wrap_and_unwrap_sol,
+            plan.max_amount_in,
+            plan.input_mint,
+            plan.output_mint,
+            plan.input_token_program,
+            plan.output_token_program,
+            config.source_token_account,
+            config.destination_token_account,
+        )?;
+        swap_instructions(&mut builder, &associated_accounts)?;
+
+        builder
+            .handle_address_lookup_tables(
+                config.as_legacy_transaction,
+                plan.pool_ids,
+                &config.address_lookup_tables,
+                api,
+                rpc_client,
+            )
+            .await?;
+        builder
+            .handle_durable_nonce(config.durable_nonce, rpc_client)
+            .await?;
+        let output_check = plan
+            .min_amount_out
+            .filter(|_| config.check_simulated_output)
+            .map(|threshold| SimulatedOutputCheck {
+                account: associated_accounts.output_ata,
+                threshold,
+            });
+        let compute_units = builder
+            .handle_compute_units_params(
+                config.cu_limits,
+                rpc_client,
+                config.fee_payer,
+                output_check,
+            )
+            .await?;
+        builder
+            .handle_priority_fee_params(
+                config.priority_fee,
+                compute_units,
+                config.fee_payer,
+                priority_fee_estimator,
+            )
+            .await?;
+
+        Ok(builder)
+    }
+
     #[allow(clippy::too_many_arguments)]
+    /// Creates the user's token accounts funded by `payer`, and wraps and unwraps SOL for the
+    /// user. Closed wrapped SOL accounts are refunded to the user, not the payer.
     pub fn handle_token_wrapping_and_accounts_creation(
         &mut self,
         user: Pubkey,
+        payer: Pubkey,
         wrap_and_unwrap_sol: bool,
         input_amount: u64,
         input_mint: Pubkey,
         output_mint: Pubkey,
         input_token_program: Pubkey,
         output_token_program: Pubkey,
+        source_token_account: Option<Pubkey>,
         destination_token_account: Option<Pubkey>,
-    ) -> anyhow::Result<UserAssociatedTokenAccounts> {
+    ) -> Result<UserAssociatedTokenAccounts> {
         let user_input_ata =
             spl_associated_token_account::get_associated_token_address_with_program_id(
                 &user,
@@ -56,11 +209,12 @@ impl SwapInstructionsBuilder {
                 &output_token_program,
             );
 
-        if input_mint == spl_token::native_mint::ID {
+        // A source-token-account override is used as is, without creating, wrapping into or closing it
+        if input_mint == spl_token::native_mint::ID && source_token_account.is_none() {
             // Only create an input-ata if it's the native mint
             let create_ata_ix =
                 spl_associated_token_account::instruction::create_associated_token_account_idempotent(
-                    &user,
+                    &payer,
                     &user,
                     &input_mint,
                     &spl_token::ID, // SOL uses token-22
@@ -83,7 +237,7 @@ impl SwapInstructionsBuilder {
                     &[],
                 )
                 .expect("spl_token::ID is valid");
-                self.cleanup_instruction = Some(close_ix);
+                self.cleanup_instructions.push(close_ix);
             }
         }
 
@@ -92,7 +246,7 @@ impl SwapInstructionsBuilder {
             // already initialized.
             let create_ata_ix =
                 spl_associated_token_account::instruction::create_associated_token_account_idempotent(
-                    &user,
+                    &payer,
                     &user,
                     &output_mint,
                     &output_token_program,
@@ -100,7 +254,7 @@ impl SwapInstructionsBuilder {
             self.setup_instructions.push(create_ata_ix);
 
             if wrap_and_unwrap_sol && output_mint == spl_token::native_mint::ID {
-                self.cleanup_instruction = Some(
+                self.cleanup_instructions.push(
                     spl_token::instruction::close_account(
                         &spl_token::ID,
                         &user_output_ata,
@@ -114,32 +268,48 @@ impl SwapInstructionsBuilder {
         }
 
         Ok(UserAssociatedTokenAccounts {
-            input_ata: user_input_ata,
-            output_ata: user_output_ata,
+            input_ata: source_token_account.unwrap_or(user_input_ata),
+            output_ata: destination_token_account.unwrap_or(user_output_ata),
         })
     }
 
-    pub fn handle_priority_fee_params(
+    /// Sets the cu price and adds the jito tip of `priority_fee_config`. Dynamic cu prices are
+    /// estimated by `estimator` from the swap's writable accounts.
+    pub async fn handle_priority_fee_params(
         &mut self,
         priority_fee_config: Option<PriorityFeeConfig>,
         compute_units: Option<u32>,
         funder: Pubkey,
-    ) -> anyhow::Result<()> {
+        estimator: &dyn PriorityFeeEstimator,
+    ) -> Result<()> {
         let compute_units = compute_units.unwrap_or(DEFAULT_INSTRUCTION_COMPUTE_UNIT);
         log::debug!("Prioritization fee config: {priority_fee_config:#?}");
-        match priority_fee_config {
+        let (cu_price, jito_tip) = match priority_fee_config {
             Some(PriorityFeeConfig::FixedCuPrice(cu_price)) => {
-                log::trace!("setting user defined cu-price: {}", cu_price);
-                let compute_ix =
-                    solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(
-                        cu_price,
-                    );
-                self.compute_budget_instructions.push(compute_ix);
+                (Some(CuPrice::Fixed(cu_price)), None)
             }
             Some(PriorityFeeConfig::DynamicMultiplier(multiplier)) => {
-                let priofee = multiplier
-                    .checked_mul(100_000)
-                    .context("Overflow error while calculating priofee auto-multiplier")?;
+                (Some(CuPrice::DynamicMultiplier(multiplier)), None)
+            }
+            Some(PriorityFeeConfig::Dynamic(config)) => (Some(CuPrice::Dynamic(config)), None),
+            Some(PriorityFeeConfig::JitoTip(jito_tip)) => (None, Some(jito_tip)),
+            Some(PriorityFeeConfig::CuPriceAndJitoTip { cu_price, jito_tip }) => {
+                (Some(cu_price), Some(jito_tip))
+            }
+            None => (None, None),
+        };
+
+        let cu_price = match cu_price {
+            Some(CuPrice::Fixed(cu_price)) => {
+                log::trace!("setting user defined cu-price: {}", cu_price);
+                Some(cu_price)
+            }
+            Some(CuPrice::DynamicMultiplier(multiplier)) => {
+                let priofee = multiplier.checked_mul(100_000).ok_or_else(|| {
+                    SwapError::Math(
+                        "Overflow error while calculating priofee auto-multiplier".to_string(),
+                    )
+                })?;
                 let cu_price = calculate_cu_price(priofee, compute_units);
                 log::trace!(
                     "prioritization-fee-lamports: cu-price={}, multiplier={}. priofee={}, cu-limit={}",
@@ -148,49 +318,102 @@ impl SwapInstructionsBuilder {
                     priofee,
                     compute_units
                 );
-                let compute_ix =
-                    solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(
-                        cu_price,
-                    );
-                self.compute_budget_instructions.push(compute_ix);
+                Some(cu_price)
             }
-            Some(PriorityFeeConfig::JitoTip(jito_tip)) => {
-                let tip_ix = build_jito_tip_ix(&funder, jito_tip);
-                self.setup_instructions.push(tip_ix);
+            Some(CuPrice::Dynamic(config)) => {
+                let cu_price = self.dynamic_cu_price(config, estimator).await?;
+                log::trace!(
+                    "dynamic cu-price={}, percentile={}",
+                    cu_price,
+                    config.percentile
+                );
+                Some(cu_price)
             }
-            None => {}
+            None => None,
+        };
+        if let Some(cu_price) = cu_price {
+            let compute_ix =
+                solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_price(
+                    cu_price,
+                );
+            self.compute_budget_instructions.push(compute_ix);
+        }
+        if let Some(jito_tip) = jito_tip {
+            let tip_ix = build_jito_tip_ix(&funder, jito_tip);
+            self.setup_instructions.push(tip_ix);
         }
 
         Ok(())
     }
 
+    /// The cu price paid by recent transactions contending for the swap's writable accounts,
+    /// such as the pool, its vaults and open orders
+    async fn dynamic_cu_price(
+        &self,
+        config: DynamicPriorityFee,
+        estimator: &dyn PriorityFeeEstimator,
+    ) -> Result<u64> {
+        if config.percentile > 100 || config.min_cu_price > config.max_cu_price {
+            return Err(SwapError::InvalidInput(format!(
+                "Invalid dynamic priority fee {:?}",
+                config
+            )));
+        }
+        let mut accounts = Vec::new();
+        for account in self
+            .swap_instructions
+            .iter()
+            .flat_map(|ix| &ix.accounts)
+            .filter(|account| account.is_writable && !account.is_signer)
+        {
+            if !accounts.contains(&account.pubkey) {
+                accounts.push(account.pubkey);
+            }
+        }
+
+        let cu_price = estimator
+            .estimate_cu_price(&accounts, config.percentile)
+            .await?;
+        Ok(cu_price.clamp(config.min_cu_price, config.max_cu_price))
+    }
+
+    /// Sets the compute unit limit. Dynamic limits, an output check or simulated token accounts
+    /// simulate the transaction, which fails with [`SwapError::Simulation`] if the swap would
+    /// fail, or with [`SwapError::SlippageExceeded`] if `output_check` is set and the swap would
+    /// pay out less than its threshold.
     pub async fn handle_compute_units_params(
         &mut self,
         compute_limits: Option<ComputeUnitLimits>,
         rpc_client: &RpcClient,
         payer: Pubkey,
-    ) -> anyhow::Result<Option<u32>> {
-        let cu_limit = match compute_limits {
-            None => None,
-            Some(ComputeUnitLimits::Dynamic) => {
-                let simulate_txn = self.clone().build_transaction(Some(&payer), None)?;
-                let result = rpc_client
-                    .simulate_transaction_with_config(
-                        &simulate_txn,
-                        RpcSimulateTransactionConfig {
-                            sig_verify: false,
-                            replace_recent_blockhash: true,
-                            commitment: Some(CommitmentConfig::confirmed()),
-                            ..Default::default()
-                        },
-                    )
-                    .await?;
+        output_check: Option<SimulatedOutputCheck>,
+    ) -> Result<Option<u32>> {
+        let margin = match compute_limits {
+            Some(ComputeUnitLimits::Dynamic) => Some(ComputeUnitMargin::default()),
+            Some(ComputeUnitLimits::DynamicWithMargin(margin)) => Some(margin),
+            Some(ComputeUnitLimits::Fixed(_)) | None => None,
+        };
+        let simulated_units = if margin.is_some()
+            || output_check.is_some()
+            || !self.simulated_token_accounts.is_empty()
+        {
+            self.simulate(rpc_client, payer, output_check).await?
+        } else {
+            None
+        };
 
-                result.value.units_consumed.and_then(|compute_units| {
-                    u32::try_from(compute_units).ok()?.checked_add(50_000)
-                })
+        let cu_limit = match compute_limits {
+            Some(ComputeUnitLimits::Fixed(cu_limits)) => {
+                Some(u32::try_from(cu_limits).map_err(|_| {
+                    SwapError::InvalidInput(format!(
+                        "Compute unit limit {} is too large",
+                        cu_limits
+                    ))
+                })?)
             }
-            Some(ComputeUnitLimits::Fixed(cu_limits)) => Some(u32::try_from(cu_limits)?),
+            _ => margin
+                .zip(simulated_units)
+                .map(|(margin, units)| margin.apply(units)),
         };
 
         if let Some(cu_limit) = cu_limit {
@@ -204,38 +427,280 @@ impl SwapInstructionsBuilder {
         Ok(cu_limit)
     }
 
-    pub fn build_instructions(self) -> anyhow::Result<Vec<Instruction>> {
+    /// Simulates the transaction and returns the compute units it consumed. The balances of
+    /// `simulated_token_accounts` and the checked output account are read before and after.
+    async fn simulate(
+        &mut self,
+        rpc_client: &RpcClient,
+        payer: Pubkey,
+        output_check: Option<SimulatedOutputCheck>,
+    ) -> Result<Option<u64>> {
+        let mut token_accounts = self.simulated_token_accounts.clone();
+        if let Some(check) = output_check {
+            if !token_accounts.contains(&check.account) {
+                token_accounts.push(check.account);
+            }
+        }
+        let balances_before = if token_accounts.is_empty() {
+            vec![]
+        } else {
+            rpc_client
+                .get_multiple_accounts_with_commitment(
+                    &token_accounts,
+                    CommitmentConfig::confirmed(),
+                )
+                .await?
+                .value
+                .iter()
+                .zip(&token_accounts)
+                .map(|(account, address)| {
+                    crate::utils::token_account_amount(address, account.as_ref())
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        // the swap runs with the most compute units a transaction may use, so its usage is
+        // measured instead of cut off at the default limit. A durable nonce advance is simulated
+        // along with the swap, replacing the blockhash lets the simulation run without the nonce
+        // value being recent
+        let mut simulation = self.clone();
+        simulation.compute_budget_instructions.push(
+            solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(
+                MAX_COMPUTE_UNIT_LIMIT,
+            ),
+        );
+        let simulate_txn = simulation.build_transaction(Some(&payer), None)?;
+        let result = rpc_client
+            .simulate_transaction_with_config(
+                &simulate_txn,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    accounts: (!token_accounts.is_empty()).then(|| {
+                        RpcSimulateTransactionAccountsConfig {
+                            encoding: Some(UiAccountEncoding::Base64),
+                            addresses: token_accounts.iter().map(Pubkey::to_string).collect(),
+                        }
+                    }),
+                    ..Default::default()
+                },
+            )
+            .await?
+            .value;
+
+        if let Some(err) = result.err.clone() {
+            return Err(SimulationFailure {
+                amm_error: decode_amm_error(&simulate_txn, &err),
+                err,
+                logs: result.logs.unwrap_or_default(),
+            }
+            .into());
+        }
+
+        let accounts_after = result.accounts.clone().unwrap_or_default();
+        let mut balances = Vec::with_capacity(token_accounts.len());
+        for (index, (account, before)) in token_accounts.iter().zip(balances_before).enumerate() {
+            // closed accounts come back empty, or not at all
+            let after = accounts_after
+                .get(index)
+                .cloned()
+                .flatten()
+                .and_then(|account| account.decode::<Account>())
+                .filter(|account| !account.data.is_empty())
+                .map(|post| crate::utils::token_account_amount(account, Some(&post)))
+                .transpose()?;
+            balances.push(SimulatedTokenBalance {
+                account: *account,
+                before,
+                after,
+            });
+        }
+        if let Some(check) = output_check {
+            let received = simulated_received(
+                &balances,
+                &check.account,
+                &parse_simulation_ray_logs(&result)?,
+            )?;
+            if received < check.threshold {
+                return Err(SwapError::SlippageExceeded {
+                    expected: received,
+                    threshold: check.threshold,
+                });
+            }
+        }
+
+        let units = result.units_consumed;
+        self.simulation = Some(result);
+        self.simulated_token_balances = balances;
+        Ok(units)
+    }
+
+    /// Loads the current value of `durable_nonce`, or clears it to build with a recent blockhash
+    pub async fn handle_durable_nonce(
+        &mut self,
+        durable_nonce: Option<DurableNonce>,
+        rpc_client: &RpcClient,
+    ) -> Result<()> {
+        let Some(durable_nonce) = durable_nonce else {
+            self.durable_nonce = None;
+            return Ok(());
+        };
+
+        let account = rpc_client
+            .get_account_with_commitment(&durable_nonce.account, CommitmentConfig::confirmed())
+            .await?
+            .value
+            .ok_or_else(|| {
+                SwapError::AccountMissing(format!("nonce account {}", durable_nonce.account))
+            })?;
+        self.durable_nonce = Some((durable_nonce, nonce_blockhash(&durable_nonce, &account)?));
+
+        Ok(())
+    }
+
+    /// Loads the lookup tables of `pool_ids` along with `address_lookup_tables` to build v0
+    /// transactions, or clears the loaded tables to build legacy transactions
+    pub async fn handle_address_lookup_tables(
+        &mut self,
+        as_legacy_transaction: bool,
+        pool_ids: &[Pubkey],
+        address_lookup_tables: &[Pubkey],
+        api: &ApiV3Client,
+        rpc_client: &RpcClient,
+    ) -> Result<()> {
+        if as_legacy_transaction {
+            self.address_lookup_tables = None;
+            return Ok(());
+        }
+
+        // pool lookup tables only shrink the transaction, so build without them if the api fails
+        match api.fetch_pool_lookup_tables(pool_ids).await {
+            Ok(tables) => self.address_lookup_table_addresses.extend(tables),
+            Err(e) => log::debug!("Failed to fetch pool lookup tables: {:#}", e),
+        }
+        self.address_lookup_table_addresses
+            .extend_from_slice(address_lookup_tables);
+        self.address_lookup_table_addresses.sort();
+        self.address_lookup_table_addresses.dedup();
+        let accounts = crate::utils::get_multiple_account_data(
+            rpc_client,
+            &self.address_lookup_table_addresses,
+        )
+        .await?;
+        let address_lookup_tables = self
+            .address_lookup_table_addresses
+            .iter()
+            .zip(accounts)
+            .map(|(key, account)| {
+                let account = account.ok_or_else(|| {
+                    SwapError::AccountMissing(format!("address lookup table {}", key))
+                })?;
+                let table = AddressLookupTable::deserialize(&account.data).map_err(|e| {
+                    SwapError::DecodeError(format!("address lookup table {}: {}", key, e))
+                })?;
+                Ok(AddressLookupTableAccount {
+                    key: *key,
+                    addresses: table.addresses.to_vec(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        self.address_lookup_tables = Some(address_lookup_tables);
+
+        Ok(())
+    }
+
+    pub fn build_instructions(self) -> Result<Vec<Instruction>> {
         let mut final_instructions = Vec::new();
         let SwapInstructionsBuilder {
             compute_budget_instructions,
             setup_instructions,
-            swap_instruction,
-            cleanup_instruction,
+            swap_instructions,
+            cleanup_instructions,
             address_lookup_table_addresses: _,
+            address_lookup_tables: _,
+            durable_nonce,
+            simulation: _,
+            simulated_token_accounts: _,
+            simulated_token_balances: _,
         } = self;
+        // the nonce must be advanced by the first instruction
+        if let Some((durable_nonce, _)) = durable_nonce {
+            final_instructions.push(solana_sdk::system_instruction::advance_nonce_account(
+                &durable_nonce.account,
+                &durable_nonce.authority,
+            ));
+        }
         final_instructions.extend(compute_budget_instructions);
         final_instructions.extend(setup_instructions);
-        final_instructions.push(swap_instruction.context("Swap instruction not set")?);
-        if let Some(cleanup_instruction) = cleanup_instruction {
-            final_instructions.push(cleanup_instruction);
+        if swap_instructions.is_empty() {
+            return Err(SwapError::Transaction(
+                "Swap instruction not set".to_string(),
+            ));
         }
+        final_instructions.extend(swap_instructions);
+        final_instructions.extend(cleanup_instructions);
         Ok(final_instructions)
     }
 
+    /// Builds a v0 transaction if address lookup tables were loaded, otherwise a legacy one.
+    /// `blockhash` is ignored when a durable nonce is set. Fails if the transaction exceeds the
+    /// maximum transaction size.
     pub fn build_transaction(
-        self,
+        mut self,
         payer: Option<&Pubkey>,
         blockhash: Option<Hash>,
-    ) -> anyhow::Result<VersionedTransaction> {
+    ) -> Result<VersionedTransaction> {
+        let address_lookup_tables = self.address_lookup_tables.take();
+        let blockhash = self.durable_nonce.map(|(_, nonce)| nonce).or(blockhash);
         let instructions = self.build_instructions()?;
-        let mut message = VersionedMessage::Legacy(Message::new(&instructions, payer));
-        if let Some(hash) = blockhash {
-            message.set_recent_blockhash(hash);
-        }
-        Ok(VersionedTransaction {
-            signatures: vec![Signature::default()],
+        let message = match address_lookup_tables {
+            None => {
+                let mut message = VersionedMessage::Legacy(Message::new(&instructions, payer));
+                if let Some(hash) = blockhash {
+                    message.set_recent_blockhash(hash);
+                }
+                message
+            }
+            Some(address_lookup_tables) => {
+                let payer = payer.ok_or_else(|| {
+                    SwapError::InvalidInput("v0 transactions require a payer".to_string())
+                })?;
+                VersionedMessage::V0(
+                    v0::Message::try_compile(
+                        payer,
+                        &instructions,
+                        &address_lookup_tables,
+                        blockhash.unwrap_or_default(),
+                    )
+                    .map_err(|e| SwapError::Transaction(e.to_string()))?,
+                )
+            }
+        };
+        let transaction = VersionedTransaction {
+            signatures: vec![
+                Signature::default();
+                message.header().num_required_signatures as usize
+            ],
             message,
-        })
+        };
+
+        let transaction_size = bincode::serialized_size(&transaction)
+            .map_err(|e| SwapError::Transaction(e.to_string()))?;
+        if transaction_size > PACKET_DATA_SIZE as u64 {
+            return Err(SwapError::Transaction(format!(
+                "Transaction of {} bytes exceeds the maximum of {} bytes{}",
+                transaction_size,
+                PACKET_DATA_SIZE,
+                if transaction.message.address_table_lookups().is_none() {
+                    ", use a v0 transaction with address lookup tables"
+                } else {
+                    ""
+                }
+            )));
+        }
+
+        Ok(transaction)
     }
 }
 
@@ -252,7 +717,79 @@ fn calculate_cu_price(priority_fee: u64, compute_units: u32) -> u64 {
     u64::try_from(cu_price).unwrap_or(u64::MAX)
 }
 
-const JITO_TIP_ACCOUNTS: [Pubkey; 8] = [
+/// The amount `account` received in a simulation with `balances`. An output account closed by the
+/// swap, such as unwrapped sol, is read from the amm's last swap log instead.
+fn simulated_received(
+    balances: &[SimulatedTokenBalance],
+    account: &Pubkey,
+    ray_logs: &[RayLog],
+) -> Result<u64> {
+    let balance = balances
+        .iter()
+        .find(|balance| balance.account == *account)
+        .ok_or_else(|| {
+            SwapError::Transaction(format!("Output account {} was not simulated", account))
+        })?;
+    match balance.after {
+        Some(after) => Ok(after.saturating_sub(balance.before)),
+        None => ray_logs
+            .iter()
+            .rev()
+            .find_map(RayLog::swap_amounts)
+            .map(|(_, amount_out)| amount_out)
+            .ok_or_else(|| {
+                SwapError::Unsupported(format!(
+                    "Output account {} is closed by the swap and its output can't be checked, \
+                     disable wrap_and_unwrap_sol to check it",
+                    account
+                ))
+            }),
+    }
+}
+
+/// The nonce stored in `account`, checked to be the initialized nonce account of `durable_nonce`
+fn nonce_blockhash(durable_nonce: &DurableNonce, account: &Account) -> Result<Hash> {
+    if account.owner != solana_sdk::system_program::ID {
+        return Err(SwapError::InvalidInput(format!(
+            "Nonce account {} is owned by {}, not the system program",
+            durable_nonce.account, account.owner
+        )));
+    }
+    let versions: nonce::state::Versions = bincode::deserialize(&account.data).map_err(|e| {
+        SwapError::DecodeError(format!("nonce account {}: {}", durable_nonce.account, e))
+    })?;
+    let nonce::State::Initialized(data) = versions.state() else {
+        return Err(SwapError::InvalidInput(format!(
+            "Nonce account {} is not initialized",
+            durable_nonce.account
+        )));
+    };
+    if data.authority != durable_nonce.authority {
+        return Err(SwapError::InvalidInput(format!(
+            "Nonce account {} has authority {}, expected {}",
+            durable_nonce.account, data.authority, durable_nonce.authority
+        )));
+    }
+    Ok(data.blockhash())
+}
+
+/// The amm v4 error `err` stands for, if it is a custom error of an amm v4 instruction
+fn decode_amm_error(
+    transaction: &VersionedTransaction,
+    err: &TransactionError,
+) -> Option<AmmError> {
+    let TransactionError::InstructionError(index, InstructionError::Custom(code)) = err else {
+        return None;
+    };
+    let instruction = transaction.message.instructions().get(*index as usize)?;
+    let program_id = instruction.program_id(transaction.message.static_account_keys());
+    if *program_id != RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID {
+        return None;
+    }
+    <AmmError as DecodeError<AmmError>>::decode_custom_error_to_enum(*code)
+}
+
+pub(crate) const JITO_TIP_ACCOUNTS: [Pubkey; 8] = [
     pubkey!("96gYZGLnJYVFmbjzopPSU6QiEV5fGqZNyN9nmNhvrZU5"),
     pubkey!("HFqU5x63VTqvQss8hp11i4wVV8bD44PvwucfZ2bU7gRe"),
     pubkey!("Cw8CFyM9FkoMi7K7Crf6HNQqf4uEMzpKw6QNghXLvLkY"),
@@ -263,8 +800,205 @@ const JITO_TIP_ACCOUNTS: [Pubkey; 8] = [
     pubkey!("3AVi9Tg9Uo68tJfuvoKvqKNWKkC5wPdSSdeBnizKZ6jT"),
 ];
 
-fn build_jito_tip_ix(from: &Pubkey, tip: u64) -> Instruction {
+pub(crate) fn build_jito_tip_ix(from: &Pubkey, tip: u64) -> Instruction {
     let random_recipient =
         &JITO_TIP_ACCOUNTS[rand::thread_rng().gen_range(0..JITO_TIP_ACCOUNTS.len())];
     solana_sdk::system_instruction::transfer(from, random_recipient, tip)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::instruction::AccountMeta;
+
+    /// A builder whose swap instruction reads `accounts` unique accounts
+    fn swap_builder(accounts: usize) -> (SwapInstructionsBuilder, Vec<Pubkey>) {
+        let keys = (0..accounts)
+            .map(|_| Pubkey::new_unique())
+            .collect::<Vec<_>>();
+        let builder = SwapInstructionsBuilder {
+            swap_instructions: vec![Instruction::new_with_bytes(
+                RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
+                &[9],
+                keys.iter()
+                    .map(|key| AccountMeta::new(*key, false))
+                    .collect(),
+            )],
+            ..Default::default()
+        };
+        (builder, keys)
+    }
+
+    #[test]
+    pub fn build_transaction_compiles_lookup_tables_into_v0() {
+        let payer = Pubkey::new_unique();
+        let blockhash = Hash::new_unique();
+        let (mut builder, keys) = swap_builder(40);
+        builder.address_lookup_tables = Some(vec![AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: keys,
+        }]);
+
+        let transaction = builder
+            .build_transaction(Some(&payer), Some(blockhash))
+            .unwrap();
+        let VersionedMessage::V0(message) = &transaction.message else {
+            panic!("expected a v0 message");
+        };
+        assert_eq!(message.recent_blockhash, blockhash);
+        assert_eq!(message.account_keys[0], payer);
+        assert_eq!(message.address_table_lookups.len(), 1);
+        assert_eq!(message.address_table_lookups[0].writable_indexes.len(), 40);
+        assert_eq!(transaction.signatures.len(), 1);
+    }
+
+    #[test]
+    pub fn build_transaction_without_lookup_tables_is_legacy() {
+        let payer = Pubkey::new_unique();
+        let blockhash = Hash::new_unique();
+        let (builder, keys) = swap_builder(3);
+
+        let transaction = builder
+            .build_transaction(Some(&payer), Some(blockhash))
+            .unwrap();
+        let VersionedMessage::Legacy(message) = &transaction.message else {
+            panic!("expected a legacy message");
+        };
+        assert_eq!(message.recent_blockhash, blockhash);
+        assert_eq!(message.account_keys[0], payer);
+        assert!(keys.iter().all(|key| message.account_keys.contains(key)));
+    }
+
+    #[test]
+    pub fn build_transaction_rejects_oversized_transactions() {
+        let (builder, _) = swap_builder(40);
+        match builder.build_transaction(Some(&Pubkey::new_unique()), Some(Hash::new_unique())) {
+            Err(SwapError::Transaction(e)) => {
+                assert!(e.contains("use a v0 transaction"), "{}", e)
+            }
+            other => panic!("expected an oversized transaction, got {:?}", other),
+        }
+    }
+
+    #[test]
+    pub fn durable_nonce_is_advanced_first_and_replaces_the_blockhash() {
+        let payer = Pubkey::new_unique();
+        let durable_nonce = DurableNonce {
+            account: Pubkey::new_unique(),
+            authority: payer,
+        };
+        let nonce_state = nonce::State::new_initialized(
+            &durable_nonce.authority,
+            nonce::state::DurableNonce::from_blockhash(&Hash::new_unique()),
+            5_000,
+        );
+        let account = Account {
+            lamports: 1_447_680,
+            data: bincode::serialize(&nonce::state::Versions::new(nonce_state.clone())).unwrap(),
+            owner: solana_sdk::system_program::ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        let nonce::State::Initialized(data) = nonce_state else {
+            unreachable!()
+        };
+        let nonce_hash = nonce_blockhash(&durable_nonce, &account).unwrap();
+        assert_eq!(nonce_hash, data.blockhash());
+
+        let (mut builder, _) = swap_builder(3);
+        builder.durable_nonce = Some((durable_nonce, nonce_hash));
+        let transaction = builder
+            .build_transaction(Some(&payer), Some(Hash::new_unique()))
+            .unwrap();
+        assert_eq!(*transaction.message.recent_blockhash(), nonce_hash);
+        let keys = transaction.message.static_account_keys();
+        let advance = &transaction.message.instructions()[0];
+        assert_eq!(*advance.program_id(keys), solana_sdk::system_program::ID);
+        assert_eq!(keys[advance.accounts[0] as usize], durable_nonce.account);
+        assert!(matches!(
+            bincode::deserialize(&advance.data),
+            Ok(solana_sdk::system_instruction::SystemInstruction::AdvanceNonceAccount)
+        ));
+
+        let other_authority = DurableNonce {
+            authority: Pubkey::new_unique(),
+            ..durable_nonce
+        };
+        assert!(matches!(
+            nonce_blockhash(&other_authority, &account),
+            Err(SwapError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    pub fn simulated_received_falls_back_to_the_last_swap_log() {
+        let account = Pubkey::new_unique();
+        let open = [SimulatedTokenBalance {
+            account,
+            before: 10,
+            after: Some(2_010),
+        }];
+        assert_eq!(simulated_received(&open, &account, &[]).unwrap(), 2_000);
+        assert!(matches!(
+            simulated_received(&open, &Pubkey::new_unique(), &[]),
+            Err(SwapError::Transaction(_))
+        ));
+
+        let closed = [SimulatedTokenBalance {
+            account,
+            before: 10,
+            after: None,
+        }];
+        let swap_log = |out_amount: u64| {
+            // a swap base in log
+            let mut data = vec![3u8];
+            for field in [1_000u64, 900, 2, 5_000, 100_000, 200_000, out_amount] {
+                data.extend_from_slice(&field.to_le_bytes());
+            }
+            RayLog::decode(&data).unwrap().unwrap()
+        };
+        assert_eq!(
+            simulated_received(&closed, &account, &[swap_log(1_990), swap_log(3_900)]).unwrap(),
+            3_900
+        );
+        assert!(matches!(
+            simulated_received(&closed, &account, &[]),
+            Err(SwapError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    pub fn decode_amm_error_reads_amm_custom_errors() {
+        let payer = Pubkey::new_unique();
+        let message = Message::new(
+            &[
+                solana_sdk::compute_budget::ComputeBudgetInstruction::set_compute_unit_limit(
+                    200_000,
+                ),
+                Instruction::new_with_bytes(RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID, &[9], vec![]),
+            ],
+            Some(&payer),
+        );
+        let transaction = VersionedTransaction {
+            signatures: vec![Signature::default()],
+            message: VersionedMessage::Legacy(message),
+        };
+        let custom =
+            |index, code| TransactionError::InstructionError(index, InstructionError::Custom(code));
+
+        assert!(matches!(
+            decode_amm_error(&transaction, &custom(1, AmmError::ExceededSlippage as u32)),
+            Some(AmmError::ExceededSlippage)
+        ));
+        // not an amm instruction
+        assert!(
+            decode_amm_error(&transaction, &custom(0, AmmError::ExceededSlippage as u32)).is_none()
+        );
+        assert!(decode_amm_error(&transaction, &custom(2, 0)).is_none());
+        assert!(decode_amm_error(
+            &transaction,
+            &TransactionError::InstructionError(1, InstructionError::InvalidAccountData)
+        )
+        .is_none());
+    }
+}
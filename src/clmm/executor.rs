@@ -0,0 +1,824 @@
+use super::pool_state::{AmmConfig, PoolState, TickArrayBitmapExtension, TickArrayState};
+use crate::amm::pool_selection::PoolRejection;
+use crate::api_v3::response::{ApiV3ClmmPool, ApiV3PoolsPage};
+use crate::api_v3::{ApiV3Client, PoolFetchParams, PoolSort, PoolSortOrder, PoolType};
+use crate::builder::{SwapInstructionsBuilder, SwapPlan};
+use crate::executor::{SwapExecutor, SwapQuote};
+use crate::priority_fee::{PriorityFeeEstimator, RpcPriorityFeeEstimator};
+use crate::types::{
+    ComputeUnitLimits, DurableNonce, PriorityFeeConfig, SwapConfig, SwapConfigOverrides, SwapInput,
+};
+use std::sync::Arc;
+
+use crate::error::{Result, SwapError};
+use crate::transfer_fee::{self, MintTransferFee, SwapTransferFees};
+use async_trait::async_trait;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::account::Account;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::transaction::VersionedTransaction;
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
+pub const RAYDIUM_CLMM_PROGRAM_ID: Pubkey = pubkey!("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK");
+const MEMO_PROGRAM_ID: Pubkey = pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+const TICK_ARRAY_SEED: &[u8] = b"tick_array";
+const POOL_TICK_ARRAY_BITMAP_SEED: &[u8] = b"pool_tick_array_bitmap_extension";
+const SWAP_V2_DISCRIMINATOR: [u8; 8] = [43, 4, 237, 11, 26, 201, 30, 98];
+/// Maximum number of tick arrays loaded for a quote. Swaps crossing more are rejected.
+const MAX_TICK_ARRAYS: usize = 5;
+
+#[derive(Clone)]
+pub struct RaydiumClmm {
+    client: Arc<RpcClient>,
+    api: ApiV3Client,
+    config: SwapConfig,
+    priority_fee_estimator: Arc<dyn PriorityFeeEstimator>,
+}
+
+#[derive(Default)]
+pub struct RaydiumClmmExecutorOpts {
+    pub priority_fee: Option<PriorityFeeConfig>,
+    pub cu_limits: Option<ComputeUnitLimits>,
+    pub wrap_and_unwrap_sol: Option<bool>,
+    /// Builds legacy transactions instead of v0 transactions. Defaults to false
+    pub as_legacy_transaction: Option<bool>,
+    /// Builds transactions with a durable nonce instead of a recent blockhash
+    pub durable_nonce: Option<DurableNonce>,
+    /// Estimates the cu price of dynamic priority fees. Defaults to the rpc node's recent
+    /// prioritization fees
+    pub priority_fee_estimator: Option<Arc<dyn PriorityFeeEstimator>>,
+    /// Fails exact-in swaps whose simulation pays less than the output threshold into the output
+    /// account, after transfer fees. Defaults to false
+    pub check_simulated_output: Option<bool>,
+}
+
+impl RaydiumClmm {
+    pub fn new(client: Arc<RpcClient>, config: RaydiumClmmExecutorOpts, api: ApiV3Client) -> Self {
+        let RaydiumClmmExecutorOpts {
+            priority_fee,
+            cu_limits,
+            wrap_and_unwrap_sol,
+            as_legacy_transaction,
+            durable_nonce,
+            priority_fee_estimator,
+            check_simulated_output,
+        } = config;
+        Self {
+            priority_fee_estimator: priority_fee_estimator
+                .unwrap_or_else(|| Arc::new(RpcPriorityFeeEstimator::new(Arc::clone(&client)))),
+            client,
+            api,
+            config: SwapConfig {
+                priority_fee,
+                cu_limits,
+                wrap_and_unwrap_sol,
+                as_legacy_transaction,
+                durable_nonce,
+                check_simulated_output,
+            },
+        }
+    }
+
+    pub async fn quote(&self, swap_input: &SwapInput) -> Result<RaydiumClmmQuote> {
+        if swap_input.input_token_mint == swap_input.output_token_mint {
+            return Err(SwapError::InvalidInput(format!(
+                "Input token cannot equal output token {}",
+                swap_input.input_token_mint
+            )));
+        }
+
+        let mut pool_id = swap_input.market;
+        if pool_id.is_none() {
+            let response: ApiV3PoolsPage<ApiV3ClmmPool> = self
+                .api
+                .fetch_pool_by_mints(
+                    &swap_input.input_token_mint,
+                    Some(&swap_input.output_token_mint),
+                    &PoolFetchParams {
+                        pool_type: PoolType::Concentrated,
+                        pool_sort: PoolSort::Liquidity,
+                        sort_type: PoolSortOrder::Descending,
+                        page_size: 10,
+                        page: 1,
+                    },
+                )
+                .await?;
+            pool_id = response.pools.into_iter().find_map(|pool| {
+                let matches_mints = (pool.mint_a.address == swap_input.input_token_mint
+                    && pool.mint_b.address == swap_input.output_token_mint)
+                    || (pool.mint_a.address == swap_input.output_token_mint
+                        && pool.mint_b.address == swap_input.input_token_mint);
+                (matches_mints && pool.program_id == RAYDIUM_CLMM_PROGRAM_ID).then_some(pool.id)
+            });
+        }
+
+        let Some(pool_id) = pool_id else {
+            return Err(SwapError::PoolNotFound(format!(
+                "no clmm pool for {} -> {}",
+                swap_input.input_token_mint, swap_input.output_token_mint
+            )));
+        };
+
+        // the tick arrays a swap crosses depend on the pool's current tick and bitmaps
+        let bitmap_extension_key = tick_array_bitmap_extension_key(&pool_id);
+        let rsps =
+            crate::utils::get_multiple_account_data(&self.client, &[pool_id, bitmap_extension_key])
+                .await?;
+        let [pool_account, bitmap_extension_account]: [Option<Account>; 2] =
+            rsps.try_into().map_err(|v: Vec<_>| {
+                SwapError::InvalidInput(format!("Expected 2 clmm accounts, got {}", v.len()))
+            })?;
+        let pool = PoolState::try_deserialize(
+            &pool_account
+                .ok_or_else(|| SwapError::AccountMissing(format!("clmm pool {}", pool_id)))?
+                .data,
+        )?;
+        let bitmap_extension = bitmap_extension_account
+            .map(|account| TickArrayBitmapExtension::try_deserialize(&account.data))
+            .transpose()?;
+        let zero_for_one = swap_input.input_token_mint == pool.token_mint_0;
+        let tick_array_start_indexes = super::swap::tick_array_start_indexes(
+            &pool,
+            bitmap_extension.as_ref(),
+            zero_for_one,
+            MAX_TICK_ARRAYS,
+        )?;
+
+        // reload the pool together with everything needed to quote to ensure data consistency
+        let mut load_pubkeys = vec![
+            pool_id,
+            pool.amm_config,
+            bitmap_extension_key,
+            pool.token_mint_0,
+            pool.token_mint_1,
+            solana_sdk::sysvar::clock::ID,
+        ];
+        load_pubkeys.extend(
+            tick_array_start_indexes
+                .into_iter()
+                .map(|start_index| tick_array_key(&pool_id, start_index)),
+        );
+        let rsps = crate::utils::get_multiple_account_data(&self.client, &load_pubkeys).await?;
+        let accounts = RaydiumClmmAccounts::try_from(rsps)?;
+
+        Self::quote_from_accounts(swap_input, pool_id, &accounts)
+    }
+
+    /// Calculates a quote from already loaded pool accounts without any network requests.
+    pub fn quote_from_accounts(
+        swap_input: &SwapInput,
+        pool_id: Pubkey,
+        accounts: &RaydiumClmmAccounts,
+    ) -> Result<RaydiumClmmQuote> {
+        if swap_input.input_token_mint == swap_input.output_token_mint {
+            return Err(SwapError::InvalidInput(format!(
+                "Input token cannot equal output token {}",
+                swap_input.input_token_mint
+            )));
+        }
+
+        let pool = PoolState::try_deserialize(&accounts.pool.data)?;
+        let amm_config = AmmConfig::try_deserialize(&accounts.amm_config.data)?;
+        let bitmap_extension = accounts
+            .tick_array_bitmap_extension
+            .as_ref()
+            .map(|account| TickArrayBitmapExtension::try_deserialize(&account.data))
+            .transpose()?;
+        let tick_arrays = accounts
+            .tick_arrays
+            .iter()
+            .map(|account| {
+                let tick_array = TickArrayState::try_deserialize(&account.data)?;
+                if tick_array.pool_id != pool_id {
+                    return Err(SwapError::InvalidInput(format!(
+                        "Tick array {} does not belong to clmm pool {}",
+                        { tick_array.start_tick_index },
+                        pool_id
+                    )));
+                }
+                Ok(tick_array)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let clock = crate::utils::decode_clock(&accounts.clock)?;
+        if !pool.swap_enabled() {
+            return Err(SwapError::PoolNotTradable {
+                pool: pool_id,
+                reason: PoolRejection::SwapNotPermitted {
+                    status: pool.status.into(),
+                },
+            });
+        }
+        // the program only swaps once the cluster clock is past the open time
+        let (open_time, now) = (pool.open_time, clock.unix_timestamp.max(0) as u64);
+        if open_time >= now {
+            return Err(SwapError::PoolNotTradable {
+                pool: pool_id,
+                reason: PoolRejection::NotOpen { open_time, now },
+            });
+        }
+
+        let zero_for_one = if swap_input.input_token_mint == pool.token_mint_0
+            && swap_input.output_token_mint == pool.token_mint_1
+        {
+            true
+        } else if swap_input.input_token_mint == pool.token_mint_1
+            && swap_input.output_token_mint == pool.token_mint_0
+        {
+            false
+        } else {
+            return Err(SwapError::InvalidInput(format!(
+                "Clmm pool {} does not trade {} for {}",
+                pool_id, swap_input.input_token_mint, swap_input.output_token_mint
+            )));
+        };
+
+        let (input_mint, output_mint) = if zero_for_one {
+            (&accounts.mint_0, &accounts.mint_1)
+        } else {
+            (&accounts.mint_1, &accounts.mint_0)
+        };
+        let input_transfer_fee = MintTransferFee::from_mint(input_mint)?;
+        let output_transfer_fee = MintTransferFee::from_mint(output_mint)?;
+        let epoch = clock.epoch;
+
+        // token-2022 transfer fees are withheld from the amount the pool receives and the amount
+        // the user receives, so thresholds are in terms of what actually leaves or arrives
+        let amount_specified_is_input = swap_input.mode.amount_specified_is_input();
+        let (input_fee, output_fee, amount_specified) = if amount_specified_is_input {
+            let input_fee =
+                transfer_fee::fee(input_transfer_fee.as_ref(), epoch, swap_input.amount)?;
+            (input_fee, 0, swap_input.amount - input_fee)
+        } else {
+            let output_fee =
+                transfer_fee::inverse_fee(output_transfer_fee.as_ref(), epoch, swap_input.amount)?;
+            let amount_out = swap_input
+                .amount
+                .checked_add(output_fee)
+                .ok_or_else(|| SwapError::Math("Clmm amount out overflows".to_string()))?;
+            (0, output_fee, amount_out)
+        };
+        let simulation = super::swap::simulate_swap(
+            &pool,
+            &amm_config,
+            bitmap_extension.as_ref(),
+            &tick_arrays,
+            amount_specified,
+            zero_for_one,
+            amount_specified_is_input,
+        )?;
+        let amount_calculated = simulation.amount_calculated;
+        let (other_amount, transfer_fees) = if amount_specified_is_input {
+            let output_fee =
+                transfer_fee::fee(output_transfer_fee.as_ref(), epoch, amount_calculated)?;
+            (
+                amount_calculated - output_fee,
+                SwapTransferFees {
+                    input: input_fee,
+                    output: output_fee,
+                },
+            )
+        } else {
+            let input_fee =
+                transfer_fee::inverse_fee(input_transfer_fee.as_ref(), epoch, amount_calculated)?;
+            (
+                amount_calculated
+                    .checked_add(input_fee)
+                    .ok_or_else(|| SwapError::Math("Clmm amount in overflows".to_string()))?,
+                SwapTransferFees {
+                    input: input_fee,
+                    output: output_fee,
+                },
+            )
+        };
+        let other_amount_threshold = crate::utils::amount_with_slippage(
+            other_amount,
+            swap_input.slippage_bps as u64,
+            !amount_specified_is_input,
+        )?;
+        log::debug!(
+            "raw quote: {}. raw other_amount_threshold: {}",
+            other_amount,
+            other_amount_threshold
+        );
+
+        let (input_mint_decimals, output_mint_decimals) = if zero_for_one {
+            (pool.mint_decimals_0, pool.mint_decimals_1)
+        } else {
+            (pool.mint_decimals_1, pool.mint_decimals_0)
+        };
+
+        Ok(RaydiumClmmQuote {
+            market: pool_id,
+            input_mint: swap_input.input_token_mint,
+            output_mint: swap_input.output_token_mint,
+            amount: swap_input.amount,
+            other_amount,
+            other_amount_threshold,
+            amount_specified_is_input,
+            input_mint_decimals,
+            output_mint_decimals,
+            transfer_fees,
+            keys: ClmmKeys {
+                pool: pool_id,
+                amm_config: pool.amm_config,
+                observation_key: pool.observation_key,
+                token_0_mint: pool.token_mint_0,
+                token_1_mint: pool.token_mint_1,
+                token_0_vault: pool.token_vault_0,
+                token_1_vault: pool.token_vault_1,
+                token_0_program: crate::utils::mint_token_program(&accounts.mint_0)?,
+                token_1_program: crate::utils::mint_token_program(&accounts.mint_1)?,
+                tick_array_bitmap_extension: accounts
+                    .tick_array_bitmap_extension
+                    .is_some()
+                    .then(|| tick_array_bitmap_extension_key(&pool_id)),
+                tick_arrays: simulation
+                    .tick_array_start_indexes
+                    .into_iter()
+                    .map(|start_index| tick_array_key(&pool_id, start_index))
+                    .collect(),
+            },
+        })
+    }
+
+    pub async fn swap_instructions(
+        &self,
+        input_pubkey: Pubkey,
+        output: RaydiumClmmQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<Vec<solana_sdk::instruction::Instruction>> {
+        let builder = self.make_swap(input_pubkey, output, overrides).await?;
+        builder.build_instructions()
+    }
+
+    pub async fn swap_transaction(
+        &self,
+        input_pubkey: Pubkey,
+        output: RaydiumClmmQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<VersionedTransaction> {
+        let fee_payer = overrides.and_then(|o| o.fee_payer).unwrap_or(input_pubkey);
+        let builder = self.make_swap(input_pubkey, output, overrides).await?;
+        builder.build_transaction(Some(&fee_payer), None)
+    }
+
+    pub fn update_config(&mut self, config: &SwapConfig) {
+        self.config = *config;
+    }
+
+    async fn make_swap(
+        &self,
+        input_pubkey: Pubkey,
+        output: RaydiumClmmQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<SwapInstructionsBuilder> {
+        let config = self.config.resolve(input_pubkey, overrides);
+        let (input_token_program, output_token_program) = output.token_programs()?;
+        let plan = SwapPlan {
+            user: input_pubkey,
+            input_mint: output.input_mint,
+            output_mint: output.output_mint,
+            input_token_program,
+            output_token_program,
+            max_amount_in: if output.amount_specified_is_input {
+                output.amount
+            } else {
+                output.other_amount_threshold
+            },
+            pool_ids: &[output.market],
+            min_amount_out: output
+                .amount_specified_is_input
+                .then_some(output.other_amount_threshold),
+        };
+        SwapInstructionsBuilder::build_swap(
+            &config,
+            plan,
+            &self.api,
+            &self.client,
+            self.priority_fee_estimator.as_ref(),
+            |builder, associated_accounts| {
+                builder.swap_instructions.push(output.swap_instruction(
+                    &input_pubkey,
+                    &associated_accounts.input_ata,
+                    &associated_accounts.output_ata,
+                )?);
+                Ok(())
+            },
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl SwapExecutor for RaydiumClmm {
+    fn program_id(&self) -> Pubkey {
+        RAYDIUM_CLMM_PROGRAM_ID
+    }
+
+    async fn quote(&self, swap_input: &SwapInput) -> Result<SwapQuote> {
+        Ok(RaydiumClmm::quote(self, swap_input).await?.into())
+    }
+
+    async fn swap_instructions(
+        &self,
+        input_pubkey: Pubkey,
+        quote: SwapQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<Vec<Instruction>> {
+        RaydiumClmm::swap_instructions(self, input_pubkey, quote.try_into()?, overrides).await
+    }
+
+    async fn swap_transaction(
+        &self,
+        input_pubkey: Pubkey,
+        quote: SwapQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<VersionedTransaction> {
+        RaydiumClmm::swap_transaction(self, input_pubkey, quote.try_into()?, overrides).await
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RaydiumClmmQuote {
+    /// The address of the clmm pool
+    pub market: Pubkey,
+    /// The input mint
+    pub input_mint: Pubkey,
+    /// The output mint,
+    pub output_mint: Pubkey,
+    /// The amount specified
+    pub amount: u64,
+    /// The other amount
+    pub other_amount: u64,
+    /// The other amount with slippage
+    pub other_amount_threshold: u64,
+    /// Whether the amount specified is in terms of the input token
+    pub amount_specified_is_input: bool,
+    /// The input mint decimals
+    pub input_mint_decimals: u8,
+    /// The output mint decimals
+    pub output_mint_decimals: u8,
+    /// Token-2022 transfer fees withheld from the swap. `amount` and `other_amount` are what the
+    /// user sends and receives, fees included
+    pub transfer_fees: SwapTransferFees,
+    /// Clmm pool keys
+    pub keys: ClmmKeys,
+}
+
+impl RaydiumClmmQuote {
+    /// Builds the swap instruction for this quote between the user's token accounts
+    pub fn swap_instruction(
+        &self,
+        user_owner: &Pubkey,
+        user_source: &Pubkey,
+        user_destination: &Pubkey,
+    ) -> Result<Instruction> {
+        swap_v2_instruction(
+            &RAYDIUM_CLMM_PROGRAM_ID,
+            &self.keys,
+            user_owner,
+            user_source,
+            user_destination,
+            &self.input_mint,
+            self.amount,
+            self.other_amount_threshold,
+            self.amount_specified_is_input,
+        )
+    }
+
+    /// Returns the (input, output) token programs of the swap
+    pub fn token_programs(&self) -> Result<(Pubkey, Pubkey)> {
+        self.keys.token_programs(&self.input_mint)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClmmKeys {
+    pub pool: Pubkey,
+    pub amm_config: Pubkey,
+    pub observation_key: Pubkey,
+    pub token_0_mint: Pubkey,
+    pub token_1_mint: Pubkey,
+    pub token_0_vault: Pubkey,
+    pub token_1_vault: Pubkey,
+    pub token_0_program: Pubkey,
+    pub token_1_program: Pubkey,
+    /// The pool's tick array bitmap extension, if it has been created
+    pub tick_array_bitmap_extension: Option<Pubkey>,
+    /// The tick arrays crossed by the quoted swap, in traversal order
+    pub tick_arrays: Vec<Pubkey>,
+}
+
+impl ClmmKeys {
+    /// Returns the (input, output) token programs for a swap from `input_mint`
+    fn token_programs(&self, input_mint: &Pubkey) -> Result<(Pubkey, Pubkey)> {
+        if *input_mint == self.token_0_mint {
+            Ok((self.token_0_program, self.token_1_program))
+        } else if *input_mint == self.token_1_mint {
+            Ok((self.token_1_program, self.token_0_program))
+        } else {
+            Err(SwapError::InvalidInput(format!(
+                "Mint {} is not traded by clmm pool {}",
+                input_mint, self.pool
+            )))
+        }
+    }
+}
+
+/// Snapshot of the accounts needed to quote against a clmm pool
+#[derive(Debug, Clone)]
+pub struct RaydiumClmmAccounts {
+    /// The clmm pool account
+    pub pool: Account,
+    /// The pool's amm config
+    pub amm_config: Account,
+    /// The pool's tick array bitmap extension, absent for pools created before it existed
+    pub tick_array_bitmap_extension: Option<Account>,
+    /// The token-0 mint
+    pub mint_0: Account,
+    /// The token-1 mint
+    pub mint_1: Account,
+    /// The Clock sysvar, whose epoch selects the mints' transfer fees and whose timestamp is
+    /// checked against the pool's open time
+    pub clock: Account,
+    /// Initialized tick arrays in the swap direction, starting from the current tick
+    pub tick_arrays: Vec<Account>,
+}
+
+impl TryFrom<Vec<Option<Account>>> for RaydiumClmmAccounts {
+    type Error = SwapError;
+
+    /// Expects accounts in the order: pool, amm config, tick array bitmap extension, token-0
+    /// mint, token-1 mint, clock sysvar, followed by any number of tick arrays
+    fn try_from(accounts: Vec<Option<Account>>) -> Result<Self, Self::Error> {
+        if accounts.len() < 6 {
+            return Err(SwapError::InvalidInput(format!(
+                "Expected at least 6 clmm accounts, got {}",
+                accounts.len()
+            )));
+        }
+        let mut accounts = accounts.into_iter();
+        let mut next = || accounts.next().flatten();
+        let missing = |name: &str| SwapError::AccountMissing(format!("clmm {}", name));
+        let pool = next().ok_or_else(|| missing("pool"))?;
+        let amm_config = next().ok_or_else(|| missing("amm config"))?;
+        let tick_array_bitmap_extension = next();
+        let mint_0 = next().ok_or_else(|| missing("token-0 mint"))?;
+        let mint_1 = next().ok_or_else(|| missing("token-1 mint"))?;
+        let clock = next().ok_or_else(|| missing("clock sysvar"))?;
+        Ok(RaydiumClmmAccounts {
+            pool,
+            amm_config,
+            tick_array_bitmap_extension,
+            mint_0,
+            mint_1,
+            clock,
+            tick_arrays: accounts.flatten().collect(),
+        })
+    }
+}
+
+fn tick_array_key(pool_id: &Pubkey, start_index: i32) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            TICK_ARRAY_SEED,
+            pool_id.as_ref(),
+            &start_index.to_be_bytes(),
+        ],
+        &RAYDIUM_CLMM_PROGRAM_ID,
+    )
+    .0
+}
+
+fn tick_array_bitmap_extension_key(pool_id: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[POOL_TICK_ARRAY_BITMAP_SEED, pool_id.as_ref()],
+        &RAYDIUM_CLMM_PROGRAM_ID,
+    )
+    .0
+}
+
+#[allow(clippy::too_many_arguments)]
+fn swap_v2_instruction(
+    clmm_program: &Pubkey,
+    keys: &ClmmKeys,
+    user_owner: &Pubkey,
+    user_source: &Pubkey,
+    user_destination: &Pubkey,
+    input_mint: &Pubkey,
+    amount_specified: u64,
+    other_amount_threshold: u64,
+    is_base_input: bool,
+) -> Result<Instruction> {
+    let (input_vault, output_vault, output_mint) = if *input_mint == keys.token_0_mint {
+        (keys.token_0_vault, keys.token_1_vault, keys.token_1_mint)
+    } else if *input_mint == keys.token_1_mint {
+        (keys.token_1_vault, keys.token_0_vault, keys.token_0_mint)
+    } else {
+        return Err(SwapError::InvalidInput(format!(
+            "Mint {} is not traded by clmm pool {}",
+            input_mint, keys.pool
+        )));
+    };
+
+    let mut data = Vec::with_capacity(41);
+    data.extend_from_slice(&SWAP_V2_DISCRIMINATOR);
+    data.extend_from_slice(&amount_specified.to_le_bytes());
+    data.extend_from_slice(&other_amount_threshold.to_le_bytes());
+    // a zero price limit lets the program use the min/max price for the swap direction
+    data.extend_from_slice(&0u128.to_le_bytes());
+    data.push(is_base_input as u8);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*user_owner, true),
+        AccountMeta::new_readonly(keys.amm_config, false),
+        AccountMeta::new(keys.pool, false),
+        AccountMeta::new(*user_source, false),
+        AccountMeta::new(*user_destination, false),
+        AccountMeta::new(input_vault, false),
+        AccountMeta::new(output_vault, false),
+        AccountMeta::new(keys.observation_key, false),
+        AccountMeta::new_readonly(spl_token::ID, false),
+        AccountMeta::new_readonly(spl_token_2022::ID, false),
+        AccountMeta::new_readonly(MEMO_PROGRAM_ID, false),
+        AccountMeta::new_readonly(*input_mint, false),
+        AccountMeta::new_readonly(output_mint, false),
+    ];
+    if let Some(bitmap_extension) = keys.tick_array_bitmap_extension {
+        accounts.push(AccountMeta::new_readonly(bitmap_extension, false));
+    }
+    accounts.extend(
+        keys.tick_arrays
+            .iter()
+            .map(|tick_array| AccountMeta::new(*tick_array, false)),
+    );
+
+    Ok(Instruction {
+        program_id: *clmm_program,
+        accounts,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::math;
+    use super::super::pool_state::TickState;
+    use super::*;
+    use crate::types::SwapExecutionMode;
+    use crate::utils::test_accounts;
+    use bytemuck::Zeroable;
+
+    const LIQUIDITY: u128 = 1_000_000_000_000;
+
+    fn account<T: bytemuck::Pod>(discriminator: [u8; 8], state: &T) -> Account {
+        Account {
+            data: [&discriminator[..], bytemuck::bytes_of(state)].concat(),
+            owner: RAYDIUM_CLMM_PROGRAM_ID,
+            ..Default::default()
+        }
+    }
+
+    /// A pool at tick 0 with liquidity between ticks -10 and 10, in the tick arrays starting at
+    /// -60 and 0, and an empty bitmap extension
+    fn pool_accounts(pool_id: Pubkey) -> (PoolState, RaydiumClmmAccounts) {
+        let mut pool = PoolState::zeroed();
+        pool.amm_config = Pubkey::new_unique();
+        pool.token_mint_0 = Pubkey::new_unique();
+        pool.token_mint_1 = Pubkey::new_unique();
+        pool.token_vault_0 = Pubkey::new_unique();
+        pool.token_vault_1 = Pubkey::new_unique();
+        pool.observation_key = Pubkey::new_unique();
+        pool.mint_decimals_0 = 6;
+        pool.mint_decimals_1 = 6;
+        pool.tick_spacing = 1;
+        pool.liquidity = LIQUIDITY;
+        pool.sqrt_price_x64 = math::get_sqrt_price_at_tick(0).unwrap();
+        pool.tick_current = 0;
+        let mut bitmap = [0u64; 16];
+        bitmap[7] |= 1 << 63;
+        bitmap[8] |= 1;
+        pool.tick_array_bitmap = bitmap;
+
+        let amm_config = AmmConfig {
+            trade_fee_rate: 2_500,
+            tick_spacing: 1,
+            ..Default::default()
+        };
+        let mut bitmap_extension = TickArrayBitmapExtension::zeroed();
+        bitmap_extension.pool_id = pool_id;
+
+        let mut lower = TickArrayState::zeroed();
+        lower.pool_id = pool_id;
+        lower.start_tick_index = -60;
+        lower.ticks[50] = TickState {
+            tick: -10,
+            liquidity_net: LIQUIDITY as i128,
+            liquidity_gross: LIQUIDITY,
+            ..Default::default()
+        };
+        let mut upper = TickArrayState::zeroed();
+        upper.pool_id = pool_id;
+        upper.start_tick_index = 0;
+        upper.ticks[10] = TickState {
+            tick: 10,
+            liquidity_net: -(LIQUIDITY as i128),
+            liquidity_gross: LIQUIDITY,
+            ..Default::default()
+        };
+
+        let accounts = RaydiumClmmAccounts {
+            pool: account(PoolState::DISCRIMINATOR, &pool),
+            amm_config: account(AmmConfig::DISCRIMINATOR, &amm_config),
+            tick_array_bitmap_extension: Some(account(
+                TickArrayBitmapExtension::DISCRIMINATOR,
+                &bitmap_extension,
+            )),
+            mint_0: test_accounts::mint(6, None),
+            mint_1: test_accounts::mint(6, None),
+            clock: test_accounts::clock(1),
+            tick_arrays: vec![
+                account(TickArrayState::DISCRIMINATOR, &upper),
+                account(TickArrayState::DISCRIMINATOR, &lower),
+            ],
+        };
+        (pool, accounts)
+    }
+
+    /// Swaps 1_000_000 token-0 for token-1, moving the price down from the tick array starting
+    /// at 0 into the one starting at -60
+    fn swap_input(pool: &PoolState) -> SwapInput {
+        SwapInput {
+            input_token_mint: pool.token_mint_0,
+            output_token_mint: pool.token_mint_1,
+            slippage_bps: 50,
+            amount: 1_000_000,
+            mode: SwapExecutionMode::ExactIn,
+            market: None,
+        }
+    }
+
+    #[test]
+    pub fn quotes_across_tick_arrays() {
+        let pool_id = Pubkey::new_unique();
+        let (pool, accounts) = pool_accounts(pool_id);
+        let quote =
+            RaydiumClmm::quote_from_accounts(&swap_input(&pool), pool_id, &accounts).unwrap();
+        // 0.25% fee at a price of ~1
+        assert!(quote.other_amount < 997_500);
+        assert!(quote.other_amount > 997_000);
+        assert_eq!(
+            quote.other_amount_threshold,
+            crate::utils::amount_with_slippage(quote.other_amount, 50, false).unwrap()
+        );
+        assert_eq!(
+            quote.keys.tick_arrays,
+            vec![tick_array_key(&pool_id, 0), tick_array_key(&pool_id, -60)]
+        );
+    }
+
+    #[test]
+    pub fn rejects_missing_and_uninitialized_tick_arrays() {
+        let pool_id = Pubkey::new_unique();
+        let (pool, mut accounts) = pool_accounts(pool_id);
+        let lower = accounts.tick_arrays.pop().unwrap();
+
+        assert!(matches!(
+            RaydiumClmm::quote_from_accounts(&swap_input(&pool), pool_id, &accounts),
+            Err(SwapError::AccountMissing(_))
+        ));
+
+        // an uninitialized tick array account holds no discriminator
+        accounts.tick_arrays.push(Account {
+            data: vec![0; lower.data.len()],
+            ..lower
+        });
+        assert!(matches!(
+            RaydiumClmm::quote_from_accounts(&swap_input(&pool), pool_id, &accounts),
+            Err(SwapError::DecodeError(_))
+        ));
+    }
+
+    #[test]
+    pub fn swap_v2_passes_the_bitmap_extension_before_the_tick_arrays() {
+        let pool_id = Pubkey::new_unique();
+        let (pool, accounts) = pool_accounts(pool_id);
+        let quote =
+            RaydiumClmm::quote_from_accounts(&swap_input(&pool), pool_id, &accounts).unwrap();
+        let instruction = quote
+            .swap_instruction(
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+            )
+            .unwrap();
+        assert_eq!(
+            instruction.accounts[13..],
+            [
+                AccountMeta::new_readonly(tick_array_bitmap_extension_key(&pool_id), false),
+                AccountMeta::new(tick_array_key(&pool_id, 0), false),
+                AccountMeta::new(tick_array_key(&pool_id, -60), false),
+            ]
+        );
+    }
+}
@@ -0,0 +1,500 @@
+//! Fixed point math used by the raydium clmm program. Ported from the on-chain implementation
+//! with panics replaced by `None` so a bad pool can't bring down the caller
+
+use uint::construct_uint;
+
+construct_uint! {
+    pub struct U256(4);
+}
+
+pub const MIN_TICK: i32 = -443636;
+pub const MAX_TICK: i32 = -MIN_TICK;
+pub const MIN_SQRT_PRICE_X64: u128 = 4295048016;
+pub const MAX_SQRT_PRICE_X64: u128 = 79226673521066979257578248091;
+/// Fee rates are denominated in hundredths of a bip (10^-6)
+pub const FEE_RATE_DENOMINATOR_VALUE: u32 = 1_000_000;
+
+const Q64: u128 = (u64::MAX as u128) + 1;
+const RESOLUTION: u8 = 64;
+const BIT_PRECISION: u32 = 16;
+
+fn mul_div_floor(a: U256, b: U256, denominator: U256) -> Option<U256> {
+    if denominator.is_zero() {
+        return None;
+    }
+    let r = a.checked_mul(b)? / denominator;
+    (r <= U256::from(u128::MAX)).then_some(r)
+}
+
+fn mul_div_ceil(a: U256, b: U256, denominator: U256) -> Option<U256> {
+    if denominator.is_zero() {
+        return None;
+    }
+    let r = a.checked_mul(b)?.checked_add(denominator - U256::one())? / denominator;
+    (r <= U256::from(u128::MAX)).then_some(r)
+}
+
+fn div_rounding_up(x: U256, y: U256) -> Option<U256> {
+    if y.is_zero() {
+        return None;
+    }
+    let (quotient, remainder) = x.div_mod(y);
+    Some(if remainder.is_zero() {
+        quotient
+    } else {
+        quotient + U256::one()
+    })
+}
+
+fn to_u64(x: U256) -> Option<u64> {
+    u64::try_from(x).ok()
+}
+
+fn to_u128(x: U256) -> Option<u128> {
+    u128::try_from(x).ok()
+}
+
+/// Calculates 1.0001^(tick/2) as a Q64.64 number
+pub fn get_sqrt_price_at_tick(tick: i32) -> Option<u128> {
+    let abs_tick = tick.unsigned_abs();
+    if abs_tick > MAX_TICK as u32 {
+        return None;
+    }
+
+    // i = 0
+    let mut ratio: u128 = if abs_tick & 0x1 != 0 {
+        0xfffcb933bd6fb800
+    } else {
+        // 2^64
+        Q64
+    };
+    for (bit, multiplier) in [
+        (0x2, 0xfff97272373d4000u128),
+        (0x4, 0xfff2e50f5f657000),
+        (0x8, 0xffe5caca7e10f000),
+        (0x10, 0xffcb9843d60f7000),
+        (0x20, 0xff973b41fa98e800),
+        (0x40, 0xff2ea16466c9b000),
+        (0x80, 0xfe5dee046a9a3800),
+        (0x100, 0xfcbe86c7900bb000),
+        (0x200, 0xf987a7253ac65800),
+        (0x400, 0xf3392b0822bb6000),
+        (0x800, 0xe7159475a2caf000),
+        (0x1000, 0xd097f3bdfd2f2000),
+        (0x2000, 0xa9f746462d9f8000),
+        (0x4000, 0x70d869a156f31c00),
+        (0x8000, 0x31be135f97ed3200),
+        (0x10000, 0x9aa508b5b85a500),
+        (0x20000, 0x5d6af8dedc582c),
+        (0x40000, 0x2216e584f5fa),
+    ] {
+        if abs_tick & bit != 0 {
+            ratio = (ratio * multiplier) >> RESOLUTION;
+        }
+    }
+
+    // Divide to obtain 1.0001^(2^(i - 1)) * 2^32 in numerator
+    if tick > 0 {
+        ratio = u128::MAX / ratio;
+    }
+
+    Some(ratio)
+}
+
+/// Calculates the greatest tick value such that get_sqrt_price_at_tick(tick) <= sqrt_price_x64
+pub fn get_tick_at_sqrt_price(sqrt_price_x64: u128) -> Option<i32> {
+    // second inequality must be < because the price can never reach the price at the max tick
+    if !(MIN_SQRT_PRICE_X64..MAX_SQRT_PRICE_X64).contains(&sqrt_price_x64) {
+        return None;
+    }
+
+    // Determine log_b(sqrt_ratio). First by calculating integer portion (msb)
+    let msb: u32 = 128 - sqrt_price_x64.leading_zeros() - 1;
+    let log2p_integer_x32 = (msb as i128 - 64) << 32;
+
+    // get fractional value (r/2^msb), msb always > 128
+    // We begin the iteration from bit 63 (0.5 in Q64.64)
+    let mut bit: i128 = 0x8000_0000_0000_0000i128;
+    let mut precision = 0;
+    let mut log2p_fraction_x64 = 0;
+
+    let mut r = if msb >= 64 {
+        sqrt_price_x64 >> (msb - 63)
+    } else {
+        sqrt_price_x64 << (63 - msb)
+    };
+
+    while bit > 0 && precision < BIT_PRECISION {
+        r *= r;
+        let is_r_more_than_two = r >> 127_u32;
+        r >>= 63 + is_r_more_than_two;
+        log2p_fraction_x64 += bit * is_r_more_than_two as i128;
+        bit >>= 1;
+        precision += 1;
+    }
+    let log2p_fraction_x32 = log2p_fraction_x64 >> 32;
+    let log2p_x32 = log2p_integer_x32 + log2p_fraction_x32;
+
+    // Change of base rule: multiply with 2^16 / log2 (√1.0001)
+    let log_sqrt_10001_x64 = log2p_x32 * 59543866431248i128;
+
+    // tick - 0.01
+    let tick_low = ((log_sqrt_10001_x64 - 184467440737095516i128) >> 64) as i32;
+
+    // tick + (2^-14 / log2(√1.001)) + 0.01
+    let tick_high = ((log_sqrt_10001_x64 + 15793534762490258745i128) >> 64) as i32;
+
+    Some(if tick_low == tick_high {
+        tick_low
+    } else if get_sqrt_price_at_tick(tick_high)? <= sqrt_price_x64 {
+        tick_high
+    } else {
+        tick_low
+    })
+}
+
+/// Adds a signed liquidity delta to liquidity
+pub fn add_delta(x: u128, y: i128) -> Option<u128> {
+    if y < 0 {
+        x.checked_sub(y.unsigned_abs())
+    } else {
+        x.checked_add(y as u128)
+    }
+}
+
+/// Gets the amount-0 delta between two prices
+pub fn get_delta_amount_0_unsigned(
+    mut sqrt_ratio_a_x64: u128,
+    mut sqrt_ratio_b_x64: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Option<u64> {
+    // sqrt_ratio_a_x64 should hold the smaller value
+    if sqrt_ratio_a_x64 > sqrt_ratio_b_x64 {
+        std::mem::swap(&mut sqrt_ratio_a_x64, &mut sqrt_ratio_b_x64);
+    };
+    if sqrt_ratio_a_x64 == 0 {
+        return None;
+    }
+
+    let numerator_1 = U256::from(liquidity) << RESOLUTION;
+    let numerator_2 = U256::from(sqrt_ratio_b_x64 - sqrt_ratio_a_x64);
+
+    let amount = if round_up {
+        div_rounding_up(
+            mul_div_ceil(numerator_1, numerator_2, U256::from(sqrt_ratio_b_x64))?,
+            U256::from(sqrt_ratio_a_x64),
+        )?
+    } else {
+        mul_div_floor(numerator_1, numerator_2, U256::from(sqrt_ratio_b_x64))?
+            / U256::from(sqrt_ratio_a_x64)
+    };
+    to_u64(amount)
+}
+
+/// Gets the amount-1 delta between two prices
+pub fn get_delta_amount_1_unsigned(
+    mut sqrt_ratio_a_x64: u128,
+    mut sqrt_ratio_b_x64: u128,
+    liquidity: u128,
+    round_up: bool,
+) -> Option<u64> {
+    // sqrt_ratio_a_x64 should hold the smaller value
+    if sqrt_ratio_a_x64 > sqrt_ratio_b_x64 {
+        std::mem::swap(&mut sqrt_ratio_a_x64, &mut sqrt_ratio_b_x64);
+    };
+
+    let amount = if round_up {
+        mul_div_ceil(
+            U256::from(liquidity),
+            U256::from(sqrt_ratio_b_x64 - sqrt_ratio_a_x64),
+            U256::from(Q64),
+        )?
+    } else {
+        mul_div_floor(
+            U256::from(liquidity),
+            U256::from(sqrt_ratio_b_x64 - sqrt_ratio_a_x64),
+            U256::from(Q64),
+        )?
+    };
+    to_u64(amount)
+}
+
+fn get_next_sqrt_price_from_amount_0_rounding_up(
+    sqrt_price_x64: u128,
+    liquidity: u128,
+    amount: u64,
+    add: bool,
+) -> Option<u128> {
+    if amount == 0 {
+        return Some(sqrt_price_x64);
+    };
+    let numerator_1 = U256::from(liquidity) << RESOLUTION;
+
+    if add {
+        if let Some(product) = U256::from(amount).checked_mul(U256::from(sqrt_price_x64)) {
+            let denominator = numerator_1.checked_add(product);
+            if let Some(denominator) = denominator.filter(|d| *d >= numerator_1) {
+                return to_u128(mul_div_ceil(
+                    numerator_1,
+                    U256::from(sqrt_price_x64),
+                    denominator,
+                )?);
+            };
+        }
+
+        to_u128(div_rounding_up(
+            numerator_1,
+            (numerator_1 / U256::from(sqrt_price_x64)).checked_add(U256::from(amount))?,
+        )?)
+    } else {
+        let product = U256::from(amount).checked_mul(U256::from(sqrt_price_x64))?;
+        let denominator = numerator_1.checked_sub(product)?;
+        to_u128(mul_div_ceil(
+            numerator_1,
+            U256::from(sqrt_price_x64),
+            denominator,
+        )?)
+    }
+}
+
+fn get_next_sqrt_price_from_amount_1_rounding_down(
+    sqrt_price_x64: u128,
+    liquidity: u128,
+    amount: u64,
+    add: bool,
+) -> Option<u128> {
+    if liquidity == 0 {
+        return None;
+    }
+    let numerator = U256::from(u128::from(amount)) << RESOLUTION;
+    if add {
+        let quotient = numerator / U256::from(liquidity);
+        sqrt_price_x64.checked_add(to_u128(quotient)?)
+    } else {
+        let quotient = div_rounding_up(numerator, U256::from(liquidity))?;
+        sqrt_price_x64.checked_sub(to_u128(quotient)?)
+    }
+}
+
+fn get_next_sqrt_price_from_input(
+    sqrt_price_x64: u128,
+    liquidity: u128,
+    amount_in: u64,
+    zero_for_one: bool,
+) -> Option<u128> {
+    if sqrt_price_x64 == 0 || liquidity == 0 {
+        return None;
+    }
+    if zero_for_one {
+        get_next_sqrt_price_from_amount_0_rounding_up(sqrt_price_x64, liquidity, amount_in, true)
+    } else {
+        get_next_sqrt_price_from_amount_1_rounding_down(sqrt_price_x64, liquidity, amount_in, true)
+    }
+}
+
+fn get_next_sqrt_price_from_output(
+    sqrt_price_x64: u128,
+    liquidity: u128,
+    amount_out: u64,
+    zero_for_one: bool,
+) -> Option<u128> {
+    if sqrt_price_x64 == 0 || liquidity == 0 {
+        return None;
+    }
+    if zero_for_one {
+        get_next_sqrt_price_from_amount_1_rounding_down(
+            sqrt_price_x64,
+            liquidity,
+            amount_out,
+            false,
+        )
+    } else {
+        get_next_sqrt_price_from_amount_0_rounding_up(sqrt_price_x64, liquidity, amount_out, false)
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct SwapStep {
+    pub sqrt_price_next_x64: u128,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+}
+
+/// Computes the result of swapping some amount in, or amount out, given the parameters of the swap
+pub fn compute_swap_step(
+    sqrt_price_current_x64: u128,
+    sqrt_price_target_x64: u128,
+    liquidity: u128,
+    amount_remaining: u64,
+    fee_rate: u32,
+    is_base_input: bool,
+    zero_for_one: bool,
+) -> Option<SwapStep> {
+    let fee_rate_complement = FEE_RATE_DENOMINATOR_VALUE.checked_sub(fee_rate)?;
+    let mut swap_step = SwapStep::default();
+    if is_base_input {
+        let amount_remaining_less_fee = to_u64(mul_div_floor(
+            U256::from(amount_remaining),
+            U256::from(fee_rate_complement),
+            U256::from(FEE_RATE_DENOMINATOR_VALUE),
+        )?)?;
+        // The amount needed to reach the target may not fit in a u64, in which case the target
+        // can't be reached with the remaining amount
+        let amount_in = if zero_for_one {
+            get_delta_amount_0_unsigned(
+                sqrt_price_target_x64,
+                sqrt_price_current_x64,
+                liquidity,
+                true,
+            )
+        } else {
+            get_delta_amount_1_unsigned(
+                sqrt_price_current_x64,
+                sqrt_price_target_x64,
+                liquidity,
+                true,
+            )
+        };
+        if let Some(amount_in) = amount_in {
+            swap_step.amount_in = amount_in;
+        }
+        swap_step.sqrt_price_next_x64 = match amount_in {
+            Some(amount_in) if amount_remaining_less_fee >= amount_in => sqrt_price_target_x64,
+            _ => get_next_sqrt_price_from_input(
+                sqrt_price_current_x64,
+                liquidity,
+                amount_remaining_less_fee,
+                zero_for_one,
+            )?,
+        };
+    } else {
+        let amount_out = if zero_for_one {
+            get_delta_amount_1_unsigned(
+                sqrt_price_target_x64,
+                sqrt_price_current_x64,
+                liquidity,
+                false,
+            )
+        } else {
+            get_delta_amount_0_unsigned(
+                sqrt_price_current_x64,
+                sqrt_price_target_x64,
+                liquidity,
+                false,
+            )
+        };
+        if let Some(amount_out) = amount_out {
+            swap_step.amount_out = amount_out;
+        }
+        swap_step.sqrt_price_next_x64 = match amount_out {
+            Some(amount_out) if amount_remaining >= amount_out => sqrt_price_target_x64,
+            _ => get_next_sqrt_price_from_output(
+                sqrt_price_current_x64,
+                liquidity,
+                amount_remaining,
+                zero_for_one,
+            )?,
+        };
+    }
+
+    // whether we reached the target price
+    let max = sqrt_price_target_x64 == swap_step.sqrt_price_next_x64;
+    if zero_for_one {
+        if !(max && is_base_input) {
+            swap_step.amount_in = get_delta_amount_0_unsigned(
+                swap_step.sqrt_price_next_x64,
+                sqrt_price_current_x64,
+                liquidity,
+                true,
+            )?;
+        };
+        if !(max && !is_base_input) {
+            swap_step.amount_out = get_delta_amount_1_unsigned(
+                swap_step.sqrt_price_next_x64,
+                sqrt_price_current_x64,
+                liquidity,
+                false,
+            )?;
+        };
+    } else {
+        if !(max && is_base_input) {
+            swap_step.amount_in = get_delta_amount_1_unsigned(
+                sqrt_price_current_x64,
+                swap_step.sqrt_price_next_x64,
+                liquidity,
+                true,
+            )?;
+        };
+        if !(max && !is_base_input) {
+            swap_step.amount_out = get_delta_amount_0_unsigned(
+                sqrt_price_current_x64,
+                swap_step.sqrt_price_next_x64,
+                liquidity,
+                false,
+            )?;
+        };
+    }
+
+    // For exact output case, cap the output amount to not exceed the remaining output amount
+    if !is_base_input && swap_step.amount_out > amount_remaining {
+        swap_step.amount_out = amount_remaining;
+    }
+
+    swap_step.fee_amount =
+        if is_base_input && swap_step.sqrt_price_next_x64 != sqrt_price_target_x64 {
+            // we didn't reach the target, so take the remainder of the maximum input as fee
+            amount_remaining.checked_sub(swap_step.amount_in)?
+        } else {
+            // take pip percentage as fee
+            to_u64(mul_div_ceil(
+                U256::from(swap_step.amount_in),
+                U256::from(fee_rate),
+                U256::from(fee_rate_complement),
+            )?)?
+        };
+
+    Some(swap_step)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn sqrt_price_at_tick_bounds() {
+        assert_eq!(get_sqrt_price_at_tick(MIN_TICK), Some(MIN_SQRT_PRICE_X64));
+        assert_eq!(get_sqrt_price_at_tick(MAX_TICK), Some(MAX_SQRT_PRICE_X64));
+        assert_eq!(get_sqrt_price_at_tick(0), Some(Q64));
+        assert!(get_sqrt_price_at_tick(MAX_TICK + 1).is_none());
+    }
+
+    #[test]
+    pub fn tick_at_sqrt_price_round_trip() {
+        for tick in [MIN_TICK, -100_000, -1, 0, 1, 60, 100_000, MAX_TICK - 1] {
+            let sqrt_price_x64 = get_sqrt_price_at_tick(tick).unwrap();
+            assert_eq!(get_tick_at_sqrt_price(sqrt_price_x64), Some(tick));
+        }
+    }
+
+    #[test]
+    pub fn swap_step_within_range_consumes_remaining_input() {
+        let sqrt_price_current_x64 = get_sqrt_price_at_tick(0).unwrap();
+        let sqrt_price_target_x64 = get_sqrt_price_at_tick(-600).unwrap();
+        let step = compute_swap_step(
+            sqrt_price_current_x64,
+            sqrt_price_target_x64,
+            1_000_000_000_000,
+            1_000_000,
+            2_500,
+            true,
+            true,
+        )
+        .unwrap();
+        assert!(step.sqrt_price_next_x64 < sqrt_price_current_x64);
+        assert!(step.sqrt_price_next_x64 > sqrt_price_target_x64);
+        assert_eq!(step.amount_in + step.fee_amount, 1_000_000);
+        assert!(step.amount_out < 1_000_000);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod executor;
+mod math;
+mod pool_state;
+mod swap;
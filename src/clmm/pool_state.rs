@@ -0,0 +1,612 @@
+//! Account layouts for the raydium clmm program along with the tick-array bitmap traversal
+//! needed to find the tick arrays a swap will cross
+
+use super::math::{MAX_TICK, MIN_TICK};
+use crate::error::{Result, SwapError};
+use bytemuck::{Pod, Zeroable};
+use solana_program::pubkey::Pubkey;
+
+const DISCRIMINATOR_LEN: usize = 8;
+pub const REWARD_NUM: usize = 3;
+pub const TICK_ARRAY_SIZE: i32 = 60;
+pub const TICK_ARRAY_SIZE_USIZE: usize = 60;
+/// Number of tick arrays tracked by each bitmap in either direction
+const TICK_ARRAY_BITMAP_SIZE: i32 = 512;
+const EXTENSION_TICKARRAY_BITMAP_SIZE: usize = 14;
+
+macro_rules! impl_account {
+    ($type_name:ident, $discriminator:expr) => {
+        unsafe impl Zeroable for $type_name {}
+        unsafe impl Pod for $type_name {}
+
+        impl $type_name {
+            pub const DISCRIMINATOR: [u8; 8] = $discriminator;
+            pub const LEN: usize = DISCRIMINATOR_LEN + std::mem::size_of::<$type_name>();
+
+            pub fn try_deserialize(data: &[u8]) -> Result<Self> {
+                if data.len() < Self::LEN {
+                    return Err(SwapError::DecodeError(format!(
+                        "{} account: invalid length. expected {} got {}",
+                        stringify!($type_name),
+                        Self::LEN,
+                        data.len()
+                    )));
+                }
+                if data[..DISCRIMINATOR_LEN] != Self::DISCRIMINATOR {
+                    return Err(SwapError::DecodeError(format!(
+                        "{} account: invalid discriminator",
+                        stringify!($type_name)
+                    )));
+                }
+                Ok(bytemuck::pod_read_unaligned(
+                    &data[DISCRIMINATOR_LEN..Self::LEN],
+                ))
+            }
+        }
+    };
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RewardInfo {
+    /// Reward state
+    pub reward_state: u8,
+    /// Reward open time
+    pub open_time: u64,
+    /// Reward end time
+    pub end_time: u64,
+    /// Reward last update time
+    pub last_update_time: u64,
+    /// Q64.64 number indicates how many tokens per second are earned per unit of liquidity.
+    pub emissions_per_second_x64: u128,
+    /// The total amount of reward emissioned
+    pub reward_total_emissioned: u64,
+    /// The total amount of claimed reward
+    pub reward_claimed: u64,
+    /// Reward token mint.
+    pub token_mint: Pubkey,
+    /// Reward vault token account.
+    pub token_vault: Pubkey,
+    /// The owner that has permission to set reward param
+    pub authority: Pubkey,
+    /// Q64.64 number that tracks the total tokens earned per unit of liquidity since the reward
+    /// emissions were turned on.
+    pub reward_growth_global_x64: u128,
+}
+unsafe impl Zeroable for RewardInfo {}
+unsafe impl Pod for RewardInfo {}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PoolState {
+    /// Bump to identify PDA
+    pub bump: [u8; 1],
+    /// Which config the pool belongs
+    pub amm_config: Pubkey,
+    /// Pool creator
+    pub owner: Pubkey,
+    /// Token pair of the pool, where token_mint_0 address < token_mint_1 address
+    pub token_mint_0: Pubkey,
+    pub token_mint_1: Pubkey,
+    /// Token pair vault
+    pub token_vault_0: Pubkey,
+    pub token_vault_1: Pubkey,
+    /// observation account key
+    pub observation_key: Pubkey,
+    /// mint0 and mint1 decimals
+    pub mint_decimals_0: u8,
+    pub mint_decimals_1: u8,
+    /// The minimum number of ticks between initialized ticks
+    pub tick_spacing: u16,
+    /// The currently in range liquidity available to the pool.
+    pub liquidity: u128,
+    /// The current price of the pool as a sqrt(token_1/token_0) Q64.64 value
+    pub sqrt_price_x64: u128,
+    /// The current tick of the pool, i.e. according to the last tick transition that was run.
+    pub tick_current: i32,
+    pub padding3: u16,
+    pub padding4: u16,
+    /// The fee growth as a Q64.64 number, i.e. fees of token_0 and token_1 collected per
+    /// unit of liquidity for the entire life of the pool.
+    pub fee_growth_global_0_x64: u128,
+    pub fee_growth_global_1_x64: u128,
+    /// The amounts of token_0 and token_1 that are owed to the protocol.
+    pub protocol_fees_token_0: u64,
+    pub protocol_fees_token_1: u64,
+    /// The amounts in and out of swap token_0 and token_1
+    pub swap_in_amount_token_0: u128,
+    pub swap_out_amount_token_1: u128,
+    pub swap_in_amount_token_1: u128,
+    pub swap_out_amount_token_0: u128,
+    /// Bitwise representation of the state of the pool
+    /// bit0, 1: disable open position and increase liquidity, 0: normal
+    /// bit1, 1: disable decrease liquidity, 0: normal
+    /// bit2, 1: disable collect fee, 0: normal
+    /// bit3, 1: disable collect reward, 0: normal
+    /// bit4, 1: disable swap, 0: normal
+    pub status: u8,
+    /// Leave blank for future use
+    pub padding: [u8; 7],
+    pub reward_infos: [RewardInfo; REWARD_NUM],
+    /// Packed initialized tick array state
+    pub tick_array_bitmap: [u64; 16],
+    /// except protocol_fee and fund_fee
+    pub total_fees_token_0: u64,
+    /// except protocol_fee and fund_fee
+    pub total_fees_claimed_token_0: u64,
+    pub total_fees_token_1: u64,
+    pub total_fees_claimed_token_1: u64,
+    pub fund_fees_token_0: u64,
+    pub fund_fees_token_1: u64,
+    /// The timestamp allowed for swap in the pool.
+    pub open_time: u64,
+    /// account recent update epoch
+    pub recent_epoch: u64,
+    /// Unused bytes for future upgrades.
+    pub padding1: [u64; 24],
+    pub padding2: [u64; 32],
+}
+impl_account!(PoolState, [247, 237, 227, 245, 215, 195, 222, 70]);
+
+impl PoolState {
+    const SWAP_DISABLED_BIT: u8 = 1 << 4;
+
+    pub fn swap_enabled(&self) -> bool {
+        self.status & Self::SWAP_DISABLED_BIT == 0
+    }
+
+    /// Returns whether the tick array containing `tick_index` lies outside the range covered by
+    /// the pool's own bitmap, and so is tracked by the bitmap extension
+    fn is_overflow_default_tickarray_bitmap(&self, tick_index: i32) -> bool {
+        let tick_array_start_index = get_array_start_index(tick_index, self.tick_spacing);
+        let max_tick_boundary = max_tick_in_tickarray_bitmap(self.tick_spacing).min(MAX_TICK);
+        let min_tick_boundary = (-max_tick_in_tickarray_bitmap(self.tick_spacing)).max(MIN_TICK);
+        tick_array_start_index >= max_tick_boundary || tick_array_start_index < min_tick_boundary
+    }
+
+    /// Returns the start index of the first tick array a swap will use, and whether that array
+    /// contains the current tick
+    pub fn get_first_initialized_tick_array(
+        &self,
+        tickarray_bitmap_extension: Option<&TickArrayBitmapExtension>,
+        zero_for_one: bool,
+    ) -> Result<(bool, i32)> {
+        let (is_initialized, start_index) =
+            if self.is_overflow_default_tickarray_bitmap(self.tick_current) {
+                tickarray_bitmap_extension
+                    .ok_or_else(|| {
+                        SwapError::AccountMissing(
+                            "clmm tick array bitmap extension for the current tick".to_string(),
+                        )
+                    })?
+                    .check_tick_array_is_initialized(
+                        get_array_start_index(self.tick_current, self.tick_spacing),
+                        self.tick_spacing,
+                    )?
+            } else {
+                check_current_tick_array_is_initialized(
+                    &{ self.tick_array_bitmap },
+                    self.tick_current,
+                    self.tick_spacing,
+                )
+            };
+        if is_initialized {
+            return Ok((true, start_index));
+        }
+        let next_start_index = self
+            .next_initialized_tick_array_start_index(
+                tickarray_bitmap_extension,
+                get_array_start_index(self.tick_current, self.tick_spacing),
+                zero_for_one,
+            )?
+            .ok_or_else(|| {
+                SwapError::InsufficientLiquidity(
+                    "no initialized tick arrays in the swap direction".to_string(),
+                )
+            })?;
+        Ok((false, next_start_index))
+    }
+
+    /// Returns the start index of the next initialized tick array after
+    /// `last_tick_array_start_index` in the swap direction
+    pub fn next_initialized_tick_array_start_index(
+        &self,
+        tickarray_bitmap_extension: Option<&TickArrayBitmapExtension>,
+        last_tick_array_start_index: i32,
+        zero_for_one: bool,
+    ) -> Result<Option<i32>> {
+        let mut last_tick_array_start_index =
+            get_array_start_index(last_tick_array_start_index, self.tick_spacing);
+
+        loop {
+            let (is_found, start_index) = next_initialized_tick_array_in_default_bitmap(
+                &{ self.tick_array_bitmap },
+                last_tick_array_start_index,
+                self.tick_spacing,
+                zero_for_one,
+            );
+            if is_found {
+                return Ok(Some(start_index));
+            }
+            last_tick_array_start_index = start_index;
+
+            // pools without an extension only have liquidity within the default bitmap
+            let Some(tickarray_bitmap_extension) = tickarray_bitmap_extension else {
+                return Ok(None);
+            };
+            let (is_found, start_index) = tickarray_bitmap_extension
+                .next_initialized_tick_array_from_one_bitmap(
+                    last_tick_array_start_index,
+                    self.tick_spacing,
+                    zero_for_one,
+                )?;
+            if is_found {
+                return Ok(Some(start_index));
+            }
+            last_tick_array_start_index = start_index;
+
+            if !(MIN_TICK..=MAX_TICK).contains(&last_tick_array_start_index) {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AmmConfig {
+    /// Bump to identify PDA
+    pub bump: u8,
+    pub index: u16,
+    /// Address of the protocol owner
+    pub owner: Pubkey,
+    /// The protocol fee
+    pub protocol_fee_rate: u32,
+    /// The trade fee, denominated in hundredths of a bip (10^-6)
+    pub trade_fee_rate: u32,
+    /// The tick spacing
+    pub tick_spacing: u16,
+    /// The fund fee, denominated in hundredths of a bip (10^-6)
+    pub fund_fee_rate: u32,
+    pub padding_u32: u32,
+    pub fund_owner: Pubkey,
+    pub padding: [u64; 3],
+}
+impl_account!(AmmConfig, [218, 244, 33, 104, 203, 203, 43, 111]);
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TickState {
+    pub tick: i32,
+    /// Amount of net liquidity added (subtracted) when tick is crossed from left to right (right to left)
+    pub liquidity_net: i128,
+    /// The total position liquidity that references this tick
+    pub liquidity_gross: u128,
+    /// Fee growth per unit of liquidity on the _other_ side of this tick (relative to the current tick)
+    /// only has relative meaning, not absolute — the value depends on when the tick is initialized
+    pub fee_growth_outside_0_x64: u128,
+    pub fee_growth_outside_1_x64: u128,
+    /// Reward growth per unit of liquidity like fee, array of Q64.64
+    pub reward_growths_outside_x64: [u128; REWARD_NUM],
+    /// Unused bytes for future upgrades.
+    pub padding: [u32; 13],
+}
+unsafe impl Zeroable for TickState {}
+unsafe impl Pod for TickState {}
+
+impl TickState {
+    pub fn is_initialized(&self) -> bool {
+        let liquidity_gross = self.liquidity_gross;
+        liquidity_gross != 0
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TickArrayState {
+    pub pool_id: Pubkey,
+    pub start_tick_index: i32,
+    pub ticks: [TickState; TICK_ARRAY_SIZE_USIZE],
+    pub initialized_tick_count: u8,
+    /// account update recent epoch
+    pub recent_epoch: u64,
+    /// Unused bytes for future upgrades.
+    pub padding: [u8; 107],
+}
+impl_account!(TickArrayState, [192, 155, 85, 205, 49, 249, 129, 42]);
+
+impl TickArrayState {
+    /// Returns the first initialized tick in the array in the swap direction
+    pub fn first_initialized_tick(&self, zero_for_one: bool) -> Option<TickState> {
+        let ticks = self.ticks;
+        if zero_for_one {
+            ticks.into_iter().rev().find(TickState::is_initialized)
+        } else {
+            ticks.into_iter().find(TickState::is_initialized)
+        }
+    }
+
+    /// Returns the next initialized tick in the array after `current_tick_index` in the swap
+    /// direction. Moving left includes the current tick; moving right excludes it.
+    pub fn next_initialized_tick(
+        &self,
+        current_tick_index: i32,
+        tick_spacing: u16,
+        zero_for_one: bool,
+    ) -> Option<TickState> {
+        let start_tick_index = self.start_tick_index;
+        if get_array_start_index(current_tick_index, tick_spacing) != start_tick_index {
+            return None;
+        }
+        let offset_in_array =
+            ((current_tick_index - start_tick_index) / i32::from(tick_spacing)) as usize;
+        let ticks = self.ticks;
+        if zero_for_one {
+            ticks[..=offset_in_array]
+                .iter()
+                .rev()
+                .find(|tick| tick.is_initialized())
+                .copied()
+        } else {
+            ticks[offset_in_array + 1..]
+                .iter()
+                .find(|tick| tick.is_initialized())
+                .copied()
+        }
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TickArrayBitmapExtension {
+    pub pool_id: Pubkey,
+    /// Packed initialized tick array state for start_tick_index is positive
+    pub positive_tick_array_bitmap: [[u64; 8]; EXTENSION_TICKARRAY_BITMAP_SIZE],
+    /// Packed initialized tick array state for start_tick_index is negitive
+    pub negative_tick_array_bitmap: [[u64; 8]; EXTENSION_TICKARRAY_BITMAP_SIZE],
+}
+impl_account!(
+    TickArrayBitmapExtension,
+    [60, 150, 36, 219, 97, 128, 139, 153]
+);
+
+impl TickArrayBitmapExtension {
+    fn get_bitmap(&self, tick_index: i32, tick_spacing: u16) -> Result<[u64; 8]> {
+        let ticks_in_one_bitmap = max_tick_in_tickarray_bitmap(tick_spacing);
+        if tick_index >= -ticks_in_one_bitmap && tick_index < ticks_in_one_bitmap {
+            return Err(SwapError::Math(format!(
+                "Tick {} is covered by the default bitmap, not the extension",
+                tick_index
+            )));
+        }
+        let mut offset = tick_index.abs() / ticks_in_one_bitmap - 1;
+        if tick_index < 0 && tick_index.abs() % ticks_in_one_bitmap == 0 {
+            offset -= 1;
+        }
+        let bitmaps = if tick_index < 0 {
+            self.negative_tick_array_bitmap
+        } else {
+            self.positive_tick_array_bitmap
+        };
+        bitmaps.get(offset as usize).copied().ok_or_else(|| {
+            SwapError::Math(format!(
+                "Tick {} is out of the extension bounds",
+                tick_index
+            ))
+        })
+    }
+
+    fn check_tick_array_is_initialized(
+        &self,
+        tick_array_start_index: i32,
+        tick_spacing: u16,
+    ) -> Result<(bool, i32)> {
+        let tickarray_bitmap = self.get_bitmap(tick_array_start_index, tick_spacing)?;
+        let offset = tick_array_offset_in_bitmap(tick_array_start_index, tick_spacing) as usize;
+        Ok((
+            is_bit_set(&tickarray_bitmap, offset),
+            tick_array_start_index,
+        ))
+    }
+
+    fn next_initialized_tick_array_from_one_bitmap(
+        &self,
+        last_tick_array_start_index: i32,
+        tick_spacing: u16,
+        zero_for_one: bool,
+    ) -> Result<(bool, i32)> {
+        let multiplier = tick_count(tick_spacing);
+        let next_tick_array_start_index = if zero_for_one {
+            last_tick_array_start_index - multiplier
+        } else {
+            last_tick_array_start_index + multiplier
+        };
+        let min_tick_array_start_index = get_array_start_index(MIN_TICK, tick_spacing);
+        let max_tick_array_start_index = get_array_start_index(MAX_TICK, tick_spacing);
+        if next_tick_array_start_index < min_tick_array_start_index
+            || next_tick_array_start_index > max_tick_array_start_index
+        {
+            return Ok((false, next_tick_array_start_index));
+        }
+
+        let tickarray_bitmap = self.get_bitmap(next_tick_array_start_index, tick_spacing)?;
+        let (bitmap_min_tick_boundary, bitmap_max_tick_boundary) =
+            get_bitmap_tick_boundary(next_tick_array_start_index, tick_spacing);
+        let offset = tick_array_offset_in_bitmap(next_tick_array_start_index, tick_spacing);
+
+        Ok(if zero_for_one {
+            match highest_set_bit_at_or_below(&tickarray_bitmap, offset as usize) {
+                Some(bit) => (
+                    true,
+                    next_tick_array_start_index - (offset - bit as i32) * multiplier,
+                ),
+                None => (false, bitmap_min_tick_boundary),
+            }
+        } else {
+            match lowest_set_bit_at_or_above(&tickarray_bitmap, offset as usize) {
+                Some(bit) => (
+                    true,
+                    next_tick_array_start_index + (bit as i32 - offset) * multiplier,
+                ),
+                None => (false, bitmap_max_tick_boundary - multiplier),
+            }
+        })
+    }
+}
+
+/// Number of ticks covered by a single tick array
+pub fn tick_count(tick_spacing: u16) -> i32 {
+    TICK_ARRAY_SIZE * i32::from(tick_spacing)
+}
+
+/// Returns the start index of the tick array containing `tick_index`
+pub fn get_array_start_index(tick_index: i32, tick_spacing: u16) -> i32 {
+    let ticks_in_array = tick_count(tick_spacing);
+    let mut start = tick_index / ticks_in_array;
+    if tick_index < 0 && tick_index % ticks_in_array != 0 {
+        start -= 1
+    }
+    start * ticks_in_array
+}
+
+fn max_tick_in_tickarray_bitmap(tick_spacing: u16) -> i32 {
+    tick_count(tick_spacing) * TICK_ARRAY_BITMAP_SIZE
+}
+
+fn get_bitmap_tick_boundary(tick_array_start_index: i32, tick_spacing: u16) -> (i32, i32) {
+    let ticks_in_one_bitmap = max_tick_in_tickarray_bitmap(tick_spacing);
+    let mut m = tick_array_start_index.abs() / ticks_in_one_bitmap;
+    if tick_array_start_index < 0 && tick_array_start_index.abs() % ticks_in_one_bitmap != 0 {
+        m += 1;
+    }
+    let min_value = ticks_in_one_bitmap * m;
+    if tick_array_start_index < 0 {
+        (-min_value, -min_value + ticks_in_one_bitmap)
+    } else {
+        (min_value, min_value + ticks_in_one_bitmap)
+    }
+}
+
+fn tick_array_offset_in_bitmap(tick_array_start_index: i32, tick_spacing: u16) -> i32 {
+    let m = tick_array_start_index.abs() % max_tick_in_tickarray_bitmap(tick_spacing);
+    let mut tick_array_offset_in_bitmap = m / tick_count(tick_spacing);
+    if tick_array_start_index < 0 && m != 0 {
+        tick_array_offset_in_bitmap = TICK_ARRAY_BITMAP_SIZE - tick_array_offset_in_bitmap;
+    }
+    tick_array_offset_in_bitmap
+}
+
+/// Position of the tick array containing `tick_index` in the pool's default bitmap
+fn compressed_bit_position(tick_index: i32, tick_spacing: u16) -> i32 {
+    let multiplier = tick_count(tick_spacing);
+    let mut compressed = tick_index / multiplier + TICK_ARRAY_BITMAP_SIZE;
+    if tick_index < 0 && tick_index % multiplier != 0 {
+        // round towards negative infinity
+        compressed -= 1;
+    }
+    compressed
+}
+
+fn check_current_tick_array_is_initialized(
+    bitmap: &[u64; 16],
+    tick_current: i32,
+    tick_spacing: u16,
+) -> (bool, i32) {
+    let compressed = compressed_bit_position(tick_current, tick_spacing);
+    let start_index = (compressed - TICK_ARRAY_BITMAP_SIZE) * tick_count(tick_spacing);
+    (is_bit_set(bitmap, compressed as usize), start_index)
+}
+
+fn next_initialized_tick_array_in_default_bitmap(
+    bitmap: &[u64; 16],
+    last_tick_array_start_index: i32,
+    tick_spacing: u16,
+    zero_for_one: bool,
+) -> (bool, i32) {
+    let tick_boundary = max_tick_in_tickarray_bitmap(tick_spacing);
+    let multiplier = tick_count(tick_spacing);
+    let next_tick_array_start_index = if zero_for_one {
+        last_tick_array_start_index - multiplier
+    } else {
+        last_tick_array_start_index + multiplier
+    };
+    if next_tick_array_start_index < -tick_boundary || next_tick_array_start_index >= tick_boundary
+    {
+        return (false, last_tick_array_start_index);
+    }
+
+    let bit_pos = compressed_bit_position(next_tick_array_start_index, tick_spacing) as usize;
+    if zero_for_one {
+        match highest_set_bit_at_or_below(bitmap, bit_pos) {
+            Some(bit) => (true, (bit as i32 - TICK_ARRAY_BITMAP_SIZE) * multiplier),
+            None => (false, -tick_boundary),
+        }
+    } else {
+        match lowest_set_bit_at_or_above(bitmap, bit_pos) {
+            Some(bit) => (true, (bit as i32 - TICK_ARRAY_BITMAP_SIZE) * multiplier),
+            None => (false, tick_boundary - multiplier),
+        }
+    }
+}
+
+fn is_bit_set(bitmap: &[u64], index: usize) -> bool {
+    bitmap
+        .get(index / 64)
+        .is_some_and(|word| word & (1 << (index % 64)) != 0)
+}
+
+fn highest_set_bit_at_or_below(bitmap: &[u64], index: usize) -> Option<usize> {
+    (0..=index).rev().find(|i| is_bit_set(bitmap, *i))
+}
+
+fn lowest_set_bit_at_or_above(bitmap: &[u64], index: usize) -> Option<usize> {
+    (index..bitmap.len() * 64).find(|i| is_bit_set(bitmap, *i))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn account_lengths_match_program() {
+        assert_eq!(PoolState::LEN, 1544);
+        assert_eq!(AmmConfig::LEN, 117);
+        assert_eq!(TickArrayState::LEN, 10240);
+        assert_eq!(TickArrayBitmapExtension::LEN, 1832);
+    }
+
+    #[test]
+    pub fn default_bitmap_traversal() {
+        let tick_spacing = 10;
+        let mut bitmap = [0u64; 16];
+        // tick arrays starting at -1200 and 600
+        for start_index in [-1200, 600] {
+            let bit = compressed_bit_position(start_index, tick_spacing) as usize;
+            bitmap[bit / 64] |= 1 << (bit % 64);
+        }
+
+        assert_eq!(
+            next_initialized_tick_array_in_default_bitmap(&bitmap, 0, tick_spacing, true),
+            (true, -1200)
+        );
+        assert_eq!(
+            next_initialized_tick_array_in_default_bitmap(&bitmap, 0, tick_spacing, false),
+            (true, 600)
+        );
+        assert_eq!(
+            next_initialized_tick_array_in_default_bitmap(&bitmap, 600, tick_spacing, false),
+            (
+                false,
+                max_tick_in_tickarray_bitmap(tick_spacing) - tick_count(tick_spacing)
+            )
+        );
+        assert_eq!(
+            check_current_tick_array_is_initialized(&bitmap, -599, tick_spacing),
+            (false, -600)
+        );
+        assert_eq!(
+            check_current_tick_array_is_initialized(&bitmap, -601, tick_spacing),
+            (true, -1200)
+        );
+    }
+}
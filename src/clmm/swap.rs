@@ -0,0 +1,300 @@
+//! Off-chain replica of the clmm swap loop, walking initialized ticks across tick arrays
+
+use super::math::{self, MAX_SQRT_PRICE_X64, MAX_TICK, MIN_SQRT_PRICE_X64, MIN_TICK};
+use super::pool_state::{
+    AmmConfig, PoolState, TickArrayBitmapExtension, TickArrayState, TickState,
+};
+use crate::error::{Result, SwapError};
+use bytemuck::Zeroable;
+
+#[derive(Debug)]
+pub struct SwapSimulation {
+    /// The amount of the unspecified token, i.e. the amount out for base-input swaps and the
+    /// amount in (including fees) for base-output swaps
+    pub amount_calculated: u64,
+    /// Start indexes of the tick arrays the swap traverses, in traversal order
+    pub tick_array_start_indexes: Vec<i32>,
+}
+
+/// Returns the start indexes of up to `count` initialized tick arrays a swap in the given
+/// direction would traverse, starting from the pool's current tick
+pub fn tick_array_start_indexes(
+    pool: &PoolState,
+    bitmap_extension: Option<&TickArrayBitmapExtension>,
+    zero_for_one: bool,
+    count: usize,
+) -> Result<Vec<i32>> {
+    let (_, mut start_index) =
+        pool.get_first_initialized_tick_array(bitmap_extension, zero_for_one)?;
+    let mut start_indexes = vec![start_index];
+    while start_indexes.len() < count {
+        match pool.next_initialized_tick_array_start_index(
+            bitmap_extension,
+            start_index,
+            zero_for_one,
+        )? {
+            Some(next_start_index) => {
+                start_indexes.push(next_start_index);
+                start_index = next_start_index;
+            }
+            None => break,
+        }
+    }
+    Ok(start_indexes)
+}
+
+/// Simulates a swap of `amount_specified` against the pool using the provided tick arrays.
+///
+/// Fails if the swap needs a tick array that was not provided or if the pool runs out of
+/// liquidity before the full amount is swapped.
+pub fn simulate_swap(
+    pool: &PoolState,
+    amm_config: &AmmConfig,
+    bitmap_extension: Option<&TickArrayBitmapExtension>,
+    tick_arrays: &[TickArrayState],
+    amount_specified: u64,
+    zero_for_one: bool,
+    is_base_input: bool,
+) -> Result<SwapSimulation> {
+    if amount_specified == 0 {
+        return Err(SwapError::InvalidInput(
+            "Swap amount must be greater than zero".to_string(),
+        ));
+    }
+    let tick_spacing = pool.tick_spacing;
+    let trade_fee_rate = amm_config.trade_fee_rate;
+    let sqrt_price_limit_x64 = if zero_for_one {
+        MIN_SQRT_PRICE_X64 + 1
+    } else {
+        MAX_SQRT_PRICE_X64 - 1
+    };
+    let find_tick_array = |start_index: i32| {
+        tick_arrays
+            .iter()
+            .find(|tick_array| { tick_array.start_tick_index } == start_index)
+            .copied()
+            .ok_or_else(|| {
+                SwapError::AccountMissing(format!(
+                    "clmm tick array starting at {} was not loaded for the swap",
+                    start_index
+                ))
+            })
+    };
+
+    let mut amount_specified_remaining = amount_specified;
+    let mut amount_calculated = 0u64;
+    let mut sqrt_price_x64 = pool.sqrt_price_x64;
+    let mut tick = pool.tick_current;
+    let mut liquidity = pool.liquidity;
+
+    let (mut is_match_pool_current_tick_array, first_start_index) =
+        pool.get_first_initialized_tick_array(bitmap_extension, zero_for_one)?;
+    let mut current_valid_start_index = first_start_index;
+    let mut tick_array_current = find_tick_array(first_start_index)?;
+    let mut tick_array_start_indexes = vec![first_start_index];
+
+    while amount_specified_remaining != 0 && sqrt_price_x64 != sqrt_price_limit_x64 {
+        let sqrt_price_start_x64 = sqrt_price_x64;
+        let mut next_initialized_tick = if let Some(tick_state) =
+            tick_array_current.next_initialized_tick(tick, tick_spacing, zero_for_one)
+        {
+            tick_state
+        } else if !is_match_pool_current_tick_array {
+            is_match_pool_current_tick_array = true;
+            tick_array_current
+                .first_initialized_tick(zero_for_one)
+                .ok_or_else(|| SwapError::Math("Tick array has no initialized ticks".to_string()))?
+        } else {
+            TickState::zeroed()
+        };
+
+        if !next_initialized_tick.is_initialized() {
+            let next_start_index = pool
+                .next_initialized_tick_array_start_index(
+                    bitmap_extension,
+                    current_valid_start_index,
+                    zero_for_one,
+                )?
+                .ok_or_else(|| {
+                    SwapError::InsufficientLiquidity("no more tick arrays".to_string())
+                })?;
+            tick_array_current = find_tick_array(next_start_index)?;
+            tick_array_start_indexes.push(next_start_index);
+            current_valid_start_index = next_start_index;
+            next_initialized_tick = tick_array_current
+                .first_initialized_tick(zero_for_one)
+                .ok_or_else(|| {
+                    SwapError::Math("Tick array has no initialized ticks".to_string())
+                })?;
+        }
+
+        let tick_next = next_initialized_tick.tick.clamp(MIN_TICK, MAX_TICK);
+        let initialized = next_initialized_tick.is_initialized();
+        let sqrt_price_next_x64 = math::get_sqrt_price_at_tick(tick_next)
+            .ok_or_else(|| SwapError::Math("Invalid tick in tick array".to_string()))?;
+        let target_price = if (zero_for_one && sqrt_price_next_x64 < sqrt_price_limit_x64)
+            || (!zero_for_one && sqrt_price_next_x64 > sqrt_price_limit_x64)
+        {
+            sqrt_price_limit_x64
+        } else {
+            sqrt_price_next_x64
+        };
+
+        let step = math::compute_swap_step(
+            sqrt_price_x64,
+            target_price,
+            liquidity,
+            amount_specified_remaining,
+            trade_fee_rate,
+            is_base_input,
+            zero_for_one,
+        )
+        .ok_or_else(|| SwapError::Math("Failed to compute clmm swap step".to_string()))?;
+        sqrt_price_x64 = step.sqrt_price_next_x64;
+
+        let (amount_specified_step, amount_calculated_step) = if is_base_input {
+            (
+                step.amount_in.checked_add(step.fee_amount),
+                Some(step.amount_out),
+            )
+        } else {
+            (
+                Some(step.amount_out),
+                step.amount_in.checked_add(step.fee_amount),
+            )
+        };
+        amount_specified_remaining = amount_specified_step
+            .and_then(|amount| amount_specified_remaining.checked_sub(amount))
+            .ok_or_else(|| {
+                SwapError::Math("Clmm swap step exceeded the remaining amount".to_string())
+            })?;
+        amount_calculated = amount_calculated_step
+            .and_then(|amount| amount_calculated.checked_add(amount))
+            .ok_or_else(|| SwapError::Math("Clmm swap amount overflow".to_string()))?;
+
+        if sqrt_price_x64 == sqrt_price_next_x64 {
+            // crossed the next tick
+            if initialized {
+                let liquidity_net = next_initialized_tick.liquidity_net;
+                let liquidity_net = if zero_for_one {
+                    liquidity_net
+                        .checked_neg()
+                        .ok_or_else(|| SwapError::Math("Invalid tick liquidity".to_string()))?
+                } else {
+                    liquidity_net
+                };
+                liquidity = math::add_delta(liquidity, liquidity_net).ok_or_else(|| {
+                    SwapError::Math("Invalid liquidity after crossing tick".to_string())
+                })?;
+            }
+            tick = if zero_for_one {
+                tick_next - 1
+            } else {
+                tick_next
+            };
+        } else if sqrt_price_x64 != sqrt_price_start_x64 {
+            tick = math::get_tick_at_sqrt_price(sqrt_price_x64)
+                .ok_or_else(|| SwapError::Math("Invalid sqrt price after swap step".to_string()))?;
+        }
+    }
+
+    if amount_specified_remaining != 0 {
+        return Err(SwapError::InsufficientLiquidity(format!(
+            "clmm pool can't swap {} ({} remaining)",
+            amount_specified, amount_specified_remaining
+        )));
+    }
+
+    Ok(SwapSimulation {
+        amount_calculated,
+        tick_array_start_indexes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIQUIDITY: u128 = 1_000_000_000_000;
+
+    /// A pool at tick 0 with liquidity between ticks -10 and 10, spread over two tick arrays
+    fn pool_with_tick_arrays() -> (PoolState, AmmConfig, Vec<TickArrayState>) {
+        let mut pool = PoolState::zeroed();
+        pool.tick_spacing = 1;
+        pool.liquidity = LIQUIDITY;
+        pool.sqrt_price_x64 = math::get_sqrt_price_at_tick(0).unwrap();
+        pool.tick_current = 0;
+        let mut bitmap = [0u64; 16];
+        // tick arrays starting at -60 and 0
+        bitmap[7] |= 1 << 63;
+        bitmap[8] |= 1;
+        pool.tick_array_bitmap = bitmap;
+
+        let amm_config = AmmConfig {
+            trade_fee_rate: 2500,
+            tick_spacing: 1,
+            ..Default::default()
+        };
+
+        let mut lower = TickArrayState::zeroed();
+        lower.start_tick_index = -60;
+        lower.ticks[50] = TickState {
+            tick: -10,
+            liquidity_net: LIQUIDITY as i128,
+            liquidity_gross: LIQUIDITY,
+            ..Default::default()
+        };
+        let mut upper = TickArrayState::zeroed();
+        upper.start_tick_index = 0;
+        upper.ticks[10] = TickState {
+            tick: 10,
+            liquidity_net: -(LIQUIDITY as i128),
+            liquidity_gross: LIQUIDITY,
+            ..Default::default()
+        };
+        (pool, amm_config, vec![lower, upper])
+    }
+
+    #[test]
+    pub fn simulate_swap_within_tick_range() {
+        let (pool, amm_config, tick_arrays) = pool_with_tick_arrays();
+        assert_eq!(
+            tick_array_start_indexes(&pool, None, false, 5).unwrap(),
+            vec![0]
+        );
+        assert_eq!(
+            tick_array_start_indexes(&pool, None, true, 5).unwrap(),
+            vec![0, -60]
+        );
+
+        let simulation = simulate_swap(
+            &pool,
+            &amm_config,
+            None,
+            &tick_arrays,
+            1_000_000,
+            false,
+            true,
+        )
+        .unwrap();
+        // 0.25% fee at a price of ~1
+        assert!(simulation.amount_calculated < 997_500);
+        assert!(simulation.amount_calculated > 997_000);
+        assert_eq!(simulation.tick_array_start_indexes, vec![0]);
+    }
+
+    #[test]
+    pub fn simulate_swap_insufficient_liquidity() {
+        let (pool, amm_config, tick_arrays) = pool_with_tick_arrays();
+        assert!(simulate_swap(
+            &pool,
+            &amm_config,
+            None,
+            &tick_arrays,
+            10_000_000_000,
+            false,
+            true
+        )
+        .is_err());
+    }
+}
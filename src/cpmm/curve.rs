@@ -0,0 +1,80 @@
+//! Constant product curve used by the raydium cp-swap program, including its rounding behaviour
+
+/// Fee rates are denominated in hundredths of a bip (10^-6)
+const FEE_RATE_DENOMINATOR_VALUE: u128 = 1_000_000;
+
+fn ceil_div(numerator: u128, denominator: u128) -> Option<u128> {
+    numerator
+        .checked_add(denominator.checked_sub(1)?)?
+        .checked_div(denominator)
+}
+
+fn trading_fee(amount: u128, trade_fee_rate: u64) -> Option<u128> {
+    ceil_div(
+        amount.checked_mul(trade_fee_rate as u128)?,
+        FEE_RATE_DENOMINATOR_VALUE,
+    )
+}
+
+/// The amount which, after deducting the trade fee, equals `post_fee_amount`
+fn calculate_pre_fee_amount(post_fee_amount: u128, trade_fee_rate: u64) -> Option<u128> {
+    if trade_fee_rate == 0 {
+        Some(post_fee_amount)
+    } else {
+        ceil_div(
+            post_fee_amount.checked_mul(FEE_RATE_DENOMINATOR_VALUE)?,
+            FEE_RATE_DENOMINATOR_VALUE.checked_sub(trade_fee_rate as u128)?,
+        )
+    }
+}
+
+/// Returns the amount out for an exact `amount_in`
+pub fn swap_base_input(
+    amount_in: u64,
+    input_vault_amount: u64,
+    output_vault_amount: u64,
+    trade_fee_rate: u64,
+) -> Option<u64> {
+    let amount_in = amount_in as u128;
+    let amount_in_less_fees = amount_in.checked_sub(trading_fee(amount_in, trade_fee_rate)?)?;
+    let amount_out = (output_vault_amount as u128)
+        .checked_mul(amount_in_less_fees)?
+        .checked_div((input_vault_amount as u128).checked_add(amount_in_less_fees)?)?;
+    u64::try_from(amount_out).ok()
+}
+
+/// Returns the amount in required for an exact `amount_out`
+pub fn swap_base_output(
+    amount_out: u64,
+    input_vault_amount: u64,
+    output_vault_amount: u64,
+    trade_fee_rate: u64,
+) -> Option<u64> {
+    let amount_out = amount_out as u128;
+    let amount_in_less_fees = ceil_div(
+        (input_vault_amount as u128).checked_mul(amount_out)?,
+        (output_vault_amount as u128).checked_sub(amount_out)?,
+    )?;
+    let amount_in = calculate_pre_fee_amount(amount_in_less_fees, trade_fee_rate)?;
+    u64::try_from(amount_in).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn base_input_and_output_round_trip() {
+        let (input_vault, output_vault, fee_rate) = (5_000_000_000, 800_000_000_000, 2_500);
+        let amount_out = swap_base_input(1_000_000, input_vault, output_vault, fee_rate).unwrap();
+        let amount_in = swap_base_output(amount_out, input_vault, output_vault, fee_rate).unwrap();
+        // Rounding always favours the pool so the round trip can never cost less than the input
+        assert!(amount_in <= 1_000_000);
+        assert!(1_000_000 - amount_in <= 2);
+    }
+
+    #[test]
+    pub fn output_exceeding_reserves_fails() {
+        assert!(swap_base_output(1_000, 1_000, 1_000, 2_500).is_none());
+    }
+}
@@ -0,0 +1,887 @@
+use super::pool_state::{AmmConfig, PoolState};
+use crate::amm::pool_selection::PoolRejection;
+use crate::api_v3::response::{ApiV3PoolsPage, ApiV3StandardPool};
+use crate::api_v3::{ApiV3Client, PoolFetchParams, PoolSort, PoolSortOrder, PoolType};
+use crate::builder::{SwapInstructionsBuilder, SwapPlan};
+use crate::executor::{SwapExecutor, SwapQuote};
+use crate::priority_fee::{PriorityFeeEstimator, RpcPriorityFeeEstimator};
+use crate::types::{
+    ComputeUnitLimits, DurableNonce, PriorityFeeConfig, SwapConfig, SwapConfigOverrides, SwapInput,
+};
+use std::sync::Arc;
+
+use crate::error::{Result, SwapError};
+use crate::transfer_fee::{self, MintTransferFee, SwapTransferFees};
+use async_trait::async_trait;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::account::Account;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::program_pack::Pack;
+use solana_sdk::transaction::VersionedTransaction;
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
+pub const RAYDIUM_CPMM_PROGRAM_ID: Pubkey = pubkey!("CPMMoo8L3F4NbTegBCKVNunggL7H1ZpdTHKxQB5qKP1C");
+const AUTH_SEED: &[u8] = b"vault_and_lp_mint_auth_seed";
+const SWAP_BASE_INPUT_DISCRIMINATOR: [u8; 8] = [143, 190, 90, 218, 196, 30, 51, 222];
+const SWAP_BASE_OUTPUT_DISCRIMINATOR: [u8; 8] = [55, 217, 98, 86, 163, 74, 180, 173];
+
+#[derive(Clone)]
+pub struct RaydiumCpmm {
+    client: Arc<RpcClient>,
+    api: ApiV3Client,
+    config: SwapConfig,
+    priority_fee_estimator: Arc<dyn PriorityFeeEstimator>,
+}
+
+#[derive(Default)]
+pub struct RaydiumCpmmExecutorOpts {
+    pub priority_fee: Option<PriorityFeeConfig>,
+    pub cu_limits: Option<ComputeUnitLimits>,
+    pub wrap_and_unwrap_sol: Option<bool>,
+    /// Builds legacy transactions instead of v0 transactions. Defaults to false
+    pub as_legacy_transaction: Option<bool>,
+    /// Builds transactions with a durable nonce instead of a recent blockhash
+    pub durable_nonce: Option<DurableNonce>,
+    /// Estimates the cu price of dynamic priority fees. Defaults to the rpc node's recent
+    /// prioritization fees
+    pub priority_fee_estimator: Option<Arc<dyn PriorityFeeEstimator>>,
+    /// Fails exact-in swaps whose simulation pays less than the output threshold into the output
+    /// account, after transfer fees. Defaults to false
+    pub check_simulated_output: Option<bool>,
+}
+
+impl RaydiumCpmm {
+    pub fn new(client: Arc<RpcClient>, config: RaydiumCpmmExecutorOpts, api: ApiV3Client) -> Self {
+        let RaydiumCpmmExecutorOpts {
+            priority_fee,
+            cu_limits,
+            wrap_and_unwrap_sol,
+            as_legacy_transaction,
+            durable_nonce,
+            priority_fee_estimator,
+            check_simulated_output,
+        } = config;
+        Self {
+            priority_fee_estimator: priority_fee_estimator
+                .unwrap_or_else(|| Arc::new(RpcPriorityFeeEstimator::new(Arc::clone(&client)))),
+            client,
+            api,
+            config: SwapConfig {
+                priority_fee,
+                cu_limits,
+                wrap_and_unwrap_sol,
+                as_legacy_transaction,
+                durable_nonce,
+                check_simulated_output,
+            },
+        }
+    }
+
+    pub async fn quote(&self, swap_input: &SwapInput) -> Result<RaydiumCpmmQuote> {
+        if swap_input.input_token_mint == swap_input.output_token_mint {
+            return Err(SwapError::InvalidInput(format!(
+                "Input token cannot equal output token {}",
+                swap_input.input_token_mint
+            )));
+        }
+
+        let mut pool_id = swap_input.market;
+        if pool_id.is_none() {
+            // Cp-swap pools are listed as standard pools, distinguished only by their program-id
+            let response: ApiV3PoolsPage<ApiV3StandardPool> = self
+                .api
+                .fetch_pool_by_mints(
+                    &swap_input.input_token_mint,
+                    Some(&swap_input.output_token_mint),
+                    &PoolFetchParams {
+                        pool_type: PoolType::Standard,
+                        pool_sort: PoolSort::Liquidity,
+                        sort_type: PoolSortOrder::Descending,
+                        page_size: 10,
+                        page: 1,
+                    },
+                )
+                .await?;
+            pool_id = response.pools.into_iter().find_map(|pool| {
+                let matches_mints = (pool.mint_a.address == swap_input.input_token_mint
+                    && pool.mint_b.address == swap_input.output_token_mint)
+                    || (pool.mint_a.address == swap_input.output_token_mint
+                        && pool.mint_b.address == swap_input.input_token_mint);
+                (matches_mints && pool.program_id == RAYDIUM_CPMM_PROGRAM_ID).then_some(pool.id)
+            });
+        }
+
+        let Some(pool_id) = pool_id else {
+            return Err(SwapError::PoolNotFound(format!(
+                "no cpmm pool for {} -> {}",
+                swap_input.input_token_mint, swap_input.output_token_mint
+            )));
+        };
+
+        let accounts = self.load_accounts(&pool_id).await?;
+        Self::quote_from_accounts(swap_input, pool_id, &accounts)
+    }
+
+    /// Loads the accounts needed to quote against the cpmm pool `pool_id`
+    pub async fn load_accounts(&self, pool_id: &Pubkey) -> Result<RaydiumCpmmAccounts> {
+        let pool_account = self.client.get_account(pool_id).await?;
+        let pool = PoolState::try_deserialize(&pool_account.data)?;
+
+        // reload the pool together with its config and vaults to ensure data consistency
+        let load_pubkeys = vec![
+            *pool_id,
+            pool.amm_config,
+            pool.token_0_vault,
+            pool.token_1_vault,
+            pool.token_0_mint,
+            pool.token_1_mint,
+            solana_sdk::sysvar::clock::ID,
+        ];
+        let rsps = crate::utils::get_multiple_account_data(&self.client, &load_pubkeys).await?;
+        RaydiumCpmmAccounts::try_from(rsps)
+    }
+
+    /// Calculates a quote from already loaded pool accounts without any network requests.
+    pub fn quote_from_accounts(
+        swap_input: &SwapInput,
+        pool_id: Pubkey,
+        accounts: &RaydiumCpmmAccounts,
+    ) -> Result<RaydiumCpmmQuote> {
+        if swap_input.input_token_mint == swap_input.output_token_mint {
+            return Err(SwapError::InvalidInput(format!(
+                "Input token cannot equal output token {}",
+                swap_input.input_token_mint
+            )));
+        }
+
+        let pool = PoolState::try_deserialize(&accounts.pool.data)?;
+        let amm_config = AmmConfig::try_deserialize(&accounts.amm_config.data)?;
+        let clock = crate::utils::decode_clock(&accounts.clock)?;
+        if !pool.swap_enabled() {
+            return Err(SwapError::PoolNotTradable {
+                pool: pool_id,
+                reason: PoolRejection::SwapNotPermitted {
+                    status: pool.status.into(),
+                },
+            });
+        }
+        // the program only swaps once the cluster clock is past the open time
+        let now = clock.unix_timestamp.max(0) as u64;
+        if pool.open_time >= now {
+            return Err(SwapError::PoolNotTradable {
+                pool: pool_id,
+                reason: PoolRejection::NotOpen {
+                    open_time: pool.open_time,
+                    now,
+                },
+            });
+        }
+
+        let zero_for_one = if swap_input.input_token_mint == pool.token_0_mint
+            && swap_input.output_token_mint == pool.token_1_mint
+        {
+            true
+        } else if swap_input.input_token_mint == pool.token_1_mint
+            && swap_input.output_token_mint == pool.token_0_mint
+        {
+            false
+        } else {
+            return Err(SwapError::InvalidInput(format!(
+                "Cpmm pool {} does not trade {} for {}",
+                pool_id, swap_input.input_token_mint, swap_input.output_token_mint
+            )));
+        };
+
+        let (vault_0_amount, vault_1_amount) = pool
+            .vault_amount_without_fee(
+                token_account_amount(&accounts.token_0_vault)?,
+                token_account_amount(&accounts.token_1_vault)?,
+            )
+            .ok_or_else(|| {
+                SwapError::Math("Cpmm vault amounts are less than accrued fees".to_string())
+            })?;
+        let (input_vault_amount, output_vault_amount) = if zero_for_one {
+            (vault_0_amount, vault_1_amount)
+        } else {
+            (vault_1_amount, vault_0_amount)
+        };
+        let (input_mint, output_mint) = if zero_for_one {
+            (&accounts.mint_0, &accounts.mint_1)
+        } else {
+            (&accounts.mint_1, &accounts.mint_0)
+        };
+        let input_transfer_fee = MintTransferFee::from_mint(input_mint)?;
+        let output_transfer_fee = MintTransferFee::from_mint(output_mint)?;
+        let epoch = clock.epoch;
+
+        let amount_specified_is_input = swap_input.mode.amount_specified_is_input();
+        let slippage_bps = swap_input.slippage_bps as u64;
+        // token-2022 transfer fees are withheld from the amount the pool receives and the amount
+        // the user receives, so thresholds are in terms of what actually leaves or arrives
+        let (other_amount, other_amount_threshold, transfer_fees) = if amount_specified_is_input {
+            let input_fee =
+                transfer_fee::fee(input_transfer_fee.as_ref(), epoch, swap_input.amount)?;
+            let amount_out = super::curve::swap_base_input(
+                swap_input.amount - input_fee,
+                input_vault_amount,
+                output_vault_amount,
+                amm_config.trade_fee_rate,
+            )
+            .ok_or_else(|| {
+                SwapError::Math("Failed to calculate cpmm swap amount out".to_string())
+            })?;
+            let output_fee = transfer_fee::fee(output_transfer_fee.as_ref(), epoch, amount_out)?;
+            let amount_out = amount_out - output_fee;
+            (
+                amount_out,
+                crate::utils::amount_with_slippage(amount_out, slippage_bps, false)?,
+                SwapTransferFees {
+                    input: input_fee,
+                    output: output_fee,
+                },
+            )
+        } else {
+            let output_fee =
+                transfer_fee::inverse_fee(output_transfer_fee.as_ref(), epoch, swap_input.amount)?;
+            let amount_out = swap_input
+                .amount
+                .checked_add(output_fee)
+                .ok_or_else(|| SwapError::Math("Cpmm amount out overflows".to_string()))?;
+            if amount_out >= output_vault_amount {
+                return Err(SwapError::InsufficientLiquidity(format!(
+                    "Cpmm pool {} holds {} of the output token, {} requested",
+                    pool_id, output_vault_amount, amount_out
+                )));
+            }
+            let amount_in = super::curve::swap_base_output(
+                amount_out,
+                input_vault_amount,
+                output_vault_amount,
+                amm_config.trade_fee_rate,
+            )
+            .ok_or_else(|| {
+                SwapError::Math("Failed to calculate cpmm swap amount in".to_string())
+            })?;
+            let input_fee =
+                transfer_fee::inverse_fee(input_transfer_fee.as_ref(), epoch, amount_in)?;
+            let amount_in = amount_in
+                .checked_add(input_fee)
+                .ok_or_else(|| SwapError::Math("Cpmm amount in overflows".to_string()))?;
+            (
+                amount_in,
+                crate::utils::amount_with_slippage(amount_in, slippage_bps, true)?,
+                SwapTransferFees {
+                    input: input_fee,
+                    output: output_fee,
+                },
+            )
+        };
+        log::debug!(
+            "raw quote: {}. raw other_amount_threshold: {}",
+            other_amount,
+            other_amount_threshold
+        );
+
+        let (input_mint_decimals, output_mint_decimals) = if zero_for_one {
+            (pool.mint_0_decimals, pool.mint_1_decimals)
+        } else {
+            (pool.mint_1_decimals, pool.mint_0_decimals)
+        };
+
+        Ok(RaydiumCpmmQuote {
+            market: pool_id,
+            input_mint: swap_input.input_token_mint,
+            output_mint: swap_input.output_token_mint,
+            amount: swap_input.amount,
+            other_amount,
+            other_amount_threshold,
+            amount_specified_is_input,
+            input_mint_decimals,
+            output_mint_decimals,
+            transfer_fees,
+            keys: CpmmKeys::new(pool_id, &pool),
+        })
+    }
+
+    pub async fn swap_instructions(
+        &self,
+        input_pubkey: Pubkey,
+        output: RaydiumCpmmQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<Vec<solana_sdk::instruction::Instruction>> {
+        let builder = self.make_swap(input_pubkey, output, overrides).await?;
+        builder.build_instructions()
+    }
+
+    pub async fn swap_transaction(
+        &self,
+        input_pubkey: Pubkey,
+        output: RaydiumCpmmQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<VersionedTransaction> {
+        let fee_payer = overrides.and_then(|o| o.fee_payer).unwrap_or(input_pubkey);
+        let builder = self.make_swap(input_pubkey, output, overrides).await?;
+        builder.build_transaction(Some(&fee_payer), None)
+    }
+
+    pub fn update_config(&mut self, config: &SwapConfig) {
+        self.config = *config;
+    }
+
+    async fn make_swap(
+        &self,
+        input_pubkey: Pubkey,
+        output: RaydiumCpmmQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<SwapInstructionsBuilder> {
+        let config = self.config.resolve(input_pubkey, overrides);
+        let (input_token_program, output_token_program) = output.token_programs()?;
+        let plan = SwapPlan {
+            user: input_pubkey,
+            input_mint: output.input_mint,
+            output_mint: output.output_mint,
+            input_token_program,
+            output_token_program,
+            max_amount_in: if output.amount_specified_is_input {
+                output.amount
+            } else {
+                output.other_amount_threshold
+            },
+            pool_ids: &[output.market],
+            min_amount_out: output
+                .amount_specified_is_input
+                .then_some(output.other_amount_threshold),
+        };
+        SwapInstructionsBuilder::build_swap(
+            &config,
+            plan,
+            &self.api,
+            &self.client,
+            self.priority_fee_estimator.as_ref(),
+            |builder, associated_accounts| {
+                builder.swap_instructions.push(output.swap_instruction(
+                    &input_pubkey,
+                    &associated_accounts.input_ata,
+                    &associated_accounts.output_ata,
+                )?);
+                Ok(())
+            },
+        )
+        .await
+    }
+}
+
+#[async_trait]
+impl SwapExecutor for RaydiumCpmm {
+    fn program_id(&self) -> Pubkey {
+        RAYDIUM_CPMM_PROGRAM_ID
+    }
+
+    async fn quote(&self, swap_input: &SwapInput) -> Result<SwapQuote> {
+        Ok(RaydiumCpmm::quote(self, swap_input).await?.into())
+    }
+
+    async fn swap_instructions(
+        &self,
+        input_pubkey: Pubkey,
+        quote: SwapQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<Vec<Instruction>> {
+        RaydiumCpmm::swap_instructions(self, input_pubkey, quote.try_into()?, overrides).await
+    }
+
+    async fn swap_transaction(
+        &self,
+        input_pubkey: Pubkey,
+        quote: SwapQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<VersionedTransaction> {
+        RaydiumCpmm::swap_transaction(self, input_pubkey, quote.try_into()?, overrides).await
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RaydiumCpmmQuote {
+    /// The address of the cpmm pool
+    pub market: Pubkey,
+    /// The input mint
+    pub input_mint: Pubkey,
+    /// The output mint,
+    pub output_mint: Pubkey,
+    /// The amount specified
+    pub amount: u64,
+    /// The other amount
+    pub other_amount: u64,
+    /// The other amount with slippage
+    pub other_amount_threshold: u64,
+    /// Whether the amount specified is in terms of the input token
+    pub amount_specified_is_input: bool,
+    /// The input mint decimals
+    pub input_mint_decimals: u8,
+    /// The output mint decimals
+    pub output_mint_decimals: u8,
+    /// Token-2022 transfer fees withheld from the swap. `amount` and `other_amount` are what the
+    /// user sends and receives, fees included
+    pub transfer_fees: SwapTransferFees,
+    /// Cpmm pool keys
+    pub keys: CpmmKeys,
+}
+
+impl RaydiumCpmmQuote {
+    /// Builds the swap instruction for this quote between the user's token accounts
+    pub fn swap_instruction(
+        &self,
+        user_owner: &Pubkey,
+        user_source: &Pubkey,
+        user_destination: &Pubkey,
+    ) -> Result<Instruction> {
+        swap_instruction(
+            &RAYDIUM_CPMM_PROGRAM_ID,
+            &self.keys,
+            user_owner,
+            user_source,
+            user_destination,
+            &self.input_mint,
+            self.amount,
+            self.other_amount_threshold,
+            self.amount_specified_is_input,
+        )
+    }
+
+    /// Returns the (input, output) token programs of the swap
+    pub fn token_programs(&self) -> Result<(Pubkey, Pubkey)> {
+        self.keys.token_programs(&self.input_mint)
+    }
+
+    /// An exact-in quote of `amount` for `other_amount` against `market`, with placeholder mints
+    /// and pool keys, for tests that only read a quote's amounts
+    #[cfg(test)]
+    pub(crate) fn test_quote(market: Pubkey, amount: u64, other_amount: u64) -> Self {
+        RaydiumCpmmQuote {
+            market,
+            input_mint: Pubkey::default(),
+            output_mint: Pubkey::default(),
+            amount,
+            other_amount,
+            other_amount_threshold: other_amount,
+            amount_specified_is_input: true,
+            input_mint_decimals: 6,
+            output_mint_decimals: 6,
+            transfer_fees: Default::default(),
+            keys: CpmmKeys {
+                pool: market,
+                amm_config: Pubkey::default(),
+                authority: Pubkey::default(),
+                observation_key: Pubkey::default(),
+                token_0_mint: Pubkey::default(),
+                token_1_mint: Pubkey::default(),
+                token_0_vault: Pubkey::default(),
+                token_1_vault: Pubkey::default(),
+                token_0_program: Pubkey::default(),
+                token_1_program: Pubkey::default(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CpmmKeys {
+    pub pool: Pubkey,
+    pub amm_config: Pubkey,
+    pub authority: Pubkey,
+    pub observation_key: Pubkey,
+    pub token_0_mint: Pubkey,
+    pub token_1_mint: Pubkey,
+    pub token_0_vault: Pubkey,
+    pub token_1_vault: Pubkey,
+    pub token_0_program: Pubkey,
+    pub token_1_program: Pubkey,
+}
+
+impl CpmmKeys {
+    fn new(pool_id: Pubkey, pool: &PoolState) -> Self {
+        CpmmKeys {
+            pool: pool_id,
+            amm_config: pool.amm_config,
+            authority: Pubkey::find_program_address(&[AUTH_SEED], &RAYDIUM_CPMM_PROGRAM_ID).0,
+            observation_key: pool.observation_key,
+            token_0_mint: pool.token_0_mint,
+            token_1_mint: pool.token_1_mint,
+            token_0_vault: pool.token_0_vault,
+            token_1_vault: pool.token_1_vault,
+            token_0_program: pool.token_0_program,
+            token_1_program: pool.token_1_program,
+        }
+    }
+
+    /// Returns the (input, output) token programs for a swap from `input_mint`
+    fn token_programs(&self, input_mint: &Pubkey) -> Result<(Pubkey, Pubkey)> {
+        if *input_mint == self.token_0_mint {
+            Ok((self.token_0_program, self.token_1_program))
+        } else if *input_mint == self.token_1_mint {
+            Ok((self.token_1_program, self.token_0_program))
+        } else {
+            Err(SwapError::InvalidInput(format!(
+                "Mint {} is not traded by cpmm pool {}",
+                input_mint, self.pool
+            )))
+        }
+    }
+}
+
+/// Snapshot of the accounts needed to quote against a cpmm pool
+#[derive(Debug, Clone)]
+pub struct RaydiumCpmmAccounts {
+    /// The cpmm pool account
+    pub pool: Account,
+    /// The pool's amm config
+    pub amm_config: Account,
+    /// The token-0 vault
+    pub token_0_vault: Account,
+    /// The token-1 vault
+    pub token_1_vault: Account,
+    /// The token-0 mint
+    pub mint_0: Account,
+    /// The token-1 mint
+    pub mint_1: Account,
+    /// The Clock sysvar, whose epoch selects the mints' transfer fees and whose timestamp is
+    /// checked against the pool's open time
+    pub clock: Account,
+}
+
+impl TryFrom<Vec<Option<Account>>> for RaydiumCpmmAccounts {
+    type Error = SwapError;
+
+    /// Expects accounts in the order: pool, amm config, token-0 vault, token-1 vault, token-0
+    /// mint, token-1 mint, clock sysvar
+    fn try_from(accounts: Vec<Option<Account>>) -> Result<Self, Self::Error> {
+        let [pool, amm_config, token_0_vault, token_1_vault, mint_0, mint_1, clock]: [Option<
+            Account,
+        >; 7] = accounts.try_into().map_err(|v: Vec<_>| {
+            SwapError::InvalidInput(format!("Expected 7 cpmm accounts, got {}", v.len()))
+        })?;
+        let missing = |name: &str| SwapError::AccountMissing(format!("cpmm {}", name));
+        Ok(RaydiumCpmmAccounts {
+            pool: pool.ok_or_else(|| missing("pool"))?,
+            amm_config: amm_config.ok_or_else(|| missing("amm config"))?,
+            token_0_vault: token_0_vault.ok_or_else(|| missing("token-0 vault"))?,
+            token_1_vault: token_1_vault.ok_or_else(|| missing("token-1 vault"))?,
+            mint_0: mint_0.ok_or_else(|| missing("token-0 mint"))?,
+            mint_1: mint_1.ok_or_else(|| missing("token-1 mint"))?,
+            clock: clock.ok_or_else(|| missing("clock sysvar"))?,
+        })
+    }
+}
+
+/// Reads the amount of a token account owned by either the token or token-2022 program
+fn token_account_amount(account: &Account) -> Result<u64> {
+    if account.owner == spl_token_2022::ID {
+        Ok(spl_token_2022::extension::StateWithExtensions::<
+            spl_token_2022::state::Account,
+        >::unpack(&account.data)
+        .map_err(|e| SwapError::DecodeError(format!("token-2022 account: {}", e)))?
+        .base
+        .amount)
+    } else {
+        Ok(spl_token::state::Account::unpack(&account.data)
+            .map_err(|e| SwapError::DecodeError(format!("token account: {}", e)))?
+            .amount)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn swap_instruction(
+    cpmm_program: &Pubkey,
+    keys: &CpmmKeys,
+    user_owner: &Pubkey,
+    user_source: &Pubkey,
+    user_destination: &Pubkey,
+    input_mint: &Pubkey,
+    amount_specified: u64,
+    other_amount_threshold: u64,
+    swap_base_in: bool,
+) -> Result<Instruction> {
+    let (input_token_program, output_token_program) = keys.token_programs(input_mint)?;
+    let (input_vault, output_vault, output_mint) = if *input_mint == keys.token_0_mint {
+        (keys.token_0_vault, keys.token_1_vault, keys.token_1_mint)
+    } else {
+        (keys.token_1_vault, keys.token_0_vault, keys.token_0_mint)
+    };
+
+    let mut data = Vec::with_capacity(24);
+    if swap_base_in {
+        data.extend_from_slice(&SWAP_BASE_INPUT_DISCRIMINATOR);
+        data.extend_from_slice(&amount_specified.to_le_bytes());
+        data.extend_from_slice(&other_amount_threshold.to_le_bytes());
+    } else {
+        data.extend_from_slice(&SWAP_BASE_OUTPUT_DISCRIMINATOR);
+        data.extend_from_slice(&other_amount_threshold.to_le_bytes());
+        data.extend_from_slice(&amount_specified.to_le_bytes());
+    }
+
+    Ok(Instruction {
+        program_id: *cpmm_program,
+        accounts: vec![
+            AccountMeta::new_readonly(*user_owner, true),
+            AccountMeta::new_readonly(keys.authority, false),
+            AccountMeta::new_readonly(keys.amm_config, false),
+            AccountMeta::new(keys.pool, false),
+            AccountMeta::new(*user_source, false),
+            AccountMeta::new(*user_destination, false),
+            AccountMeta::new(input_vault, false),
+            AccountMeta::new(output_vault, false),
+            AccountMeta::new_readonly(input_token_program, false),
+            AccountMeta::new_readonly(output_token_program, false),
+            AccountMeta::new_readonly(*input_mint, false),
+            AccountMeta::new_readonly(output_mint, false),
+            AccountMeta::new(keys.observation_key, false),
+        ],
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::SwapExecutionMode;
+    use crate::utils::test_accounts;
+
+    const OPEN_TIME: u64 = 1_700_000_000;
+
+    fn token_program(transfer_fee_bps: Option<u16>) -> Pubkey {
+        if transfer_fee_bps.is_some() {
+            spl_token_2022::ID
+        } else {
+            spl_token::ID
+        }
+    }
+
+    /// A pool of 6 decimal token-0 and 9 decimal token-1, with mints charging `transfer_fee_bps`
+    fn pool_state(transfer_fee_bps: [Option<u16>; 2]) -> PoolState {
+        PoolState {
+            amm_config: Pubkey::new_unique(),
+            token_0_vault: Pubkey::new_unique(),
+            token_1_vault: Pubkey::new_unique(),
+            token_0_mint: Pubkey::new_unique(),
+            token_1_mint: Pubkey::new_unique(),
+            token_0_program: token_program(transfer_fee_bps[0]),
+            token_1_program: token_program(transfer_fee_bps[1]),
+            observation_key: Pubkey::new_unique(),
+            mint_0_decimals: 6,
+            mint_1_decimals: 9,
+            open_time: OPEN_TIME,
+            ..Default::default()
+        }
+    }
+
+    /// The accounts of `pool` holding 1_000_000_000 token-0 and 2_000_000_000 token-1 at a 0.25%
+    /// trade fee, a second after the pool opened
+    fn pool_accounts(pool: &PoolState, transfer_fee_bps: [Option<u16>; 2]) -> RaydiumCpmmAccounts {
+        let account = |data: Vec<u8>| Account {
+            data,
+            owner: RAYDIUM_CPMM_PROGRAM_ID,
+            ..Default::default()
+        };
+        let amm_config = AmmConfig {
+            trade_fee_rate: 2_500,
+            ..Default::default()
+        };
+        RaydiumCpmmAccounts {
+            pool: account([&PoolState::DISCRIMINATOR[..], bytemuck::bytes_of(pool)].concat()),
+            amm_config: account(
+                [
+                    &AmmConfig::DISCRIMINATOR[..],
+                    bytemuck::bytes_of(&amm_config),
+                ]
+                .concat(),
+            ),
+            token_0_vault: test_accounts::token_account(
+                pool.token_0_mint,
+                1_000_000_000,
+                pool.token_0_program,
+            ),
+            token_1_vault: test_accounts::token_account(
+                pool.token_1_mint,
+                2_000_000_000,
+                pool.token_1_program,
+            ),
+            mint_0: test_accounts::mint(6, transfer_fee_bps[0]),
+            mint_1: test_accounts::mint(9, transfer_fee_bps[1]),
+            clock: test_accounts::clock(OPEN_TIME as i64 + 1),
+        }
+    }
+
+    fn swap_input(pool: &PoolState, mode: SwapExecutionMode) -> SwapInput {
+        SwapInput {
+            input_token_mint: pool.token_0_mint,
+            output_token_mint: pool.token_1_mint,
+            slippage_bps: 50,
+            amount: 1_000_000,
+            mode,
+            market: None,
+        }
+    }
+
+    fn quote(
+        mode: SwapExecutionMode,
+        transfer_fee_bps: [Option<u16>; 2],
+    ) -> Result<RaydiumCpmmQuote> {
+        let pool = pool_state(transfer_fee_bps);
+        RaydiumCpmm::quote_from_accounts(
+            &swap_input(&pool, mode),
+            Pubkey::new_unique(),
+            &pool_accounts(&pool, transfer_fee_bps),
+        )
+    }
+
+    #[test]
+    pub fn quotes_exact_in_after_transfer_fees() {
+        for (transfer_fee_bps, other_amount, other_amount_threshold, input_fee, output_fee) in [
+            ([None, None], 1_993_011, 1_983_045, 0, 0),
+            ([Some(100), None], 1_973_101, 1_963_235, 10_000, 0),
+            ([None, Some(200)], 1_953_150, 1_943_384, 0, 39_861),
+            ([Some(100), Some(200)], 1_933_638, 1_923_969, 10_000, 39_463),
+        ] {
+            let quote = quote(SwapExecutionMode::ExactIn, transfer_fee_bps).unwrap();
+            assert!(quote.amount_specified_is_input);
+            assert_eq!(quote.amount, 1_000_000);
+            assert_eq!(quote.other_amount, other_amount);
+            assert_eq!(quote.other_amount_threshold, other_amount_threshold);
+            assert_eq!(
+                quote.transfer_fees,
+                SwapTransferFees {
+                    input: input_fee,
+                    output: output_fee,
+                }
+            );
+            assert_eq!(
+                (quote.input_mint_decimals, quote.output_mint_decimals),
+                (6, 9)
+            );
+        }
+    }
+
+    #[test]
+    pub fn quotes_exact_out_after_transfer_fees() {
+        for (transfer_fee_bps, other_amount, other_amount_threshold, input_fee, output_fee) in [
+            ([None, None], 501_505, 504_013, 0, 0),
+            ([Some(100), None], 506_571, 509_104, 5_066, 0),
+            ([None, Some(200)], 511_745, 514_304, 0, 20_409),
+            ([Some(100), Some(200)], 516_915, 519_500, 5_170, 20_409),
+        ] {
+            let quote = quote(SwapExecutionMode::ExactOut, transfer_fee_bps).unwrap();
+            assert!(!quote.amount_specified_is_input);
+            assert_eq!(quote.amount, 1_000_000);
+            assert_eq!(quote.other_amount, other_amount);
+            assert_eq!(quote.other_amount_threshold, other_amount_threshold);
+            assert_eq!(
+                quote.transfer_fees,
+                SwapTransferFees {
+                    input: input_fee,
+                    output: output_fee,
+                }
+            );
+        }
+    }
+
+    #[test]
+    pub fn rejects_disabled_and_unopened_pools() {
+        let pool_id = Pubkey::new_unique();
+        let disabled = PoolState {
+            status: 4,
+            ..pool_state([None, None])
+        };
+        assert!(matches!(
+            RaydiumCpmm::quote_from_accounts(
+                &swap_input(&disabled, SwapExecutionMode::ExactIn),
+                pool_id,
+                &pool_accounts(&disabled, [None, None]),
+            ),
+            Err(SwapError::PoolNotTradable {
+                pool,
+                reason: PoolRejection::SwapNotPermitted { status: 4 },
+            }) if pool == pool_id
+        ));
+
+        let unopened = PoolState {
+            open_time: OPEN_TIME + 1,
+            ..pool_state([None, None])
+        };
+        assert!(matches!(
+            RaydiumCpmm::quote_from_accounts(
+                &swap_input(&unopened, SwapExecutionMode::ExactIn),
+                pool_id,
+                &pool_accounts(&unopened, [None, None]),
+            ),
+            Err(SwapError::PoolNotTradable {
+                reason: PoolRejection::NotOpen {
+                    open_time,
+                    now,
+                },
+                ..
+            }) if open_time == OPEN_TIME + 1 && now == OPEN_TIME + 1
+        ));
+    }
+
+    #[test]
+    pub fn swap_instructions_match_the_program_layout() {
+        let (owner, source, destination) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        for mode in [SwapExecutionMode::ExactIn, SwapExecutionMode::ExactOut] {
+            let quote = quote(mode, [Some(100), None]).unwrap();
+            let keys = quote.keys;
+            let instruction = quote
+                .swap_instruction(&owner, &source, &destination)
+                .unwrap();
+            assert_eq!(instruction.program_id, RAYDIUM_CPMM_PROGRAM_ID);
+            assert_eq!(
+                instruction.accounts,
+                vec![
+                    AccountMeta::new_readonly(owner, true),
+                    AccountMeta::new_readonly(
+                        Pubkey::find_program_address(&[AUTH_SEED], &RAYDIUM_CPMM_PROGRAM_ID).0,
+                        false
+                    ),
+                    AccountMeta::new_readonly(keys.amm_config, false),
+                    AccountMeta::new(quote.market, false),
+                    AccountMeta::new(source, false),
+                    AccountMeta::new(destination, false),
+                    AccountMeta::new(keys.token_0_vault, false),
+                    AccountMeta::new(keys.token_1_vault, false),
+                    AccountMeta::new_readonly(spl_token_2022::ID, false),
+                    AccountMeta::new_readonly(spl_token::ID, false),
+                    AccountMeta::new_readonly(keys.token_0_mint, false),
+                    AccountMeta::new_readonly(keys.token_1_mint, false),
+                    AccountMeta::new(keys.observation_key, false),
+                ]
+            );
+
+            // both instructions take the amount in followed by the amount out
+            let (discriminator, amount_in, amount_out) = if quote.amount_specified_is_input {
+                (
+                    SWAP_BASE_INPUT_DISCRIMINATOR,
+                    quote.amount,
+                    quote.other_amount_threshold,
+                )
+            } else {
+                (
+                    SWAP_BASE_OUTPUT_DISCRIMINATOR,
+                    quote.other_amount_threshold,
+                    quote.amount,
+                )
+            };
+            assert_eq!(
+                instruction.data,
+                [
+                    &discriminator[..],
+                    &amount_in.to_le_bytes(),
+                    &amount_out.to_le_bytes()
+                ]
+                .concat()
+            );
+        }
+    }
+}
@@ -0,0 +1,3 @@
+mod curve;
+pub mod executor;
+mod pool_state;
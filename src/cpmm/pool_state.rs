@@ -0,0 +1,137 @@
+//! Account layouts for the raydium cp-swap program. Both accounts are fixed-size so they can be
+//! read directly from the account data after the 8-byte anchor discriminator
+
+use crate::error::{Result, SwapError};
+use bytemuck::{Pod, Zeroable};
+use solana_program::pubkey::Pubkey;
+
+const DISCRIMINATOR_LEN: usize = 8;
+
+macro_rules! impl_account {
+    ($type_name:ident, $discriminator:expr) => {
+        unsafe impl Zeroable for $type_name {}
+        unsafe impl Pod for $type_name {}
+
+        impl $type_name {
+            pub const DISCRIMINATOR: [u8; 8] = $discriminator;
+            pub const LEN: usize = DISCRIMINATOR_LEN + std::mem::size_of::<$type_name>();
+
+            pub fn try_deserialize(data: &[u8]) -> Result<Self> {
+                if data.len() < Self::LEN {
+                    return Err(SwapError::DecodeError(format!(
+                        "{} account: invalid length. expected {} got {}",
+                        stringify!($type_name),
+                        Self::LEN,
+                        data.len()
+                    )));
+                }
+                if data[..DISCRIMINATOR_LEN] != Self::DISCRIMINATOR {
+                    return Err(SwapError::DecodeError(format!(
+                        "{} account: invalid discriminator",
+                        stringify!($type_name)
+                    )));
+                }
+                Ok(bytemuck::pod_read_unaligned(
+                    &data[DISCRIMINATOR_LEN..Self::LEN],
+                ))
+            }
+        }
+    };
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PoolState {
+    /// Which config the pool belongs
+    pub amm_config: Pubkey,
+    /// pool creator
+    pub pool_creator: Pubkey,
+    /// Token A
+    pub token_0_vault: Pubkey,
+    /// Token B
+    pub token_1_vault: Pubkey,
+    /// Pool tokens are issued when A or B tokens are deposited.
+    pub lp_mint: Pubkey,
+    /// Mint information for token A
+    pub token_0_mint: Pubkey,
+    /// Mint information for token B
+    pub token_1_mint: Pubkey,
+    /// token_0 program
+    pub token_0_program: Pubkey,
+    /// token_1 program
+    pub token_1_program: Pubkey,
+    /// observation account to store oracle data
+    pub observation_key: Pubkey,
+    pub auth_bump: u8,
+    /// Bitwise representation of the state of the pool
+    /// bit0, 1: disable deposit(value is 1), 0: normal
+    /// bit1, 1: disable withdraw(value is 2), 0: normal
+    /// bit2, 1: disable swap(value is 4), 0: normal
+    pub status: u8,
+    pub lp_mint_decimals: u8,
+    /// mint0 and mint1 decimals
+    pub mint_0_decimals: u8,
+    pub mint_1_decimals: u8,
+    /// True circulating supply without burns and lock ups
+    pub lp_supply: u64,
+    /// The amounts of token_0 and token_1 that are owed to the liquidity provider.
+    pub protocol_fees_token_0: u64,
+    pub protocol_fees_token_1: u64,
+    pub fund_fees_token_0: u64,
+    pub fund_fees_token_1: u64,
+    /// The timestamp allowed for swap in the pool.
+    pub open_time: u64,
+    /// recent epoch
+    pub recent_epoch: u64,
+    /// padding for future updates
+    pub padding: [u64; 31],
+}
+impl_account!(PoolState, [247, 237, 227, 245, 215, 195, 222, 70]);
+
+impl PoolState {
+    const SWAP_DISABLED_BIT: u8 = 1 << 2;
+
+    pub fn swap_enabled(&self) -> bool {
+        self.status & Self::SWAP_DISABLED_BIT == 0
+    }
+
+    /// Vault amounts available to traders, excluding accrued protocol and fund fees
+    pub fn vault_amount_without_fee(&self, vault_0: u64, vault_1: u64) -> Option<(u64, u64)> {
+        Some((
+            vault_0.checked_sub(
+                self.protocol_fees_token_0
+                    .checked_add(self.fund_fees_token_0)?,
+            )?,
+            vault_1.checked_sub(
+                self.protocol_fees_token_1
+                    .checked_add(self.fund_fees_token_1)?,
+            )?,
+        ))
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AmmConfig {
+    /// Bump to identify PDA
+    pub bump: u8,
+    /// Status to control if new pool can be create
+    pub disable_create_pool: u8,
+    /// Config index
+    pub index: u16,
+    /// The trade fee, denominated in hundredths of a bip (10^-6)
+    pub trade_fee_rate: u64,
+    /// The protocol fee
+    pub protocol_fee_rate: u64,
+    /// The fund fee, denominated in hundredths of a bip (10^-6)
+    pub fund_fee_rate: u64,
+    /// Fee for create a new pool
+    pub create_pool_fee: u64,
+    /// Address of the protocol fee owner
+    pub protocol_owner: Pubkey,
+    /// Address of the fund fee owner
+    pub fund_owner: Pubkey,
+    /// padding
+    pub padding: [u64; 16],
+}
+impl_account!(AmmConfig, [218, 244, 33, 104, 203, 203, 43, 111]);
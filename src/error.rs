@@ -0,0 +1,137 @@
+//! The error type returned across the crate's public api.
+//!
+//! Variants classify failures by cause so callers can decide what to retry: rpc and http errors
+//! are usually transient, while missing pools or invalid input are not.
+
+use crate::amm::pool_selection::{NoTradablePool, PoolRejection};
+use crate::api_v3::ApiV3ErrorResponse;
+
+use raydium_amm::error::AmmError;
+use solana_client::client_error::ClientError;
+use solana_sdk::program_error::ProgramError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::TransactionError;
+
+pub type Result<T, E = SwapError> = std::result::Result<T, E>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SwapError {
+    /// No pool matches the swap, or the requested pool does not exist
+    #[error("Pool not found: {0}")]
+    PoolNotFound(String),
+    /// Pools were found for the swap but none of them can be traded against
+    #[error(transparent)]
+    NoTradablePool(#[from] NoTradablePool),
+    /// The requested pool exists but can't be traded against
+    #[error("Pool {pool} can't be traded: {reason}")]
+    PoolNotTradable { pool: Pubkey, reason: PoolRejection },
+    /// An on-chain account the swap depends on does not exist
+    #[error("Account not found: {0}")]
+    AccountMissing(String),
+    /// A pool's accounts decoded but describe a pool that can't be swapped against, e.g. an
+    /// account owned by the wrong program or a zero fee denominator
+    #[error("Invalid pool state: {0}")]
+    InvalidPoolState(String),
+    /// Account data or an api response could not be decoded
+    #[error("Failed to decode {0}")]
+    DecodeError(String),
+    /// The raydium api responded with `success: false`
+    #[error(transparent)]
+    ApiError(ApiV3ErrorResponse),
+    /// The http request to the raydium api or a block engine failed
+    #[error("Http request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    /// The block engine rejected a bundle request
+    #[error("Block engine error: {0}")]
+    BlockEngine(String),
+    /// A json-rpc api other than the rpc node, such as a priority fee api, returned an error
+    #[error("Json-rpc error {code}: {message}")]
+    JsonRpc { code: i64, message: String },
+    /// The rpc request failed
+    #[error("Rpc request failed: {0}")]
+    Rpc(Box<ClientError>),
+    /// A pool program's math returned an error
+    #[error("Program error: {0}")]
+    Program(#[from] ProgramError),
+    /// Swap math overflowed or produced an invalid value
+    #[error("Math error: {0}")]
+    Math(String),
+    /// The pool can't fill the swap
+    #[error("Insufficient liquidity: {0}")]
+    InsufficientLiquidity(String),
+    /// The swap would receive less, or spend more, than the slippage threshold allows
+    #[error("Slippage exceeded: expected {expected}, threshold {threshold}")]
+    SlippageExceeded { expected: u64, threshold: u64 },
+    /// The swap request is invalid, e.g. the input and output mints are equal
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    /// The swap request is valid but not supported by this executor
+    #[error("Unsupported: {0}")]
+    Unsupported(String),
+    /// Simulating the swap transaction failed, it would fail on-chain as well
+    #[error("Simulation failed: {0}")]
+    Simulation(Box<SimulationFailure>),
+    /// The swap instructions or transaction could not be built
+    #[error("Failed to build transaction: {0}")]
+    Transaction(String),
+}
+
+impl SwapError {
+    /// Whether retrying the same request may succeed, i.e. the failure was in transport rather
+    /// than in the request or in the pool state
+    pub fn is_transient(&self) -> bool {
+        matches!(self, SwapError::Http(_) | SwapError::Rpc(_))
+    }
+
+    /// Classifies an error from raydium-library, which reports everything as `anyhow::Error`.
+    /// Rpc errors keep their variant, anything else is wrapped with `other`.
+    pub(crate) fn from_library(e: anyhow::Error, other: impl FnOnce(String) -> SwapError) -> Self {
+        match e.downcast::<ClientError>() {
+            Ok(e) => e.into(),
+            Err(e) => other(format!("{:#}", e)),
+        }
+    }
+}
+
+/// A failed transaction simulation
+#[derive(Debug)]
+pub struct SimulationFailure {
+    pub err: TransactionError,
+    /// The decoded error of a failed amm v4 instruction
+    pub amm_error: Option<AmmError>,
+    /// The program logs up to the failure
+    pub logs: Vec<String>,
+}
+
+impl std::fmt::Display for SimulationFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.amm_error {
+            Some(amm_error) => write!(f, "{} ({})", self.err, amm_error),
+            None => write!(f, "{}", self.err),
+        }
+    }
+}
+
+impl From<SimulationFailure> for SwapError {
+    fn from(e: SimulationFailure) -> Self {
+        SwapError::Simulation(Box::new(e))
+    }
+}
+
+impl From<ClientError> for SwapError {
+    fn from(e: ClientError) -> Self {
+        SwapError::Rpc(Box::new(e))
+    }
+}
+
+impl From<ApiV3ErrorResponse> for SwapError {
+    fn from(e: ApiV3ErrorResponse) -> Self {
+        SwapError::ApiError(e)
+    }
+}
+
+impl From<serde_json::Error> for SwapError {
+    fn from(e: serde_json::Error) -> Self {
+        SwapError::DecodeError(format!("api response: {}", e))
+    }
+}
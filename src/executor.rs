@@ -0,0 +1,445 @@
+//! Pool-kind agnostic swap execution.
+//!
+//! Every raydium pool kind has its own executor with its own quote type. [`SwapExecutor`] wraps
+//! them behind a common interface so callers can hold a `Vec<Box<dyn SwapExecutor>>`, and
+//! [`SwapExecutorDispatcher`] routes a swap to the right executor from the pool's program id.
+
+use crate::amm::executor::{RaydiumAmmQuote, RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID};
+use crate::api_v3::response::{ApiV3AnyPoolsPage, ApiV3Pool};
+use crate::api_v3::{ApiV3Client, PoolFetchParams, PoolSort, PoolSortOrder, PoolType};
+use crate::clmm::executor::{RaydiumClmmQuote, RAYDIUM_CLMM_PROGRAM_ID};
+use crate::cpmm::executor::{RaydiumCpmmQuote, RAYDIUM_CPMM_PROGRAM_ID};
+use crate::transfer_fee::SwapTransferFees;
+use crate::types::{SwapConfigOverrides, SwapInput};
+
+use crate::error::{Result, SwapError};
+use async_trait::async_trait;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::VersionedTransaction;
+
+#[async_trait]
+pub trait SwapExecutor: Send + Sync {
+    /// The program id of the pools this executor swaps against
+    fn program_id(&self) -> Pubkey;
+
+    async fn quote(&self, swap_input: &SwapInput) -> Result<SwapQuote>;
+
+    async fn swap_instructions(
+        &self,
+        input_pubkey: Pubkey,
+        quote: SwapQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<Vec<Instruction>>;
+
+    async fn swap_transaction(
+        &self,
+        input_pubkey: Pubkey,
+        quote: SwapQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<VersionedTransaction>;
+}
+
+/// A quote from any of the pool kinds
+#[derive(Debug, Clone)]
+pub enum SwapQuote {
+    Amm(RaydiumAmmQuote),
+    Cpmm(RaydiumCpmmQuote),
+    Clmm(RaydiumClmmQuote),
+}
+
+macro_rules! quote_field {
+    ($self:ident, $field:ident) => {
+        match $self {
+            SwapQuote::Amm(quote) => quote.$field,
+            SwapQuote::Cpmm(quote) => quote.$field,
+            SwapQuote::Clmm(quote) => quote.$field,
+        }
+    };
+}
+
+impl SwapQuote {
+    /// The program id of the quoted pool
+    pub fn program_id(&self) -> Pubkey {
+        match self {
+            SwapQuote::Amm(_) => RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
+            SwapQuote::Cpmm(_) => RAYDIUM_CPMM_PROGRAM_ID,
+            SwapQuote::Clmm(_) => RAYDIUM_CLMM_PROGRAM_ID,
+        }
+    }
+
+    /// The address of the quoted pool
+    pub fn market(&self) -> Pubkey {
+        quote_field!(self, market)
+    }
+
+    pub fn input_mint(&self) -> Pubkey {
+        quote_field!(self, input_mint)
+    }
+
+    pub fn output_mint(&self) -> Pubkey {
+        quote_field!(self, output_mint)
+    }
+
+    /// The amount specified
+    pub fn amount(&self) -> u64 {
+        quote_field!(self, amount)
+    }
+
+    /// The other amount
+    pub fn other_amount(&self) -> u64 {
+        quote_field!(self, other_amount)
+    }
+
+    /// The other amount with slippage
+    pub fn other_amount_threshold(&self) -> u64 {
+        quote_field!(self, other_amount_threshold)
+    }
+
+    /// Whether the amount specified is in terms of the input token
+    pub fn amount_specified_is_input(&self) -> bool {
+        quote_field!(self, amount_specified_is_input)
+    }
+
+    pub fn input_mint_decimals(&self) -> u8 {
+        quote_field!(self, input_mint_decimals)
+    }
+
+    pub fn output_mint_decimals(&self) -> u8 {
+        quote_field!(self, output_mint_decimals)
+    }
+
+    /// Token-2022 transfer fees withheld from the swap
+    pub fn transfer_fees(&self) -> SwapTransferFees {
+        quote_field!(self, transfer_fees)
+    }
+
+    /// The amount the user sends, including the input transfer fee
+    pub fn gross_amount_in(&self) -> u64 {
+        if self.amount_specified_is_input() {
+            self.amount()
+        } else {
+            self.other_amount()
+        }
+    }
+
+    /// The amount the pool receives after the input transfer fee
+    pub fn net_amount_in(&self) -> u64 {
+        self.gross_amount_in() - self.transfer_fees().input
+    }
+
+    /// The amount the pool sends, including the output transfer fee
+    pub fn gross_amount_out(&self) -> u64 {
+        self.net_amount_out() + self.transfer_fees().output
+    }
+
+    /// The amount the user receives after the output transfer fee
+    pub fn net_amount_out(&self) -> u64 {
+        if self.amount_specified_is_input() {
+            self.other_amount()
+        } else {
+            self.amount()
+        }
+    }
+
+    /// Returns the (input, output) token programs of the swap
+    pub fn token_programs(&self) -> Result<(Pubkey, Pubkey)> {
+        match self {
+            SwapQuote::Amm(quote) => quote.token_programs(),
+            SwapQuote::Cpmm(quote) => quote.token_programs(),
+            SwapQuote::Clmm(quote) => quote.token_programs(),
+        }
+    }
+
+    /// Builds the swap instruction for this quote between the user's token accounts
+    pub fn swap_instruction(
+        &self,
+        user_owner: &Pubkey,
+        user_source: &Pubkey,
+        user_destination: &Pubkey,
+    ) -> Result<Instruction> {
+        match self {
+            SwapQuote::Amm(quote) => {
+                quote.swap_instruction(user_owner, user_source, user_destination)
+            }
+            SwapQuote::Cpmm(quote) => {
+                quote.swap_instruction(user_owner, user_source, user_destination)
+            }
+            SwapQuote::Clmm(quote) => {
+                quote.swap_instruction(user_owner, user_source, user_destination)
+            }
+        }
+    }
+}
+
+macro_rules! impl_quote_conversions {
+    ($quote:ident, $variant:ident) => {
+        impl From<$quote> for SwapQuote {
+            fn from(quote: $quote) -> Self {
+                SwapQuote::$variant(quote)
+            }
+        }
+
+        impl TryFrom<SwapQuote> for $quote {
+            type Error = SwapError;
+
+            fn try_from(quote: SwapQuote) -> Result<Self, Self::Error> {
+                match quote {
+                    SwapQuote::$variant(quote) => Ok(quote),
+                    other => Err(SwapError::InvalidInput(format!(
+                        "Expected a {} quote, got a quote for program {}",
+                        stringify!($variant),
+                        other.program_id()
+                    ))),
+                }
+            }
+        }
+    };
+}
+
+impl_quote_conversions!(RaydiumAmmQuote, Amm);
+impl_quote_conversions!(RaydiumCpmmQuote, Cpmm);
+impl_quote_conversions!(RaydiumClmmQuote, Clmm);
+
+/// Quotes for the same swap across pools, ordered from best to worst execution price
+#[derive(Debug, Clone)]
+pub struct RankedQuotes {
+    pub best: SwapQuote,
+    pub alternatives: Vec<SwapQuote>,
+}
+
+/// Routes swaps to the executor registered for the pool's program id
+pub struct SwapExecutorDispatcher {
+    api: ApiV3Client,
+    executors: Vec<Box<dyn SwapExecutor>>,
+}
+
+impl SwapExecutorDispatcher {
+    pub fn new(api: ApiV3Client, executors: Vec<Box<dyn SwapExecutor>>) -> Self {
+        Self { api, executors }
+    }
+
+    pub fn api(&self) -> &ApiV3Client {
+        &self.api
+    }
+
+    pub fn executors(&self) -> &[Box<dyn SwapExecutor>] {
+        &self.executors
+    }
+
+    /// Returns the executor for pools owned by `program_id`
+    pub fn executor(&self, program_id: &Pubkey) -> Option<&dyn SwapExecutor> {
+        self.executors
+            .iter()
+            .find(|executor| executor.program_id() == *program_id)
+            .map(|executor| executor.as_ref())
+    }
+
+    /// Quotes against `swap_input.market` if set, otherwise against the most liquid pool for the
+    /// mint pair that has a registered executor
+    pub async fn quote(&self, swap_input: &SwapInput) -> Result<SwapQuote> {
+        if swap_input.input_token_mint == swap_input.output_token_mint {
+            return Err(SwapError::InvalidInput(format!(
+                "Input token cannot equal output token {}",
+                swap_input.input_token_mint
+            )));
+        }
+
+        let pool: ApiV3Pool = match swap_input.market {
+            Some(pool_id) => self
+                .api
+                .fetch_pools_by_ids::<ApiV3Pool>(vec![pool_id.to_string()])
+                .await?
+                .into_iter()
+                .next()
+                .ok_or_else(|| SwapError::PoolNotFound(pool_id.to_string()))?,
+            None => self
+                .supported_pools(swap_input, 10)
+                .await?
+                .into_iter()
+                .next()
+                .ok_or_else(|| {
+                    SwapError::PoolNotFound(format!(
+                        "no supported pool for {} -> {}",
+                        swap_input.input_token_mint, swap_input.output_token_mint
+                    ))
+                })?,
+        };
+
+        let executor = self.executor(&pool.program_id).ok_or_else(|| {
+            SwapError::Unsupported(format!(
+                "No executor registered for program {} of pool {}",
+                pool.program_id, pool.id
+            ))
+        })?;
+        executor
+            .quote(&SwapInput {
+                market: Some(pool.id),
+                ..*swap_input
+            })
+            .await
+    }
+
+    /// Quotes every pool for the mint pair that has a registered executor in parallel and ranks
+    /// them by execution price: highest output for exact-in swaps, lowest input for exact-out.
+    /// `swap_input.market` is ignored.
+    pub async fn quote_best(&self, swap_input: &SwapInput) -> Result<RankedQuotes> {
+        if swap_input.input_token_mint == swap_input.output_token_mint {
+            return Err(SwapError::InvalidInput(format!(
+                "Input token cannot equal output token {}",
+                swap_input.input_token_mint
+            )));
+        }
+
+        let pools = self.supported_pools(swap_input, 100).await?;
+        let quotes = futures_util::future::join_all(pools.iter().map(|pool| async move {
+            let executor = self.executor(&pool.program_id).ok_or_else(|| {
+                SwapError::Unsupported(format!("No executor registered for pool {}", pool.id))
+            })?;
+            executor
+                .quote(&SwapInput {
+                    market: Some(pool.id),
+                    ..*swap_input
+                })
+                .await
+        }))
+        .await;
+
+        let mut ranked = Vec::with_capacity(quotes.len());
+        for (pool, quote) in pools.iter().zip(quotes) {
+            match quote {
+                Ok(quote) => ranked.push(quote),
+                Err(e) => log::debug!("Failed to quote pool {}: {:#}", pool.id, e),
+            }
+        }
+        rank_quotes(&mut ranked, swap_input.mode.amount_specified_is_input());
+
+        let mut ranked = ranked.into_iter();
+        let best = ranked.next().ok_or_else(|| {
+            SwapError::PoolNotFound(format!(
+                "none of the {} pools for the mint pair could quote the swap",
+                pools.len()
+            ))
+        })?;
+        Ok(RankedQuotes {
+            best,
+            alternatives: ranked.collect(),
+        })
+    }
+
+    pub async fn swap_instructions(
+        &self,
+        input_pubkey: Pubkey,
+        quote: SwapQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<Vec<Instruction>> {
+        self.quote_executor(&quote)?
+            .swap_instructions(input_pubkey, quote, overrides)
+            .await
+    }
+
+    pub async fn swap_transaction(
+        &self,
+        input_pubkey: Pubkey,
+        quote: SwapQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<VersionedTransaction> {
+        self.quote_executor(&quote)?
+            .swap_transaction(input_pubkey, quote, overrides)
+            .await
+    }
+
+    /// Lists pools trading the mint pair that have a registered executor, most liquid first
+    async fn supported_pools(
+        &self,
+        swap_input: &SwapInput,
+        page_size: u16,
+    ) -> Result<Vec<ApiV3Pool>> {
+        let response: ApiV3AnyPoolsPage = self
+            .api
+            .fetch_pool_by_mints(
+                &swap_input.input_token_mint,
+                Some(&swap_input.output_token_mint),
+                &PoolFetchParams {
+                    pool_type: PoolType::All,
+                    pool_sort: PoolSort::Liquidity,
+                    sort_type: PoolSortOrder::Descending,
+                    page_size,
+                    page: 1,
+                },
+            )
+            .await?;
+        Ok(response
+            .pools
+            .into_iter()
+            .filter(|pool| {
+                let matches_mints = (pool.mint_a.address == swap_input.input_token_mint
+                    && pool.mint_b.address == swap_input.output_token_mint)
+                    || (pool.mint_a.address == swap_input.output_token_mint
+                        && pool.mint_b.address == swap_input.input_token_mint);
+                matches_mints && self.executor(&pool.program_id).is_some()
+            })
+            .collect())
+    }
+
+    fn quote_executor(&self, quote: &SwapQuote) -> Result<&dyn SwapExecutor> {
+        self.executor(&quote.program_id()).ok_or_else(|| {
+            SwapError::Unsupported(format!(
+                "No executor registered for program {}",
+                quote.program_id()
+            ))
+        })
+    }
+}
+
+/// Orders quotes for the same swap from best to worst: highest output first for exact-in swaps,
+/// lowest input first for exact-out. Ties keep their order.
+fn rank_quotes(quotes: &mut [SwapQuote], amount_specified_is_input: bool) {
+    if amount_specified_is_input {
+        quotes.sort_by_key(|quote| std::cmp::Reverse(quote.other_amount()));
+    } else {
+        quotes.sort_by_key(|quote| quote.other_amount());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quote(market: Pubkey, other_amount: u64, amount_specified_is_input: bool) -> SwapQuote {
+        SwapQuote::Cpmm(RaydiumCpmmQuote {
+            amount_specified_is_input,
+            ..RaydiumCpmmQuote::test_quote(market, 1_000, other_amount)
+        })
+    }
+
+    fn ranked_markets(
+        other_amounts: &[u64],
+        amount_specified_is_input: bool,
+    ) -> (Vec<Pubkey>, Vec<Pubkey>) {
+        let markets = other_amounts
+            .iter()
+            .map(|_| Pubkey::new_unique())
+            .collect::<Vec<_>>();
+        let mut quotes = markets
+            .iter()
+            .zip(other_amounts)
+            .map(|(market, other_amount)| quote(*market, *other_amount, amount_specified_is_input))
+            .collect::<Vec<_>>();
+        rank_quotes(&mut quotes, amount_specified_is_input);
+        (markets, quotes.iter().map(SwapQuote::market).collect())
+    }
+
+    #[test]
+    pub fn rank_quotes_prefers_highest_output_for_exact_in() {
+        let (markets, ranked) = ranked_markets(&[900, 1_100, 1_000, 1_100], true);
+        // ties keep the order the pools were quoted in
+        assert_eq!(ranked, vec![markets[1], markets[3], markets[2], markets[0]]);
+    }
+
+    #[test]
+    pub fn rank_quotes_prefers_lowest_input_for_exact_out() {
+        let (markets, ranked) = ranked_markets(&[1_100, 900, 1_000, 900], false);
+        assert_eq!(ranked, vec![markets[1], markets[3], markets[2], markets[0]]);
+    }
+}
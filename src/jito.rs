@@ -0,0 +1,284 @@
+//! Jito block engine bundles.
+//!
+//! A bundle is a list of up to five transactions that land in order, in the same slot, or not at
+//! all. Swaps are sent as the swap transaction followed by an optional transaction that only pays
+//! the tip, so the tip is never paid for a swap that didn't land.
+
+use crate::builder::build_jito_tip_ix;
+use crate::error::{Result, SwapError};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+use solana_program::message::{Message, VersionedMessage};
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::VersionedTransaction;
+
+/// Protocol defined: The most transactions a bundle may hold
+pub const MAX_BUNDLE_TRANSACTIONS: usize = 5;
+
+#[derive(Clone, Debug)]
+pub struct JitoClient {
+    http: reqwest::Client,
+    block_engine_url: String,
+}
+
+impl Default for JitoClient {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// The state of a bundle submitted in the last five minutes
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub enum BundleStatus {
+    /// The block engine doesn't know the bundle, it was never received or is too old
+    Invalid,
+    /// The bundle hasn't been processed yet
+    Pending,
+    /// No leader included the bundle before it expired
+    Failed,
+    /// The bundle landed in `landed_slot`
+    Landed,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub struct InflightBundleStatus {
+    pub status: BundleStatus,
+    pub landed_slot: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcResponse<T> {
+    result: Option<T>,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct InflightBundleStatuses {
+    value: Vec<InflightBundleStatus>,
+}
+
+impl JitoClient {
+    pub const DEFAULT_BLOCK_ENGINE_URL: &'static str = "https://mainnet.block-engine.jito.wtf";
+
+    pub fn new(block_engine_url: Option<String>) -> Self {
+        JitoClient {
+            http: reqwest::Client::new(),
+            block_engine_url: block_engine_url
+                .unwrap_or(Self::DEFAULT_BLOCK_ENGINE_URL.to_string()),
+        }
+    }
+
+    /// Submits signed `transactions` as a bundle and returns the bundle id
+    pub async fn send_bundle(&self, transactions: &[VersionedTransaction]) -> Result<String> {
+        self.request("bundles", "sendBundle", send_bundle_params(transactions)?)
+            .await
+    }
+
+    /// The status of a bundle submitted in the last five minutes
+    pub async fn get_inflight_bundle_status(&self, bundle_id: &str) -> Result<BundleStatus> {
+        let statuses: InflightBundleStatuses = self
+            .request(
+                "getInflightBundleStatuses",
+                "getInflightBundleStatuses",
+                json!([[bundle_id]]),
+            )
+            .await?;
+        Ok(statuses
+            .value
+            .first()
+            .map_or(BundleStatus::Invalid, |status| status.status))
+    }
+
+    async fn request<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<T> {
+        let url = format!("{}/api/v1/{}", self.block_engine_url, path);
+        let response = self
+            .http
+            .post(url)
+            .json(&json_rpc_request(method, params))
+            .send()
+            .await?;
+        let status_error = response.error_for_status_ref().err();
+        let body = response.bytes().await?;
+        parse_response(method, &body, status_error)
+    }
+}
+
+/// The params of a `sendBundle` request for signed `transactions`
+fn send_bundle_params(transactions: &[VersionedTransaction]) -> Result<serde_json::Value> {
+    if transactions.is_empty() || transactions.len() > MAX_BUNDLE_TRANSACTIONS {
+        return Err(SwapError::InvalidInput(format!(
+            "A bundle holds 1 to {} transactions, got {}",
+            MAX_BUNDLE_TRANSACTIONS,
+            transactions.len()
+        )));
+    }
+    let transactions = transactions
+        .iter()
+        .map(|transaction| {
+            bincode::serialize(transaction)
+                .map(|bytes| STANDARD.encode(bytes))
+                .map_err(|e| SwapError::Transaction(format!("Failed to serialize: {}", e)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(json!([transactions, { "encoding": "base64" }]))
+}
+
+fn json_rpc_request(method: &str, params: serde_json::Value) -> serde_json::Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": params,
+    })
+}
+
+/// Reads the result of a `method` response. The block engine returns JSON-RPC errors with http
+/// error statuses, so `status_error` is only returned for bodies that aren't JSON-RPC.
+fn parse_response<T: DeserializeOwned>(
+    method: &str,
+    body: &[u8],
+    status_error: Option<reqwest::Error>,
+) -> Result<T> {
+    match (
+        serde_json::from_slice::<JsonRpcResponse<T>>(body),
+        status_error,
+    ) {
+        (
+            Ok(JsonRpcResponse {
+                result: Some(result),
+                ..
+            }),
+            _,
+        ) => Ok(result),
+        (
+            Ok(JsonRpcResponse {
+                error: Some(error), ..
+            }),
+            _,
+        ) => Err(SwapError::BlockEngine(format!(
+            "{} ({})",
+            error.message, error.code
+        ))),
+        (_, Some(status_error)) => Err(status_error.into()),
+        (Ok(_), None) => Err(SwapError::DecodeError(format!(
+            "{} response without a result",
+            method
+        ))),
+        (Err(e), None) => Err(SwapError::DecodeError(format!(
+            "{} response: {}",
+            method, e
+        ))),
+    }
+}
+
+/// The message of a transaction that transfers `tip` lamports from `payer` to a random tip account
+pub fn tip_transaction_message(payer: &Pubkey, tip: u64, blockhash: Hash) -> VersionedMessage {
+    VersionedMessage::Legacy(Message::new_with_blockhash(
+        &[build_jito_tip_ix(payer, tip)],
+        Some(payer),
+        &blockhash,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::JITO_TIP_ACCOUNTS;
+
+    #[test]
+    pub fn tip_transaction_pays_a_tip_account() {
+        let payer = Pubkey::new_unique();
+        let message = tip_transaction_message(&payer, 10_000, Hash::new_unique());
+        let keys = message.static_account_keys();
+        assert_eq!(keys[0], payer);
+        assert_eq!(message.header().num_required_signatures, 1);
+        assert!(JITO_TIP_ACCOUNTS.contains(&keys[1]));
+    }
+
+    #[test]
+    pub fn send_bundle_request_holds_base64_transactions() {
+        let payer = Pubkey::new_unique();
+        let transaction = VersionedTransaction {
+            signatures: vec![Default::default()],
+            message: tip_transaction_message(&payer, 10_000, Hash::new_unique()),
+        };
+        let request = json_rpc_request(
+            "sendBundle",
+            send_bundle_params(std::slice::from_ref(&transaction)).unwrap(),
+        );
+        assert_eq!(
+            request,
+            json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "sendBundle",
+                "params": [
+                    [STANDARD.encode(bincode::serialize(&transaction).unwrap())],
+                    { "encoding": "base64" },
+                ],
+            })
+        );
+        assert!(matches!(
+            send_bundle_params(&[]),
+            Err(SwapError::InvalidInput(_))
+        ));
+        assert!(matches!(
+            send_bundle_params(&vec![transaction; MAX_BUNDLE_TRANSACTIONS + 1]),
+            Err(SwapError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    pub fn parses_json_rpc_errors_before_the_http_status() {
+        let bundle_id: String = parse_response(
+            "sendBundle",
+            br#"{"jsonrpc":"2.0","result":"b","id":1}"#,
+            None,
+        )
+        .unwrap();
+        assert_eq!(bundle_id, "b");
+        assert!(matches!(
+            parse_response::<String>(
+                "sendBundle",
+                br#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"bundle contains an expired blockhash"},"id":1}"#,
+                None,
+            ),
+            Err(SwapError::BlockEngine(message)) if message.contains("expired blockhash")
+        ));
+        assert!(matches!(
+            parse_response::<String>("sendBundle", b"rate limited", None),
+            Err(SwapError::DecodeError(_))
+        ));
+    }
+
+    #[test]
+    pub fn status_deserializes_from_block_engine_names() {
+        let statuses: InflightBundleStatuses = serde_json::from_str(
+            r#"{"context":{"slot":1},"value":[{"bundle_id":"b","status":"Landed","landed_slot":7}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            statuses.value,
+            vec![InflightBundleStatus {
+                status: BundleStatus::Landed,
+                landed_slot: Some(7),
+            }]
+        );
+    }
+}
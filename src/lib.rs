@@ -1,5 +1,17 @@
 pub mod amm;
 pub mod api_v3;
 pub mod builder;
+pub mod clmm;
+pub mod cpmm;
+pub mod error;
+pub mod executor;
+pub mod jito;
+pub mod logs;
+pub mod priority_fee;
+pub mod receipt;
+pub mod router;
+pub mod sender;
+pub mod split;
+pub mod transfer_fee;
 pub mod types;
 pub mod utils;
@@ -0,0 +1,425 @@
+//! Raydium amm v4 `ray_log` program logs.
+//!
+//! The amm logs a base64 encoded, bincode serialized record for each instruction, as
+//! `Program log: ray_log: <base64>`. The first byte is the record's kind. Records carry the exact
+//! amounts moved and the pool's reserves before the instruction, so a swap's amounts and the
+//! reserves it left behind are known without reading any accounts.
+//!
+//! Logs are read from confirmed transactions, from simulations and from `logsSubscribe`
+//! notifications.
+
+use crate::error::{Result, SwapError};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use raydium_amm::log::{self, LogType};
+use serde::Deserialize;
+use solana_client::rpc_response::{RpcLogsResponse, RpcSimulateTransactionResult};
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::EncodedConfirmedTransactionWithStatusMeta;
+
+const RAY_LOG_PREFIX: &str = "ray_log: ";
+
+const INIT: u8 = LogType::Init as u8;
+const DEPOSIT: u8 = LogType::Deposit as u8;
+const WITHDRAW: u8 = LogType::Withdraw as u8;
+const SWAP_BASE_IN: u8 = LogType::SwapBaseIn as u8;
+const SWAP_BASE_OUT: u8 = LogType::SwapBaseOut as u8;
+
+/// The `direction` of a swap from pc to coin
+pub const DIRECTION_PC_TO_COIN: u64 = 1;
+/// The `direction` of a swap from coin to pc
+pub const DIRECTION_COIN_TO_PC: u64 = 2;
+
+/// A pool's initialization
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct InitLog {
+    pub time: u64,
+    pub pc_decimals: u8,
+    pub coin_decimals: u8,
+    pub pc_lot_size: u64,
+    pub coin_lot_size: u64,
+    /// The initial pc reserve
+    pub pc_amount: u64,
+    /// The initial coin reserve
+    pub coin_amount: u64,
+    pub market: Pubkey,
+}
+
+/// A liquidity deposit
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DepositLog {
+    pub max_coin: u64,
+    pub max_pc: u64,
+    /// 0 if `max_coin` fixes the deposit ratio, 1 if `max_pc` does
+    pub base: u64,
+    /// The pool's coin reserve before the deposit
+    pub pool_coin: u64,
+    /// The pool's pc reserve before the deposit
+    pub pool_pc: u64,
+    /// The lp supply before the deposit
+    pub pool_lp: u64,
+    pub calc_pnl_x: u128,
+    pub calc_pnl_y: u128,
+    /// The coin amount taken from the user
+    pub deduct_coin: u64,
+    /// The pc amount taken from the user
+    pub deduct_pc: u64,
+    /// The lp amount minted to the user
+    pub mint_lp: u64,
+}
+
+/// A liquidity withdrawal
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WithdrawLog {
+    /// The lp amount burned
+    pub withdraw_lp: u64,
+    /// The user's lp balance before the withdrawal
+    pub user_lp: u64,
+    /// The pool's coin reserve before the withdrawal
+    pub pool_coin: u64,
+    /// The pool's pc reserve before the withdrawal
+    pub pool_pc: u64,
+    /// The lp supply before the withdrawal
+    pub pool_lp: u64,
+    pub calc_pnl_x: u128,
+    pub calc_pnl_y: u128,
+    /// The coin amount sent to the user
+    pub out_coin: u64,
+    /// The pc amount sent to the user
+    pub out_pc: u64,
+}
+
+/// A swap with an exact input amount
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SwapBaseInLog {
+    pub amount_in: u64,
+    pub minimum_out: u64,
+    /// [`DIRECTION_PC_TO_COIN`] or [`DIRECTION_COIN_TO_PC`]
+    pub direction: u64,
+    /// The user's source token account balance before the swap
+    pub user_source: u64,
+    /// The pool's coin reserve before the swap
+    pub pool_coin: u64,
+    /// The pool's pc reserve before the swap
+    pub pool_pc: u64,
+    pub out_amount: u64,
+}
+
+/// A swap with an exact output amount
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SwapBaseOutLog {
+    pub max_in: u64,
+    pub amount_out: u64,
+    /// [`DIRECTION_PC_TO_COIN`] or [`DIRECTION_COIN_TO_PC`]
+    pub direction: u64,
+    /// The user's source token account balance before the swap
+    pub user_source: u64,
+    /// The pool's coin reserve before the swap
+    pub pool_coin: u64,
+    /// The pool's pc reserve before the swap
+    pub pool_pc: u64,
+    /// The amount taken from the user
+    pub deduct_in: u64,
+}
+
+impl From<log::InitLog> for InitLog {
+    fn from(log: log::InitLog) -> Self {
+        Self {
+            time: log.time,
+            pc_decimals: log.pc_decimals,
+            coin_decimals: log.coin_decimals,
+            pc_lot_size: log.pc_lot_size,
+            coin_lot_size: log.coin_lot_size,
+            pc_amount: log.pc_amount,
+            coin_amount: log.coin_amount,
+            market: log.market,
+        }
+    }
+}
+
+impl From<log::DepositLog> for DepositLog {
+    fn from(log: log::DepositLog) -> Self {
+        Self {
+            max_coin: log.max_coin,
+            max_pc: log.max_pc,
+            base: log.base,
+            pool_coin: log.pool_coin,
+            pool_pc: log.pool_pc,
+            pool_lp: log.pool_lp,
+            calc_pnl_x: log.calc_pnl_x,
+            calc_pnl_y: log.calc_pnl_y,
+            deduct_coin: log.deduct_coin,
+            deduct_pc: log.deduct_pc,
+            mint_lp: log.mint_lp,
+        }
+    }
+}
+
+impl From<log::WithdrawLog> for WithdrawLog {
+    fn from(log: log::WithdrawLog) -> Self {
+        Self {
+            withdraw_lp: log.withdraw_lp,
+            user_lp: log.user_lp,
+            pool_coin: log.pool_coin,
+            pool_pc: log.pool_pc,
+            pool_lp: log.pool_lp,
+            calc_pnl_x: log.calc_pnl_x,
+            calc_pnl_y: log.calc_pnl_y,
+            out_coin: log.out_coin,
+            out_pc: log.out_pc,
+        }
+    }
+}
+
+impl From<log::SwapBaseInLog> for SwapBaseInLog {
+    fn from(log: log::SwapBaseInLog) -> Self {
+        Self {
+            amount_in: log.amount_in,
+            minimum_out: log.minimum_out,
+            direction: log.direction,
+            user_source: log.user_source,
+            pool_coin: log.pool_coin,
+            pool_pc: log.pool_pc,
+            out_amount: log.out_amount,
+        }
+    }
+}
+
+impl From<log::SwapBaseOutLog> for SwapBaseOutLog {
+    fn from(log: log::SwapBaseOutLog) -> Self {
+        Self {
+            max_in: log.max_in,
+            amount_out: log.amount_out,
+            direction: log.direction,
+            user_source: log.user_source,
+            pool_coin: log.pool_coin,
+            pool_pc: log.pool_pc,
+            deduct_in: log.deduct_in,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RayLog {
+    Init(InitLog),
+    Deposit(DepositLog),
+    Withdraw(WithdrawLog),
+    SwapBaseIn(SwapBaseInLog),
+    SwapBaseOut(SwapBaseOutLog),
+}
+
+impl RayLog {
+    /// Parses a program log line. Returns `None` for lines that are not ray logs, or are ray
+    /// logs of a kind that isn't decoded.
+    pub fn parse(line: &str) -> Option<Result<Self>> {
+        let (_, data) = line.split_once(RAY_LOG_PREFIX)?;
+        let data = match STANDARD.decode(data.trim()) {
+            Ok(data) => data,
+            Err(e) => return Some(Err(SwapError::DecodeError(format!("ray_log: {}", e)))),
+        };
+        Self::decode(&data).transpose()
+    }
+
+    /// Decodes the bytes of a ray log. Returns `None` for kinds that aren't decoded.
+    pub fn decode(data: &[u8]) -> Result<Option<Self>> {
+        let kind = data
+            .first()
+            .ok_or_else(|| SwapError::DecodeError("ray_log: empty".to_string()))?;
+        let log = match *kind {
+            INIT => RayLog::Init(deserialize::<log::InitLog>(data)?.into()),
+            DEPOSIT => RayLog::Deposit(deserialize::<log::DepositLog>(data)?.into()),
+            WITHDRAW => RayLog::Withdraw(deserialize::<log::WithdrawLog>(data)?.into()),
+            SWAP_BASE_IN => RayLog::SwapBaseIn(deserialize::<log::SwapBaseInLog>(data)?.into()),
+            SWAP_BASE_OUT => RayLog::SwapBaseOut(deserialize::<log::SwapBaseOutLog>(data)?.into()),
+            _ => return Ok(None),
+        };
+        Ok(Some(log))
+    }
+
+    /// The amounts the user sent and received, if this is a swap
+    pub fn swap_amounts(&self) -> Option<(u64, u64)> {
+        match self {
+            RayLog::SwapBaseIn(log) => Some((log.amount_in, log.out_amount)),
+            RayLog::SwapBaseOut(log) => Some((log.deduct_in, log.amount_out)),
+            _ => None,
+        }
+    }
+
+    /// The pool's coin and pc reserves after the instruction. Returns `None` if the logged
+    /// amounts don't add up.
+    pub fn reserves_after(&self) -> Option<(u64, u64)> {
+        match self {
+            RayLog::Init(log) => Some((log.coin_amount, log.pc_amount)),
+            RayLog::Deposit(log) => Some((
+                log.pool_coin.checked_add(log.deduct_coin)?,
+                log.pool_pc.checked_add(log.deduct_pc)?,
+            )),
+            RayLog::Withdraw(log) => Some((
+                log.pool_coin.checked_sub(log.out_coin)?,
+                log.pool_pc.checked_sub(log.out_pc)?,
+            )),
+            RayLog::SwapBaseIn(log) => swap_reserves_after(
+                log.direction,
+                log.pool_coin,
+                log.pool_pc,
+                log.amount_in,
+                log.out_amount,
+            ),
+            RayLog::SwapBaseOut(log) => swap_reserves_after(
+                log.direction,
+                log.pool_coin,
+                log.pool_pc,
+                log.deduct_in,
+                log.amount_out,
+            ),
+        }
+    }
+}
+
+fn swap_reserves_after(
+    direction: u64,
+    pool_coin: u64,
+    pool_pc: u64,
+    amount_in: u64,
+    amount_out: u64,
+) -> Option<(u64, u64)> {
+    match direction {
+        DIRECTION_COIN_TO_PC => Some((
+            pool_coin.checked_add(amount_in)?,
+            pool_pc.checked_sub(amount_out)?,
+        )),
+        DIRECTION_PC_TO_COIN => Some((
+            pool_coin.checked_sub(amount_out)?,
+            pool_pc.checked_add(amount_in)?,
+        )),
+        _ => None,
+    }
+}
+
+/// Parses every ray log in `logs`, in order, skipping other lines
+pub fn parse_ray_logs<S: AsRef<str>>(logs: impl IntoIterator<Item = S>) -> Result<Vec<RayLog>> {
+    logs.into_iter()
+        .filter_map(|line| RayLog::parse(line.as_ref()))
+        .collect()
+}
+
+/// Parses the ray logs of a simulated transaction, such as the compute unit simulation kept in
+/// [`SwapInstructionsBuilder::simulation`](crate::builder::SwapInstructionsBuilder::simulation)
+pub fn parse_simulation_ray_logs(result: &RpcSimulateTransactionResult) -> Result<Vec<RayLog>> {
+    parse_ray_logs(result.logs.iter().flatten())
+}
+
+/// Parses the ray logs of a confirmed transaction
+pub fn parse_transaction_ray_logs(
+    transaction: &EncodedConfirmedTransactionWithStatusMeta,
+) -> Result<Vec<RayLog>> {
+    match transaction
+        .transaction
+        .meta
+        .as_ref()
+        .map(|meta| &meta.log_messages)
+    {
+        Some(OptionSerializer::Some(logs)) => parse_ray_logs(logs),
+        _ => Ok(vec![]),
+    }
+}
+
+/// Parses the ray logs of a `logsSubscribe` notification. Failed transactions still log, but
+/// nothing they logged took effect, so they yield no records.
+pub fn parse_logs_response_ray_logs(response: &RpcLogsResponse) -> Result<Vec<RayLog>> {
+    if response.err.is_some() {
+        return Ok(vec![]);
+    }
+    parse_ray_logs(&response.logs)
+}
+
+fn deserialize<'a, T: Deserialize<'a>>(record: &'a [u8]) -> Result<T> {
+    bincode::deserialize(record).map_err(|e| SwapError::DecodeError(format!("ray_log: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(kind: u8, fields: &[u64]) -> String {
+        let mut data = vec![kind];
+        for field in fields {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        format!("Program log: ray_log: {}", STANDARD.encode(data))
+    }
+
+    #[test]
+    pub fn parse_swap_logs() {
+        let logs = [
+            "Program 675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8 invoke [1]".to_string(),
+            encode(
+                SWAP_BASE_IN,
+                &[1_000, 900, 1, 5_000, 100_000, 200_000, 1_990],
+            ),
+            encode(
+                SWAP_BASE_OUT,
+                &[2_100, 2_000, 2, 5_000, 100_000, 200_000, 2_050],
+            ),
+        ];
+        let parsed = parse_ray_logs(&logs).unwrap();
+        assert_eq!(
+            parsed,
+            vec![
+                RayLog::SwapBaseIn(SwapBaseInLog {
+                    amount_in: 1_000,
+                    minimum_out: 900,
+                    direction: 1,
+                    user_source: 5_000,
+                    pool_coin: 100_000,
+                    pool_pc: 200_000,
+                    out_amount: 1_990,
+                }),
+                RayLog::SwapBaseOut(SwapBaseOutLog {
+                    max_in: 2_100,
+                    amount_out: 2_000,
+                    direction: 2,
+                    user_source: 5_000,
+                    pool_coin: 100_000,
+                    pool_pc: 200_000,
+                    deduct_in: 2_050,
+                }),
+            ]
+        );
+        assert_eq!(parsed[1].swap_amounts(), Some((2_050, 2_000)));
+        assert_eq!(parsed[0].reserves_after(), Some((98_010, 201_000)));
+        assert_eq!(parsed[1].reserves_after(), Some((102_050, 198_000)));
+    }
+
+    #[test]
+    pub fn parse_liquidity_logs() {
+        let mut deposit = vec![DEPOSIT];
+        for field in [1_000u64, 2_100, 0, 100_000, 200_000, 50_000] {
+            deposit.extend_from_slice(&field.to_le_bytes());
+        }
+        deposit.extend_from_slice(&[0; 32]);
+        for field in [1_000u64, 2_000, 500] {
+            deposit.extend_from_slice(&field.to_le_bytes());
+        }
+        let log = RayLog::decode(&deposit).unwrap().unwrap();
+        assert!(matches!(
+            log,
+            RayLog::Deposit(DepositLog { mint_lp: 500, .. })
+        ));
+        assert_eq!(log.swap_amounts(), None);
+        assert_eq!(log.reserves_after(), Some((101_000, 202_000)));
+
+        assert_eq!(RayLog::decode(&[9, 1, 2, 3]).unwrap(), None);
+    }
+
+    #[test]
+    pub fn parse_rejects_truncated_logs() {
+        let line = encode(SWAP_BASE_IN, &[1_000, 900]);
+        assert!(matches!(
+            RayLog::parse(&line),
+            Some(Err(SwapError::DecodeError(_)))
+        ));
+    }
+}
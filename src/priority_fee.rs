@@ -0,0 +1,175 @@
+//! Cu price estimates for dynamic priority fees.
+//!
+//! [`PriorityFeeConfig::Dynamic`](crate::types::PriorityFeeConfig::Dynamic) asks a
+//! [`PriorityFeeEstimator`] for the cu price of a transaction writing the swap's accounts. The
+//! rpc node's `getRecentPrioritizationFees` is used by default; fee apis with a
+//! `getPriorityFeeEstimate` style method, or any other source implementing the trait, can be set
+//! on the executors instead.
+
+use crate::error::{Result, SwapError};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use std::sync::Arc;
+
+/// Protocol defined: The most accounts `getRecentPrioritizationFees` accepts
+const MAX_PRIORITIZATION_FEE_ACCOUNTS: usize = 128;
+
+#[async_trait]
+pub trait PriorityFeeEstimator: Send + Sync {
+    /// The cu price, in micro-lamports, that the `percentile` of recent transactions writing
+    /// `writable_accounts` paid or less
+    async fn estimate_cu_price(&self, writable_accounts: &[Pubkey], percentile: u8) -> Result<u64>;
+}
+
+/// Estimates from the rpc node's `getRecentPrioritizationFees`, the lowest fee that landed a
+/// transaction writing the accounts in each of the last 150 slots
+#[derive(Clone)]
+pub struct RpcPriorityFeeEstimator {
+    client: Arc<RpcClient>,
+}
+
+impl RpcPriorityFeeEstimator {
+    pub fn new(client: Arc<RpcClient>) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl PriorityFeeEstimator for RpcPriorityFeeEstimator {
+    async fn estimate_cu_price(&self, writable_accounts: &[Pubkey], percentile: u8) -> Result<u64> {
+        let accounts =
+            &writable_accounts[..writable_accounts.len().min(MAX_PRIORITIZATION_FEE_ACCOUNTS)];
+        let fees = self
+            .client
+            .get_recent_prioritization_fees(accounts)
+            .await?
+            .into_iter()
+            .map(|fee| fee.prioritization_fee)
+            .collect();
+        Ok(fee_percentile(fees, percentile))
+    }
+}
+
+/// Estimates from a json-rpc method taking `[{ accountKeys, options: { priorityLevel } }]` and
+/// returning `{ priorityFeeEstimate }`, such as helius' `getPriorityFeeEstimate`. The percentile
+/// is rounded up to the nearest priority level.
+#[derive(Clone, Debug)]
+pub struct JsonRpcPriorityFeeEstimator {
+    http: reqwest::Client,
+    url: String,
+    method: String,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcResponse {
+    result: Option<PriorityFeeEstimate>,
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Deserialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PriorityFeeEstimate {
+    priority_fee_estimate: f64,
+}
+
+impl JsonRpcPriorityFeeEstimator {
+    pub const DEFAULT_METHOD: &'static str = "getPriorityFeeEstimate";
+
+    pub fn new(url: String, method: Option<String>) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            url,
+            method: method.unwrap_or(Self::DEFAULT_METHOD.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl PriorityFeeEstimator for JsonRpcPriorityFeeEstimator {
+    async fn estimate_cu_price(&self, writable_accounts: &[Pubkey], percentile: u8) -> Result<u64> {
+        let account_keys = writable_accounts
+            .iter()
+            .map(Pubkey::to_string)
+            .collect::<Vec<_>>();
+        let response: JsonRpcResponse = self
+            .http
+            .post(&self.url)
+            .json(&json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": self.method,
+                "params": [{
+                    "accountKeys": account_keys,
+                    "options": { "priorityLevel": priority_level(percentile) },
+                }],
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        match response {
+            JsonRpcResponse {
+                result: Some(estimate),
+                ..
+            } => Ok(estimate.priority_fee_estimate.ceil() as u64),
+            JsonRpcResponse {
+                error: Some(error), ..
+            } => Err(SwapError::JsonRpc {
+                code: error.code,
+                message: error.message,
+            }),
+            _ => Err(SwapError::DecodeError(format!(
+                "{} response without an estimate",
+                self.method
+            ))),
+        }
+    }
+}
+
+/// The lowest priority level at or above `percentile`
+fn priority_level(percentile: u8) -> &'static str {
+    match percentile {
+        0 => "Min",
+        1..=25 => "Low",
+        26..=50 => "Medium",
+        51..=75 => "High",
+        76..=95 => "VeryHigh",
+        _ => "UnsafeMax",
+    }
+}
+
+/// The nearest-rank `percentile` of `fees`, zero if there are none
+fn fee_percentile(mut fees: Vec<u64>, percentile: u8) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    fees.sort_unstable();
+    let rank = (percentile as usize * fees.len()).div_ceil(100);
+    fees[rank.saturating_sub(1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn fee_percentile_uses_nearest_rank() {
+        let fees = vec![50, 10, 40, 20, 30];
+        assert_eq!(fee_percentile(fees.clone(), 0), 10);
+        assert_eq!(fee_percentile(fees.clone(), 50), 30);
+        assert_eq!(fee_percentile(fees.clone(), 75), 40);
+        assert_eq!(fee_percentile(fees, 100), 50);
+        assert_eq!(fee_percentile(vec![], 75), 0);
+    }
+}
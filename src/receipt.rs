@@ -0,0 +1,541 @@
+//! What a landed swap actually did.
+//!
+//! A [`SwapReceipt`] is read from a confirmed transaction: the amounts the owner spent and
+//! received, from the owner's token balances before and after the transaction, and the fees the
+//! transaction paid. Amm v4 swaps also carry their `ray_log` records.
+
+use crate::builder::JITO_TIP_ACCOUNTS;
+use crate::error::{Result, SwapError};
+use crate::executor::SwapQuote;
+use crate::logs::{parse_transaction_ray_logs, RayLog};
+
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::system_instruction::SystemInstruction;
+use solana_sdk::transaction::TransactionError;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, UiTransactionEncoding, UiTransactionTokenBalance,
+};
+use std::str::FromStr;
+
+/// Protocol defined: The fee charged per signature
+const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// The outcome of a landed swap
+#[derive(Debug, Clone)]
+pub struct SwapReceipt {
+    pub signature: Signature,
+    pub slot: u64,
+    /// Set if the transaction failed, in which case no tokens moved but fees were still paid
+    pub err: Option<TransactionError>,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+    /// The amount of the input mint the owner spent
+    pub amount_in: u64,
+    /// The amount of the output mint the owner received
+    pub amount_out: u64,
+    /// The signature fee
+    pub base_fee: u64,
+    /// The compute unit price fee
+    pub priority_fee: u64,
+    /// Lamports transferred to jito tip accounts
+    pub jito_tip: u64,
+    /// The amm v4 `ray_log` records of the transaction in order, empty if it failed
+    pub ray_logs: Vec<RayLog>,
+}
+
+impl SwapReceipt {
+    /// Fetches a confirmed transaction and reads the swap `quote` was made for, as executed by
+    /// `owner`
+    pub async fn fetch(
+        rpc_client: &RpcClient,
+        signature: &Signature,
+        owner: &Pubkey,
+        quote: &SwapQuote,
+    ) -> Result<Self> {
+        let transaction = rpc_client
+            .get_transaction_with_config(
+                signature,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .await?;
+        Self::from_transaction(&transaction, owner, quote)
+    }
+
+    /// Reads the swap `quote` was made for, as executed by `owner`, from a confirmed transaction
+    /// fetched with base64 or base58 encoding
+    pub fn from_transaction(
+        transaction: &EncodedConfirmedTransactionWithStatusMeta,
+        owner: &Pubkey,
+        quote: &SwapQuote,
+    ) -> Result<Self> {
+        Self::from_transaction_with_mints(
+            transaction,
+            owner,
+            quote.input_mint(),
+            quote.output_mint(),
+        )
+    }
+
+    fn from_transaction_with_mints(
+        transaction: &EncodedConfirmedTransactionWithStatusMeta,
+        owner: &Pubkey,
+        input_mint: Pubkey,
+        output_mint: Pubkey,
+    ) -> Result<Self> {
+        let meta =
+            transaction.transaction.meta.as_ref().ok_or_else(|| {
+                SwapError::DecodeError("transaction without status meta".to_string())
+            })?;
+        let versioned = transaction
+            .transaction
+            .transaction
+            .decode()
+            .ok_or_else(|| {
+                SwapError::DecodeError("transaction is not base64 or base58 encoded".to_string())
+            })?;
+
+        let mut account_keys = versioned.message.static_account_keys().to_vec();
+        if let OptionSerializer::Some(loaded) = &meta.loaded_addresses {
+            for address in loaded.writable.iter().chain(&loaded.readonly) {
+                account_keys.push(parse_pubkey(address)?);
+            }
+        }
+
+        let base_fee = LAMPORTS_PER_SIGNATURE * versioned.signatures.len() as u64;
+        let jito_tip = versioned
+            .message
+            .instructions()
+            .iter()
+            .filter(|ix| {
+                account_keys.get(ix.program_id_index as usize)
+                    == Some(&solana_sdk::system_program::ID)
+            })
+            .filter_map(|ix| match bincode::deserialize(&ix.data) {
+                Ok(SystemInstruction::Transfer { lamports }) => {
+                    let recipient = account_keys.get(*ix.accounts.get(1)? as usize)?;
+                    JITO_TIP_ACCOUNTS.contains(recipient).then_some(lamports)
+                }
+                _ => None,
+            })
+            .sum();
+        // a failed transaction's logs describe instructions that were rolled back
+        let ray_logs = match meta.err {
+            Some(_) => vec![],
+            None => parse_transaction_ray_logs(transaction)?,
+        };
+
+        let empty = vec![];
+        let pre_balances = option_or(&meta.pre_token_balances, &empty);
+        let post_balances = option_or(&meta.post_token_balances, &empty);
+        let input_delta = token_delta(pre_balances, post_balances, owner, &input_mint)?;
+        let output_delta = token_delta(pre_balances, post_balances, owner, &output_mint)?;
+
+        // wrapped sol accounts created and closed within the transaction have no token balances,
+        // and a closed account that held wrapped sol before only has its balance before. Their
+        // amounts come from the amm's swap logs or the owner's lamport balance instead
+        let closed_wrapped_sol = closed_token_accounts(
+            pre_balances,
+            post_balances,
+            owner,
+            &spl_token::native_mint::ID,
+        );
+        // a closed account's lamports, including the wrapped sol it held before, go to the owner
+        let closed_lamports: i128 = closed_wrapped_sol
+            .iter()
+            .filter_map(|index| meta.pre_balances.get(usize::from(*index)))
+            .map(|lamports| *lamports as i128)
+            .sum();
+        let owner_lamports_delta = account_keys
+            .iter()
+            .position(|key| key == owner)
+            .and_then(|index| {
+                let pre = *meta.pre_balances.get(index)? as i128;
+                let post = *meta.post_balances.get(index)? as i128;
+                Some(post - pre - closed_lamports)
+            })
+            .unwrap_or_default();
+        let reads_wrapped_sol = |mint: &Pubkey, delta: i128| {
+            *mint == spl_token::native_mint::ID && (delta == 0 || !closed_wrapped_sol.is_empty())
+        };
+        let owner_fees = if account_keys.first() == Some(owner) {
+            meta.fee as i128 + jito_tip as i128
+        } else {
+            0
+        };
+        // a routed swap logs once per amm leg, the first spends the input mint and the last pays
+        // out the output mint
+        let swaps = ray_logs
+            .iter()
+            .filter_map(RayLog::swap_amounts)
+            .collect::<Vec<_>>();
+        let amount_in = if reads_wrapped_sol(&input_mint, input_delta) {
+            match swaps.first() {
+                Some((amount_in, _)) => *amount_in as i128,
+                None => -(owner_lamports_delta + owner_fees),
+            }
+        } else {
+            -input_delta
+        };
+        let amount_out = if reads_wrapped_sol(&output_mint, output_delta) {
+            match swaps.last() {
+                Some((_, amount_out)) => *amount_out as i128,
+                None => owner_lamports_delta + owner_fees,
+            }
+        } else {
+            output_delta
+        };
+
+        Ok(SwapReceipt {
+            signature: versioned.signatures[0],
+            slot: transaction.slot,
+            err: meta.err.clone(),
+            input_mint,
+            output_mint,
+            amount_in: amount_in.max(0) as u64,
+            amount_out: amount_out.max(0) as u64,
+            base_fee,
+            priority_fee: meta.fee.saturating_sub(base_fee),
+            jito_tip,
+            ray_logs,
+        })
+    }
+
+    /// The total lamports paid to land the transaction
+    pub fn total_fees(&self) -> u64 {
+        self.base_fee + self.priority_fee + self.jito_tip
+    }
+
+    /// How much worse the swap executed than `quote`, in basis points of the quoted amount.
+    /// Compares the output for exact-in swaps and the input for exact-out swaps. Negative if the
+    /// swap did better than quoted.
+    pub fn realized_slippage_bps(&self, quote: &SwapQuote) -> f64 {
+        let quoted = quote.other_amount();
+        if quoted == 0 {
+            return 0.0;
+        }
+        let shortfall = if quote.amount_specified_is_input() {
+            quoted as f64 - self.amount_out as f64
+        } else {
+            self.amount_in as f64 - quoted as f64
+        };
+        shortfall * 10_000.0 / quoted as f64
+    }
+}
+
+fn option_or<'a, T>(value: &'a OptionSerializer<T>, default: &'a T) -> &'a T {
+    match value {
+        OptionSerializer::Some(value) => value,
+        _ => default,
+    }
+}
+
+fn parse_pubkey(address: &str) -> Result<Pubkey> {
+    Pubkey::from_str(address)
+        .map_err(|e| SwapError::DecodeError(format!("address {}: {}", address, e)))
+}
+
+/// The change in `owner`'s balance of `mint` across all of its token accounts
+fn token_delta(
+    pre_balances: &[UiTransactionTokenBalance],
+    post_balances: &[UiTransactionTokenBalance],
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> Result<i128> {
+    let (owner, mint) = (owner.to_string(), mint.to_string());
+    let total = |balances: &[UiTransactionTokenBalance]| -> Result<i128> {
+        balances
+            .iter()
+            .filter(|balance| {
+                balance.mint == mint && balance.owner == OptionSerializer::Some(owner.clone())
+            })
+            .map(|balance| {
+                balance
+                    .ui_token_amount
+                    .amount
+                    .parse::<u64>()
+                    .map(i128::from)
+                    .map_err(|e| SwapError::DecodeError(format!("token balance: {}", e)))
+            })
+            .sum()
+    };
+    Ok(total(post_balances)? - total(pre_balances)?)
+}
+
+/// The account indexes of `owner`'s token accounts of `mint` that have a balance before the
+/// transaction but none after it, because the transaction closed them
+fn closed_token_accounts(
+    pre_balances: &[UiTransactionTokenBalance],
+    post_balances: &[UiTransactionTokenBalance],
+    owner: &Pubkey,
+    mint: &Pubkey,
+) -> Vec<u8> {
+    let (owner, mint) = (OptionSerializer::Some(owner.to_string()), mint.to_string());
+    pre_balances
+        .iter()
+        .filter(|balance| balance.mint == mint && balance.owner == owner)
+        .map(|balance| balance.account_index)
+        .filter(|index| {
+            !post_balances
+                .iter()
+                .any(|balance| balance.account_index == *index)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::build_jito_tip_ix;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use solana_account_decoder::parse_token::UiTokenAmount;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::instruction::Instruction;
+    use solana_sdk::message::Message;
+    use solana_sdk::transaction::Transaction;
+    use solana_transaction_status::{
+        EncodedTransaction, EncodedTransactionWithStatusMeta, TransactionBinaryEncoding,
+        UiTransactionStatusMeta,
+    };
+
+    const SWAP_BASE_IN: u8 = 3;
+
+    fn ray_log(fields: [u64; 7]) -> String {
+        let mut data = vec![SWAP_BASE_IN];
+        for field in fields {
+            data.extend_from_slice(&field.to_le_bytes());
+        }
+        format!("Program log: ray_log: {}", STANDARD.encode(data))
+    }
+
+    fn token_balance(
+        account_index: u8,
+        mint: &Pubkey,
+        owner: &Pubkey,
+        amount: u64,
+    ) -> UiTransactionTokenBalance {
+        UiTransactionTokenBalance {
+            account_index,
+            mint: mint.to_string(),
+            ui_token_amount: UiTokenAmount {
+                ui_amount: None,
+                decimals: 6,
+                amount: amount.to_string(),
+                ui_amount_string: String::new(),
+            },
+            owner: OptionSerializer::Some(owner.to_string()),
+            program_id: OptionSerializer::Some(spl_token::ID.to_string()),
+        }
+    }
+
+    /// A confirmed transaction paid for by `payer`, whose lamports go from `lamports.0` to
+    /// `lamports.1`
+    fn confirmed_transaction(
+        payer: &Pubkey,
+        instructions: &[Instruction],
+        fee: u64,
+        lamports: (u64, u64),
+        token_balances: (
+            Vec<UiTransactionTokenBalance>,
+            Vec<UiTransactionTokenBalance>,
+        ),
+        log_messages: Vec<String>,
+    ) -> EncodedConfirmedTransactionWithStatusMeta {
+        let message = Message::new_with_blockhash(instructions, Some(payer), &Hash::new_unique());
+        let mut pre_balances = vec![0; message.account_keys.len()];
+        let mut post_balances = pre_balances.clone();
+        (pre_balances[0], post_balances[0]) = lamports;
+        let transaction = Transaction::new_unsigned(message);
+        EncodedConfirmedTransactionWithStatusMeta {
+            slot: 42,
+            transaction: EncodedTransactionWithStatusMeta {
+                transaction: EncodedTransaction::Binary(
+                    STANDARD.encode(bincode::serialize(&transaction).unwrap()),
+                    TransactionBinaryEncoding::Base64,
+                ),
+                meta: Some(UiTransactionStatusMeta {
+                    err: None,
+                    status: Ok(()),
+                    fee,
+                    pre_balances,
+                    post_balances,
+                    inner_instructions: OptionSerializer::None,
+                    log_messages: OptionSerializer::Some(log_messages),
+                    pre_token_balances: OptionSerializer::Some(token_balances.0),
+                    post_token_balances: OptionSerializer::Some(token_balances.1),
+                    rewards: OptionSerializer::None,
+                    loaded_addresses: OptionSerializer::Skip,
+                    return_data: OptionSerializer::Skip,
+                    compute_units_consumed: OptionSerializer::Skip,
+                }),
+                version: None,
+            },
+            block_time: None,
+        }
+    }
+
+    #[test]
+    pub fn reads_amounts_from_token_balances() {
+        let owner = Pubkey::new_unique();
+        let (input_mint, output_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let transaction = confirmed_transaction(
+            &owner,
+            &[build_jito_tip_ix(&owner, 1_000)],
+            10_000,
+            (1_000_000, 989_000),
+            (
+                vec![
+                    token_balance(1, &input_mint, &owner, 5_000),
+                    token_balance(2, &output_mint, &owner, 0),
+                ],
+                vec![
+                    token_balance(1, &input_mint, &owner, 4_000),
+                    token_balance(2, &output_mint, &owner, 1_990),
+                ],
+            ),
+            vec![],
+        );
+        let receipt =
+            SwapReceipt::from_transaction_with_mints(&transaction, &owner, input_mint, output_mint)
+                .unwrap();
+        assert_eq!(receipt.slot, 42);
+        assert_eq!((receipt.amount_in, receipt.amount_out), (1_000, 1_990));
+        assert_eq!(
+            (receipt.base_fee, receipt.priority_fee, receipt.jito_tip),
+            (5_000, 5_000, 1_000)
+        );
+        assert_eq!(receipt.total_fees(), 11_000);
+    }
+
+    #[test]
+    pub fn reads_wrapped_sol_from_the_first_and_last_swap_logs() {
+        let owner = Pubkey::new_unique();
+        let (input_mint, usdc) = (spl_token::native_mint::ID, Pubkey::new_unique());
+        let output_mint = Pubkey::new_unique();
+        // a two leg route, SOL to usdc to the output mint, through temporary wrapped sol
+        let transaction = confirmed_transaction(
+            &owner,
+            &[],
+            5_000,
+            (10_000_000, 8_995_000),
+            (
+                vec![token_balance(1, &output_mint, &owner, 0)],
+                vec![token_balance(1, &output_mint, &owner, 3_900)],
+            ),
+            vec![
+                ray_log([1_000_000, 0, 1, 1_000_000, 100_000, 50_000_000, 2_000]),
+                ray_log([2_000, 0, 2, 2_000, 10_000_000, 20_000_000, 3_900]),
+            ],
+        );
+        let receipt =
+            SwapReceipt::from_transaction_with_mints(&transaction, &owner, input_mint, output_mint)
+                .unwrap();
+        assert_eq!(receipt.ray_logs.len(), 2);
+        assert_eq!((receipt.amount_in, receipt.amount_out), (1_000_000, 3_900));
+
+        // without swap logs the input is read from the owner's lamports, less the fees it paid
+        let receipt = SwapReceipt::from_transaction_with_mints(
+            &confirmed_transaction(
+                &owner,
+                &[],
+                5_000,
+                (10_000_000, 8_995_000),
+                (vec![], vec![]),
+                vec![],
+            ),
+            &owner,
+            input_mint,
+            usdc,
+        )
+        .unwrap();
+        assert_eq!(receipt.amount_in, 1_000_000);
+
+        // the owner's wrapped sol account held 250_000 before the swap and is closed by it
+        let wrapped_sol = spl_associated_token_account::get_associated_token_address(
+            &owner,
+            &spl_token::native_mint::ID,
+        );
+        let wrapped_sol_lamports = 2_039_280 + 250_000;
+        let closing_transaction = |lamports, log_messages| {
+            let mut transaction = confirmed_transaction(
+                &owner,
+                &[solana_sdk::system_instruction::transfer(
+                    &owner,
+                    &wrapped_sol,
+                    1_000_000,
+                )],
+                5_000,
+                lamports,
+                (vec![token_balance(1, &input_mint, &owner, 250_000)], vec![]),
+                log_messages,
+            );
+            transaction.transaction.meta.as_mut().unwrap().pre_balances[1] = wrapped_sol_lamports;
+            transaction
+        };
+        let receipt = SwapReceipt::from_transaction_with_mints(
+            &closing_transaction(
+                (
+                    10_000_000,
+                    10_000_000 - 1_000_000 - 5_000 + wrapped_sol_lamports,
+                ),
+                vec![ray_log([
+                    1_000_000, 0, 1, 1_250_000, 100_000, 50_000_000, 2_000,
+                ])],
+            ),
+            &owner,
+            input_mint,
+            usdc,
+        )
+        .unwrap();
+        assert_eq!(receipt.amount_in, 1_000_000);
+        let receipt = SwapReceipt::from_transaction_with_mints(
+            &closing_transaction(
+                (
+                    10_000_000,
+                    10_000_000 - 1_000_000 - 5_000 + wrapped_sol_lamports,
+                ),
+                vec![],
+            ),
+            &owner,
+            input_mint,
+            usdc,
+        )
+        .unwrap();
+        assert_eq!(receipt.amount_in, 1_000_000);
+    }
+
+    #[test]
+    pub fn wrapped_sol_output_excludes_fees_and_tips() {
+        let owner = Pubkey::new_unique();
+        let input_mint = Pubkey::new_unique();
+        let transaction = confirmed_transaction(
+            &owner,
+            &[build_jito_tip_ix(&owner, 10_000)],
+            5_000,
+            (1_000_000, 1_485_000),
+            (
+                vec![token_balance(1, &input_mint, &owner, 7_000)],
+                vec![token_balance(1, &input_mint, &owner, 0)],
+            ),
+            vec![],
+        );
+        let receipt = SwapReceipt::from_transaction_with_mints(
+            &transaction,
+            &owner,
+            input_mint,
+            spl_token::native_mint::ID,
+        )
+        .unwrap();
+        assert_eq!(receipt.jito_tip, 10_000);
+        assert_eq!((receipt.amount_in, receipt.amount_out), (7_000, 500_000));
+    }
+}
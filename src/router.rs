@@ -0,0 +1,694 @@
+//! Multi-hop routing for mint pairs that don't share a direct pool.
+//!
+//! The router builds a graph of raydium pools around the input and output mints, finds paths
+//! through a small set of intermediate mints (SOL and USDC by default), chains quotes along each
+//! path and executes the best one as a single transaction.
+
+use crate::api_v3::response::{ApiV3AnyPoolsPage, ApiV3Pool};
+use crate::api_v3::{PoolFetchParams, PoolSort, PoolSortOrder, PoolType};
+use crate::builder::{SwapInstructionsBuilder, SwapPlan, UserAssociatedTokenAccounts};
+use crate::executor::{SwapExecutorDispatcher, SwapQuote};
+use crate::priority_fee::{PriorityFeeEstimator, RpcPriorityFeeEstimator};
+use crate::types::{
+    ComputeUnitLimits, DurableNonce, PriorityFeeConfig, ResolvedSwapConfig, SwapConfig,
+    SwapConfigOverrides, SwapExecutionMode, SwapInput,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::error::{Result, SwapError};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::transaction::VersionedTransaction;
+use solana_sdk::{pubkey, pubkey::Pubkey};
+
+const USDC_MINT: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+/// Routes are limited to this many swaps
+const MAX_HOPS: usize = 3;
+/// Only the most liquid pools of each mint pair are considered, to bound the number of paths
+const MAX_POOLS_PER_PAIR: usize = 2;
+
+pub struct RaydiumRouter {
+    client: Arc<RpcClient>,
+    dispatcher: SwapExecutorDispatcher,
+    config: SwapConfig,
+    priority_fee_estimator: Arc<dyn PriorityFeeEstimator>,
+    intermediate_mints: Vec<Pubkey>,
+}
+
+#[derive(Default)]
+pub struct RaydiumRouterOpts {
+    pub priority_fee: Option<PriorityFeeConfig>,
+    pub cu_limits: Option<ComputeUnitLimits>,
+    pub wrap_and_unwrap_sol: Option<bool>,
+    /// Builds legacy transactions instead of v0 transactions. Defaults to false
+    pub as_legacy_transaction: Option<bool>,
+    /// Builds transactions with a durable nonce instead of a recent blockhash
+    pub durable_nonce: Option<DurableNonce>,
+    /// Estimates the cu price of dynamic priority fees. Defaults to the rpc node's recent
+    /// prioritization fees
+    pub priority_fee_estimator: Option<Arc<dyn PriorityFeeEstimator>>,
+    /// Fails exact-in swaps whose simulation pays less than the output threshold into the output
+    /// account, after transfer fees. Defaults to false
+    pub check_simulated_output: Option<bool>,
+    /// Mints a route may pass through. Defaults to SOL and USDC
+    pub intermediate_mints: Option<Vec<Pubkey>>,
+}
+
+impl RaydiumRouter {
+    pub fn new(
+        client: Arc<RpcClient>,
+        config: RaydiumRouterOpts,
+        dispatcher: SwapExecutorDispatcher,
+    ) -> Self {
+        let RaydiumRouterOpts {
+            priority_fee,
+            cu_limits,
+            wrap_and_unwrap_sol,
+            as_legacy_transaction,
+            durable_nonce,
+            priority_fee_estimator,
+            check_simulated_output,
+            intermediate_mints,
+        } = config;
+        Self {
+            priority_fee_estimator: priority_fee_estimator
+                .unwrap_or_else(|| Arc::new(RpcPriorityFeeEstimator::new(Arc::clone(&client)))),
+            client,
+            dispatcher,
+            intermediate_mints: intermediate_mints
+                .unwrap_or_else(|| vec![spl_token::native_mint::ID, USDC_MINT]),
+            config: SwapConfig {
+                priority_fee,
+                cu_limits,
+                wrap_and_unwrap_sol,
+                as_legacy_transaction,
+                durable_nonce,
+                check_simulated_output,
+            },
+        }
+    }
+
+    /// Finds the route with the highest output for an exact-in swap. Direct pools are considered
+    /// alongside 2 and 3 hop routes. `swap_input.market` is ignored.
+    ///
+    /// Each leg is quoted for the expected output of the leg before it, and the slippage tolerance
+    /// is applied once, to the output of the final leg.
+    pub async fn quote(&self, swap_input: &SwapInput) -> Result<RouteQuote> {
+        if swap_input.input_token_mint == swap_input.output_token_mint {
+            return Err(SwapError::InvalidInput(format!(
+                "Input token cannot equal output token {}",
+                swap_input.input_token_mint
+            )));
+        }
+        if !swap_input.mode.amount_specified_is_input() {
+            return Err(SwapError::Unsupported(
+                "Routes only support exact-in swaps".to_string(),
+            ));
+        }
+
+        let graph = self.load_pool_graph(swap_input).await?;
+        let paths = graph.find_paths(
+            &swap_input.input_token_mint,
+            &swap_input.output_token_mint,
+            &self.intermediate_mints,
+            MAX_HOPS,
+        );
+        if paths.is_empty() {
+            return Err(SwapError::PoolNotFound(format!(
+                "no route from {} to {}",
+                swap_input.input_token_mint, swap_input.output_token_mint
+            )));
+        }
+
+        let quotes = futures_util::future::join_all(
+            paths.iter().map(|path| self.quote_path(path, swap_input)),
+        )
+        .await;
+        let mut route_quotes = Vec::with_capacity(quotes.len());
+        for (path, quote) in paths.iter().zip(quotes) {
+            match quote {
+                Ok(quote) => route_quotes.push(quote),
+                Err(e) => log::debug!(
+                    "Failed to quote route through pools {:?}: {:#}",
+                    path.iter().map(|edge| edge.pool).collect::<Vec<_>>(),
+                    e
+                ),
+            }
+        }
+
+        route_quotes
+            .into_iter()
+            .max_by_key(|quote| quote.other_amount)
+            .ok_or_else(|| {
+                SwapError::PoolNotFound(format!(
+                    "none of the {} routes from {} to {} could be quoted",
+                    paths.len(),
+                    swap_input.input_token_mint,
+                    swap_input.output_token_mint
+                ))
+            })
+    }
+
+    pub async fn swap_instructions(
+        &self,
+        input_pubkey: Pubkey,
+        output: RouteQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<Vec<Instruction>> {
+        let builder = self.make_swap(input_pubkey, output, overrides).await?;
+        builder.build_instructions()
+    }
+
+    pub async fn swap_transaction(
+        &self,
+        input_pubkey: Pubkey,
+        output: RouteQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<VersionedTransaction> {
+        let fee_payer = overrides.and_then(|o| o.fee_payer).unwrap_or(input_pubkey);
+        let builder = self.make_swap(input_pubkey, output, overrides).await?;
+        builder.build_transaction(Some(&fee_payer), None)
+    }
+
+    pub fn update_config(&mut self, config: &SwapConfig) {
+        self.config = *config;
+    }
+
+    /// Loads the pools around the input and output mints along with the pools connecting the
+    /// intermediate mints to each other
+    async fn load_pool_graph(&self, swap_input: &SwapInput) -> Result<PoolGraph> {
+        let mut mint_pairs = vec![
+            (swap_input.input_token_mint, None),
+            (swap_input.output_token_mint, None),
+        ];
+        for (i, mint_a) in self.intermediate_mints.iter().enumerate() {
+            for mint_b in &self.intermediate_mints[i + 1..] {
+                mint_pairs.push((*mint_a, Some(*mint_b)));
+            }
+        }
+
+        let params = PoolFetchParams {
+            pool_type: PoolType::All,
+            pool_sort: PoolSort::Liquidity,
+            sort_type: PoolSortOrder::Descending,
+            page_size: 100,
+            page: 1,
+        };
+        let responses = futures_util::future::join_all(mint_pairs.iter().map(|(mint1, mint2)| {
+            self.dispatcher
+                .api()
+                .fetch_pool_by_mints::<ApiV3Pool>(mint1, mint2.as_ref(), &params)
+        }))
+        .await;
+
+        let mut graph = PoolGraph::new(MAX_POOLS_PER_PAIR);
+        for ((mint1, mint2), response) in mint_pairs.iter().zip(responses) {
+            let response: ApiV3AnyPoolsPage = match response {
+                Ok(response) => response,
+                Err(e) => {
+                    log::debug!("Failed to fetch pools for {} / {:?}: {:#}", mint1, mint2, e);
+                    continue;
+                }
+            };
+            for pool in response.pools {
+                if self.dispatcher.executor(&pool.program_id).is_some() {
+                    graph.add_pool(
+                        pool.id,
+                        pool.program_id,
+                        pool.mint_a.address,
+                        pool.mint_b.address,
+                    );
+                }
+            }
+        }
+        if graph.is_empty() {
+            return Err(SwapError::PoolNotFound(format!(
+                "no pools found around {} and {}",
+                swap_input.input_token_mint, swap_input.output_token_mint
+            )));
+        }
+        Ok(graph)
+    }
+
+    /// Chains quotes along `path`, feeding each leg's expected output into the next leg
+    async fn quote_path(&self, path: &[PoolEdge], swap_input: &SwapInput) -> Result<RouteQuote> {
+        let mut legs = Vec::with_capacity(path.len());
+        let mut amount = swap_input.amount;
+        for (i, edge) in path.iter().enumerate() {
+            let is_last_leg = i == path.len() - 1;
+            let executor = self.dispatcher.executor(&edge.program_id).ok_or_else(|| {
+                SwapError::Unsupported(format!("No executor registered for pool {}", edge.pool))
+            })?;
+            let quote = executor
+                .quote(&SwapInput {
+                    input_token_mint: edge.input_mint,
+                    output_token_mint: edge.output_mint,
+                    slippage_bps: if is_last_leg {
+                        swap_input.slippage_bps
+                    } else {
+                        0
+                    },
+                    amount,
+                    mode: SwapExecutionMode::ExactIn,
+                    market: Some(edge.pool),
+                })
+                .await?;
+            amount = quote.other_amount();
+            legs.push(quote);
+        }
+
+        let last_leg = legs
+            .last()
+            .ok_or_else(|| SwapError::InvalidInput("Route has no legs".to_string()))?;
+        Ok(RouteQuote {
+            input_mint: swap_input.input_token_mint,
+            output_mint: swap_input.output_token_mint,
+            amount: swap_input.amount,
+            other_amount: last_leg.other_amount(),
+            other_amount_threshold: last_leg.other_amount_threshold(),
+            legs,
+        })
+    }
+
+    async fn make_swap(
+        &self,
+        input_pubkey: Pubkey,
+        output: RouteQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<SwapInstructionsBuilder> {
+        let config = self.config.resolve(input_pubkey, overrides);
+        let (first_leg, last_leg) = output
+            .legs
+            .first()
+            .zip(output.legs.last())
+            .ok_or_else(|| SwapError::InvalidInput("Route has no legs".to_string()))?;
+        let (input_token_program, _) = first_leg.token_programs()?;
+        let (_, output_token_program) = last_leg.token_programs()?;
+        let pool_ids = output
+            .legs
+            .iter()
+            .map(|leg| leg.market())
+            .collect::<Vec<_>>();
+        let plan = SwapPlan {
+            user: input_pubkey,
+            input_mint: output.input_mint,
+            output_mint: output.output_mint,
+            input_token_program,
+            output_token_program,
+            max_amount_in: output.amount,
+            pool_ids: &pool_ids,
+            min_amount_out: Some(output.other_amount_threshold),
+        };
+        SwapInstructionsBuilder::build_swap(
+            &config,
+            plan,
+            self.dispatcher.api(),
+            &self.client,
+            self.priority_fee_estimator.as_ref(),
+            |builder, associated_accounts| {
+                route_instructions(builder, &output, &config, input_pubkey, associated_accounts)
+            },
+        )
+        .await
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RouteQuote {
+    /// The input mint
+    pub input_mint: Pubkey,
+    /// The output mint
+    pub output_mint: Pubkey,
+    /// The amount in
+    pub amount: u64,
+    /// The amount out of the final leg
+    pub other_amount: u64,
+    /// The amount out of the final leg with slippage
+    pub other_amount_threshold: u64,
+    /// Quotes for each swap along the route, in order. Each leg spends the expected output of the
+    /// leg before it, so intermediate legs are quoted without slippage and must pay out in full:
+    /// a shortfall would otherwise be made up from the user's own intermediate token balance.
+    pub legs: Vec<SwapQuote>,
+}
+
+/// A swap through a pool in one direction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoolEdge {
+    pub pool: Pubkey,
+    pub program_id: Pubkey,
+    pub input_mint: Pubkey,
+    pub output_mint: Pubkey,
+}
+
+/// Pools indexed by the mint they swap from
+#[derive(Debug, Default)]
+pub struct PoolGraph {
+    edges: HashMap<Pubkey, Vec<PoolEdge>>,
+    max_pools_per_pair: usize,
+}
+
+impl PoolGraph {
+    pub fn new(max_pools_per_pair: usize) -> Self {
+        Self {
+            edges: HashMap::new(),
+            max_pools_per_pair,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    /// Adds a pool in both directions. Pools beyond `max_pools_per_pair` for a mint pair are
+    /// ignored, so pools should be added from most to least preferred.
+    pub fn add_pool(&mut self, pool: Pubkey, program_id: Pubkey, mint_a: Pubkey, mint_b: Pubkey) {
+        let edges = self.edges.entry(mint_a).or_default();
+        let pair_pools = edges.iter().filter(|edge| edge.output_mint == mint_b);
+        if edges.iter().any(|edge| edge.pool == pool)
+            || pair_pools.count() >= self.max_pools_per_pair
+        {
+            return;
+        }
+        edges.push(PoolEdge {
+            pool,
+            program_id,
+            input_mint: mint_a,
+            output_mint: mint_b,
+        });
+        self.edges.entry(mint_b).or_default().push(PoolEdge {
+            pool,
+            program_id,
+            input_mint: mint_b,
+            output_mint: mint_a,
+        });
+    }
+
+    /// Finds every path of at most `max_hops` swaps from `input_mint` to `output_mint` that only
+    /// passes through `intermediate_mints`, without visiting a mint twice
+    pub fn find_paths(
+        &self,
+        input_mint: &Pubkey,
+        output_mint: &Pubkey,
+        intermediate_mints: &[Pubkey],
+        max_hops: usize,
+    ) -> Vec<Vec<PoolEdge>> {
+        let mut paths = Vec::new();
+        self.extend_paths(
+            &mut Vec::new(),
+            input_mint,
+            output_mint,
+            intermediate_mints,
+            max_hops,
+            &mut paths,
+        );
+        paths
+    }
+
+    fn extend_paths(
+        &self,
+        path: &mut Vec<PoolEdge>,
+        current_mint: &Pubkey,
+        output_mint: &Pubkey,
+        intermediate_mints: &[Pubkey],
+        max_hops: usize,
+        paths: &mut Vec<Vec<PoolEdge>>,
+    ) {
+        if path.len() == max_hops {
+            return;
+        }
+        for edge in self.edges.get(current_mint).into_iter().flatten() {
+            if edge.output_mint == *output_mint {
+                path.push(*edge);
+                paths.push(path.clone());
+                path.pop();
+                continue;
+            }
+            let visited = edge.output_mint == path.first().map_or(*current_mint, |e| e.input_mint)
+                || path.iter().any(|e| e.output_mint == edge.output_mint);
+            if visited || !intermediate_mints.contains(&edge.output_mint) {
+                continue;
+            }
+            path.push(*edge);
+            self.extend_paths(
+                path,
+                &edge.output_mint,
+                output_mint,
+                intermediate_mints,
+                max_hops,
+                paths,
+            );
+            path.pop();
+        }
+    }
+}
+
+/// Adds the instructions of the legs of `route` to `builder`, creating the user's intermediate
+/// token accounts
+fn route_instructions(
+    builder: &mut SwapInstructionsBuilder,
+    route: &RouteQuote,
+    config: &ResolvedSwapConfig,
+    user: Pubkey,
+    associated_accounts: &UserAssociatedTokenAccounts,
+) -> Result<()> {
+    // intermediate accounts receive the output of one leg and fund the next. Wrapped sol is
+    // unwrapped once the route completes, like the input and output accounts
+    let last_index = route.legs.len() - 1;
+    for leg in &route.legs[..last_index] {
+        let (_, token_program) = leg.token_programs()?;
+        builder.setup_instructions.push(
+            spl_associated_token_account::instruction::create_associated_token_account_idempotent(
+                &config.fee_payer,
+                &user,
+                &leg.output_mint(),
+                &token_program,
+            ),
+        );
+        if config.wrap_and_unwrap_sol && leg.output_mint() == spl_token::native_mint::ID {
+            builder.cleanup_instructions.push(
+                spl_token::instruction::close_account(
+                    &spl_token::ID,
+                    &spl_associated_token_account::get_associated_token_address(
+                        &user,
+                        &spl_token::native_mint::ID,
+                    ),
+                    &user,
+                    &user,
+                    &[],
+                )
+                .expect("spl_token::ID is valid"),
+            );
+        }
+    }
+    for (index, leg) in route.legs.iter().enumerate() {
+        let (input_token_program, output_token_program) = leg.token_programs()?;
+        let user_source = if index == 0 {
+            associated_accounts.input_ata
+        } else {
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &user,
+                &leg.input_mint(),
+                &input_token_program,
+            )
+        };
+        let user_destination = if index == last_index {
+            associated_accounts.output_ata
+        } else {
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &user,
+                &leg.output_mint(),
+                &output_token_program,
+            )
+        };
+        builder.swap_instructions.push(leg.swap_instruction(
+            &user,
+            &user_source,
+            &user_destination,
+        )?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_v3::ApiV3Client;
+    use crate::cpmm::executor::RaydiumCpmmQuote;
+    use crate::executor::SwapExecutor;
+    use crate::utils::amount_with_slippage;
+    use async_trait::async_trait;
+
+    /// Quotes every pool at a price of 2 output tokens per input token
+    struct DoublingExecutor {
+        program_id: Pubkey,
+    }
+
+    #[async_trait]
+    impl SwapExecutor for DoublingExecutor {
+        fn program_id(&self) -> Pubkey {
+            self.program_id
+        }
+
+        async fn quote(&self, swap_input: &SwapInput) -> Result<SwapQuote> {
+            let other_amount = swap_input.amount * 2;
+            Ok(SwapQuote::Cpmm(RaydiumCpmmQuote {
+                input_mint: swap_input.input_token_mint,
+                output_mint: swap_input.output_token_mint,
+                other_amount_threshold: amount_with_slippage(
+                    other_amount,
+                    swap_input.slippage_bps as u64,
+                    false,
+                )?,
+                ..RaydiumCpmmQuote::test_quote(
+                    swap_input.market.unwrap_or_default(),
+                    swap_input.amount,
+                    other_amount,
+                )
+            }))
+        }
+
+        async fn swap_instructions(
+            &self,
+            _input_pubkey: Pubkey,
+            _quote: SwapQuote,
+            _overrides: Option<&SwapConfigOverrides>,
+        ) -> Result<Vec<Instruction>> {
+            Err(SwapError::Unsupported("quote only executor".to_string()))
+        }
+
+        async fn swap_transaction(
+            &self,
+            _input_pubkey: Pubkey,
+            _quote: SwapQuote,
+            _overrides: Option<&SwapConfigOverrides>,
+        ) -> Result<VersionedTransaction> {
+            Err(SwapError::Unsupported("quote only executor".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    pub async fn quote_path_chains_leg_thresholds() {
+        let program_id = Pubkey::new_unique();
+        let router = RaydiumRouter::new(
+            Arc::new(RpcClient::new("http://localhost:8899".to_string())),
+            RaydiumRouterOpts::default(),
+            SwapExecutorDispatcher::new(
+                ApiV3Client::default(),
+                vec![Box::new(DoublingExecutor { program_id })],
+            ),
+        );
+        let [token_a, sol, token_b] = [(); 3].map(|_| Pubkey::new_unique());
+        let path = [
+            PoolEdge {
+                pool: Pubkey::new_unique(),
+                program_id,
+                input_mint: token_a,
+                output_mint: sol,
+            },
+            PoolEdge {
+                pool: Pubkey::new_unique(),
+                program_id,
+                input_mint: sol,
+                output_mint: token_b,
+            },
+        ];
+        let route = router
+            .quote_path(
+                &path,
+                &SwapInput {
+                    input_token_mint: token_a,
+                    output_token_mint: token_b,
+                    slippage_bps: 100,
+                    amount: 1_000_000,
+                    mode: SwapExecutionMode::ExactIn,
+                    market: None,
+                },
+            )
+            .await
+            .unwrap();
+
+        // each leg spends the previous leg's expected output, only the last leg allows slippage
+        let amounts = route
+            .legs
+            .iter()
+            .map(|leg| {
+                (
+                    leg.amount(),
+                    leg.other_amount(),
+                    leg.other_amount_threshold(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            amounts,
+            vec![
+                (1_000_000, 2_000_000, 2_000_000),
+                (2_000_000, 4_000_000, 3_960_000),
+            ]
+        );
+        assert_eq!(route.other_amount, 4_000_000);
+        assert_eq!(
+            route.other_amount_threshold,
+            amount_with_slippage(4_000_000, 100, false).unwrap()
+        );
+    }
+
+    #[test]
+    pub fn find_paths_through_intermediate_mints() {
+        let [token_a, token_b, sol, usdc, other] = [(); 5].map(|_| Pubkey::new_unique());
+        let program_id = Pubkey::new_unique();
+        let mut graph = PoolGraph::new(2);
+        let pool_a_sol = Pubkey::new_unique();
+        let pool_sol_usdc = Pubkey::new_unique();
+        let pool_usdc_b = Pubkey::new_unique();
+        let pool_sol_b = Pubkey::new_unique();
+        graph.add_pool(pool_a_sol, program_id, token_a, sol);
+        graph.add_pool(pool_sol_usdc, program_id, sol, usdc);
+        graph.add_pool(pool_usdc_b, program_id, usdc, token_b);
+        graph.add_pool(pool_sol_b, program_id, sol, token_b);
+        // not an intermediate mint, so never routed through
+        graph.add_pool(Pubkey::new_unique(), program_id, token_a, other);
+        graph.add_pool(Pubkey::new_unique(), program_id, other, token_b);
+
+        let mut paths: Vec<Vec<Pubkey>> = graph
+            .find_paths(&token_a, &token_b, &[sol, usdc], 3)
+            .into_iter()
+            .map(|path| path.into_iter().map(|edge| edge.pool).collect())
+            .collect();
+        paths.sort_by_key(|path| path.len());
+        assert_eq!(
+            paths,
+            vec![
+                vec![pool_a_sol, pool_sol_b],
+                vec![pool_a_sol, pool_sol_usdc, pool_usdc_b],
+            ]
+        );
+        assert_eq!(
+            graph.find_paths(&token_a, &token_b, &[sol, usdc], 2).len(),
+            1
+        );
+    }
+
+    #[test]
+    pub fn add_pool_limits_pools_per_pair() {
+        let (token_a, token_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let program_id = Pubkey::new_unique();
+        let mut graph = PoolGraph::new(2);
+        let pools = [(); 3].map(|_| Pubkey::new_unique());
+        for pool in pools {
+            graph.add_pool(pool, program_id, token_a, token_b);
+        }
+        // re-adding a known pool is a no-op
+        graph.add_pool(pools[0], program_id, token_b, token_a);
+
+        let paths = graph.find_paths(&token_b, &token_a, &[], 3);
+        assert_eq!(
+            paths
+                .into_iter()
+                .map(|path| path[0].pool)
+                .collect::<Vec<_>>(),
+            pools[..2]
+        );
+    }
+}
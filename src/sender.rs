@@ -0,0 +1,450 @@
+//! Signing, sending and confirming swaps.
+//!
+//! [`SwapSender::execute`] quotes a swap, builds and signs its transaction with a fresh blockhash
+//! and rebroadcasts it until it is confirmed or the blockhash expires. An expired swap is quoted
+//! and built again, since the pool has likely moved by then.
+//!
+//! With a [`JitoClient`] set, the swap is sent as a bundle through the block engine first, and
+//! through the rpc node if the bundle fails or doesn't land in time.
+
+use crate::error::{Result, SwapError};
+use crate::executor::{SwapExecutor, SwapQuote};
+use crate::jito::{tip_transaction_message, BundleStatus, JitoClient};
+use crate::types::{SwapConfigOverrides, SwapInput};
+
+use solana_client::client_error::ClientErrorKind;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_client::rpc_request::{RpcError, RpcResponseErrorData};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
+use solana_sdk::signature::{Signature, Signer};
+use solana_sdk::system_instruction::SystemInstruction;
+use solana_sdk::system_program;
+use solana_sdk::transaction::{Result as TransactionResult, VersionedTransaction};
+use std::sync::Arc;
+use std::time::Duration;
+
+const DEFAULT_REBROADCAST_INTERVAL: Duration = Duration::from_secs(2);
+const DEFAULT_MAX_REQUOTES: usize = 2;
+const DEFAULT_BUNDLE_TIMEOUT: Duration = Duration::from_secs(30);
+const BUNDLE_STATUS_INTERVAL: Duration = Duration::from_millis(500);
+/// A blockhash is valid for about a minute, so a transaction is never rebroadcast more often than
+/// this, whatever the interval or the rpc node's errors
+const MAX_REBROADCASTS: usize = 150;
+
+#[derive(Clone)]
+pub struct SwapSender {
+    client: Arc<RpcClient>,
+    commitment: CommitmentConfig,
+    rebroadcast_interval: Duration,
+    max_requotes: usize,
+    skip_preflight: bool,
+    jito: Option<JitoClient>,
+    jito_tip: Option<u64>,
+    bundle_timeout: Duration,
+}
+
+#[derive(Default)]
+pub struct SwapSenderOpts {
+    /// The commitment a swap is confirmed at. Defaults to confirmed
+    pub commitment: Option<CommitmentConfig>,
+    /// How often an unconfirmed transaction is sent again. Defaults to 2 seconds
+    pub rebroadcast_interval: Option<Duration>,
+    /// How many times an expired swap is quoted and sent again. Defaults to 2
+    pub max_requotes: Option<usize>,
+    /// Skips simulating the transaction before the first send. Defaults to false
+    pub skip_preflight: Option<bool>,
+    /// Sends swaps as bundles through this block engine, falling back to the rpc node
+    pub jito: Option<JitoClient>,
+    /// Lamports tipped by the fee payer in a separate transaction at the end of the bundle.
+    /// Without it the swap must carry its own tip, see `PriorityFeeConfig::JitoTip`
+    pub jito_tip: Option<u64>,
+    /// How long a bundle may stay pending before the swap is sent through the rpc node.
+    /// Defaults to 30 seconds
+    pub bundle_timeout: Option<Duration>,
+}
+
+/// The outcome of a landed swap
+#[derive(Debug, Clone)]
+pub struct SwapExecution {
+    /// The signature of the transaction that landed
+    pub signature: Signature,
+    /// The slot the transaction landed in
+    pub slot: u64,
+    /// The quote the landed transaction was built from
+    pub quote: SwapQuote,
+    /// Whether the transaction succeeded. A failed transaction still paid its fees
+    pub status: TransactionResult<()>,
+    /// How many times the swap was quoted and sent before it landed, starting at 1
+    pub attempts: usize,
+}
+
+impl SwapSender {
+    pub fn new(client: Arc<RpcClient>, config: SwapSenderOpts) -> Self {
+        let SwapSenderOpts {
+            commitment,
+            rebroadcast_interval,
+            max_requotes,
+            skip_preflight,
+            jito,
+            jito_tip,
+            bundle_timeout,
+        } = config;
+        Self {
+            client,
+            commitment: commitment.unwrap_or(CommitmentConfig::confirmed()),
+            rebroadcast_interval: rebroadcast_interval.unwrap_or(DEFAULT_REBROADCAST_INTERVAL),
+            max_requotes: max_requotes.unwrap_or(DEFAULT_MAX_REQUOTES),
+            skip_preflight: skip_preflight.unwrap_or(false),
+            jito,
+            jito_tip,
+            bundle_timeout: bundle_timeout.unwrap_or(DEFAULT_BUNDLE_TIMEOUT),
+        }
+    }
+
+    /// Quotes, builds, signs and sends a swap, then waits until it lands at the configured
+    /// commitment. The first signer is the swapping wallet, followed by the fee payer if
+    /// `overrides` sets a separate one.
+    ///
+    /// Durable nonce transactions never expire and are meant to be signed offline, so they are
+    /// not supported here.
+    pub async fn execute<E: SwapExecutor + ?Sized>(
+        &self,
+        executor: &E,
+        swap_input: &SwapInput,
+        signers: &[&dyn Signer],
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<SwapExecution> {
+        let owner = signers
+            .first()
+            .ok_or_else(|| SwapError::InvalidInput("No signers given".to_string()))?
+            .pubkey();
+        let mut attempt = 1;
+        loop {
+            let quote = executor.quote(swap_input).await?;
+            let transaction = executor
+                .swap_transaction(owner, quote.clone(), overrides)
+                .await?;
+            check_executable(&transaction)?;
+            let (signature, last_valid_block_height) =
+                self.sign_and_send(transaction, signers).await?;
+
+            if let Some((slot, status)) = self.confirm(&signature, last_valid_block_height).await? {
+                return Ok(SwapExecution {
+                    signature,
+                    slot,
+                    quote,
+                    status,
+                    attempts: attempt,
+                });
+            }
+            log::debug!(
+                "Swap transaction {} expired, attempt {} of {}",
+                signature,
+                attempt,
+                self.max_requotes + 1
+            );
+            if !can_requote(attempt, self.max_requotes) {
+                return Err(SwapError::Transaction(format!(
+                    "Swap did not land after {} attempts",
+                    attempt
+                )));
+            }
+            attempt += 1;
+        }
+    }
+
+    /// Signs `transaction` with a fresh blockhash and sends it, as a bundle if a block engine is
+    /// set. Returns the signature and the last block height the blockhash is valid for.
+    async fn sign_and_send(
+        &self,
+        transaction: VersionedTransaction,
+        signers: &[&dyn Signer],
+    ) -> Result<(Signature, u64)> {
+        let (blockhash, last_valid_block_height) = self
+            .client
+            .get_latest_blockhash_with_commitment(self.commitment)
+            .await?;
+        let mut message = transaction.message;
+        message.set_recent_blockhash(blockhash);
+        let transaction = VersionedTransaction::try_new(message, signers)
+            .map_err(|e| SwapError::Transaction(format!("Failed to sign: {}", e)))?;
+
+        if let Some(jito) = &self.jito {
+            match self
+                .send_bundle(jito, &transaction, signers, blockhash)
+                .await
+            {
+                Ok(()) => return Ok((transaction.signatures[0], last_valid_block_height)),
+                Err(e) => log::debug!(
+                    "Bundle for {} did not land, sending through rpc: {:#}",
+                    transaction.signatures[0],
+                    e
+                ),
+            }
+        }
+
+        // preflight errors such as exceeded slippage are returned, the transaction can't land
+        let signature = self
+            .client
+            .send_transaction_with_config(
+                &transaction,
+                RpcSendTransactionConfig {
+                    skip_preflight: self.skip_preflight,
+                    preflight_commitment: Some(self.commitment.commitment),
+                    max_retries: Some(0),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| match e.kind() {
+                ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                    data: RpcResponseErrorData::SendTransactionPreflightFailure(_),
+                    message,
+                    ..
+                }) => SwapError::Transaction(message.clone()),
+                _ => e.into(),
+            })?;
+        self.rebroadcast(transaction, last_valid_block_height);
+
+        Ok((signature, last_valid_block_height))
+    }
+
+    /// Sends `transaction` as a bundle, followed by a tip transaction if a tip is configured, and
+    /// waits until the bundle lands. Fails if the bundle is rejected, fails or is still pending
+    /// after the bundle timeout.
+    async fn send_bundle(
+        &self,
+        jito: &JitoClient,
+        transaction: &VersionedTransaction,
+        signers: &[&dyn Signer],
+        blockhash: Hash,
+    ) -> Result<()> {
+        let mut bundle = vec![transaction.clone()];
+        if let Some(tip) = self.jito_tip {
+            let payer = transaction.message.static_account_keys()[0];
+            let payer_signer = signers
+                .iter()
+                .find(|signer| signer.pubkey() == payer)
+                .ok_or_else(|| {
+                    SwapError::InvalidInput(format!("No signer for fee payer {}", payer))
+                })?;
+            let tip_transaction = VersionedTransaction::try_new(
+                tip_transaction_message(&payer, tip, blockhash),
+                &[*payer_signer],
+            )
+            .map_err(|e| SwapError::Transaction(format!("Failed to sign tip: {}", e)))?;
+            bundle.push(tip_transaction);
+        }
+
+        let bundle_id = jito.send_bundle(&bundle).await?;
+        let started = tokio::time::Instant::now();
+        // a bundle is unknown to the block engine until it is processed, so invalid is only
+        // final once the timeout passes
+        while started.elapsed() < self.bundle_timeout {
+            tokio::time::sleep(BUNDLE_STATUS_INTERVAL).await;
+            match jito.get_inflight_bundle_status(&bundle_id).await {
+                Ok(BundleStatus::Landed) => return Ok(()),
+                Ok(BundleStatus::Failed) => {
+                    return Err(SwapError::BlockEngine(format!(
+                        "Bundle {} failed",
+                        bundle_id
+                    )))
+                }
+                Ok(BundleStatus::Pending | BundleStatus::Invalid) => {}
+                Err(e) => log::debug!("Failed to get bundle status: {:#}", e),
+            }
+        }
+        Err(SwapError::BlockEngine(format!(
+            "Bundle {} did not land within {:?}",
+            bundle_id, self.bundle_timeout
+        )))
+    }
+
+    /// Keeps sending `transaction` in the background until it lands or the block height passes
+    /// `last_valid_block_height`. Rpc nodes drop transactions they could not forward, so a single
+    /// send often doesn't land under load.
+    fn rebroadcast(&self, transaction: VersionedTransaction, last_valid_block_height: u64) {
+        let sender = self.clone();
+        tokio::spawn(async move {
+            let config = RpcSendTransactionConfig {
+                skip_preflight: true,
+                max_retries: Some(0),
+                ..Default::default()
+            };
+            for attempt in 1.. {
+                tokio::time::sleep(sender.rebroadcast_interval).await;
+                match sender
+                    .client
+                    .get_signature_statuses(&transaction.signatures[..1])
+                    .await
+                {
+                    Ok(response) if response.value[0].is_some() => return,
+                    Ok(_) => {}
+                    Err(e) => log::debug!("Failed to get signature status: {:#}", e),
+                }
+                // an unknown block height still uses up an attempt, so a failing rpc node can't
+                // keep the task alive
+                let block_height = sender
+                    .client
+                    .get_block_height_with_commitment(sender.commitment)
+                    .await
+                    .map_err(|e| log::debug!("Failed to get block height: {:#}", e))
+                    .ok();
+                if !keep_rebroadcasting(attempt, block_height, last_valid_block_height) {
+                    return;
+                }
+                if let Err(e) = sender
+                    .client
+                    .send_transaction_with_config(&transaction, config)
+                    .await
+                {
+                    log::debug!(
+                        "Failed to rebroadcast {}: {:#}",
+                        transaction.signatures[0],
+                        e
+                    );
+                }
+            }
+        });
+    }
+
+    /// Waits until `signature` lands at the configured commitment and returns its slot and
+    /// status. Returns `None` once the block height passes `last_valid_block_height` without the
+    /// transaction landing.
+    async fn confirm(
+        &self,
+        signature: &Signature,
+        last_valid_block_height: u64,
+    ) -> Result<Option<(u64, TransactionResult<()>)>> {
+        loop {
+            let status = self
+                .client
+                .get_signature_statuses(&[*signature])
+                .await?
+                .value
+                .into_iter()
+                .next()
+                .flatten();
+            if let Some(status) = status {
+                if status.err.is_some() || status.satisfies_commitment(self.commitment) {
+                    return Ok(Some((status.slot, status.status)));
+                }
+            }
+
+            // the transaction can still land in the last valid block, so the status is checked
+            // once more after expiry before giving up
+            let block_height = self
+                .client
+                .get_block_height_with_commitment(self.commitment)
+                .await?;
+            if block_height > last_valid_block_height {
+                let status = self
+                    .client
+                    .get_signature_statuses_with_history(&[*signature])
+                    .await?
+                    .value
+                    .into_iter()
+                    .next()
+                    .flatten();
+                return Ok(status
+                    .filter(|status| {
+                        status.err.is_some() || status.satisfies_commitment(self.commitment)
+                    })
+                    .map(|status| (status.slot, status.status)));
+            }
+
+            tokio::time::sleep(self.rebroadcast_interval.min(Duration::from_millis(500))).await;
+        }
+    }
+}
+
+/// Rejects the transactions [`SwapSender::execute`] can't send. A durable nonce transaction,
+/// from the overrides or the executor's config, starts by advancing the nonce and would be
+/// invalidated by the fresh blockhash.
+fn check_executable(transaction: &VersionedTransaction) -> Result<()> {
+    let message = &transaction.message;
+    let advances_nonce = message.instructions().first().is_some_and(|instruction| {
+        message
+            .static_account_keys()
+            .get(usize::from(instruction.program_id_index))
+            .is_some_and(system_program::check_id)
+            && matches!(
+                bincode::deserialize(&instruction.data),
+                Ok(SystemInstruction::AdvanceNonceAccount)
+            )
+    });
+    if advances_nonce {
+        return Err(SwapError::Unsupported(
+            "Durable nonce transactions can't be executed".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether a swap whose `attempt`th transaction expired is quoted and sent again
+fn can_requote(attempt: usize, max_requotes: usize) -> bool {
+    attempt <= max_requotes
+}
+
+/// Whether a transaction is sent again after `attempt` rebroadcasts, at `block_height`, or with
+/// the block height unknown
+fn keep_rebroadcasting(
+    attempt: usize,
+    block_height: Option<u64>,
+    last_valid_block_height: u64,
+) -> bool {
+    attempt <= MAX_REBROADCASTS
+        && block_height.is_none_or(|block_height| block_height <= last_valid_block_height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::message::{v0, VersionedMessage};
+    use solana_sdk::pubkey::Pubkey;
+    use solana_sdk::signature::Signature;
+    use solana_sdk::system_instruction;
+
+    #[test]
+    pub fn requotes_until_max_requotes() {
+        assert!(!can_requote(1, 0));
+        assert!(can_requote(1, 2));
+        assert!(can_requote(2, 2));
+        assert!(!can_requote(3, 2));
+    }
+
+    #[test]
+    pub fn rebroadcasts_until_expiry_or_max_attempts() {
+        assert!(keep_rebroadcasting(1, Some(100), 100));
+        assert!(!keep_rebroadcasting(1, Some(101), 100));
+        assert!(keep_rebroadcasting(1, None, 100));
+        assert!(keep_rebroadcasting(MAX_REBROADCASTS, None, 100));
+        assert!(!keep_rebroadcasting(MAX_REBROADCASTS + 1, None, 100));
+        assert!(!keep_rebroadcasting(MAX_REBROADCASTS + 1, Some(1), 100));
+    }
+
+    #[test]
+    pub fn rejects_durable_nonce_transactions() {
+        let payer = Pubkey::new_unique();
+        let transaction = |instructions: &[_]| {
+            let message =
+                v0::Message::try_compile(&payer, instructions, &[], Hash::new_unique()).unwrap();
+            VersionedTransaction {
+                signatures: vec![Signature::default()],
+                message: VersionedMessage::V0(message),
+            }
+        };
+        let transfer = system_instruction::transfer(&payer, &Pubkey::new_unique(), 1);
+        let advance = system_instruction::advance_nonce_account(&Pubkey::new_unique(), &payer);
+
+        assert!(check_executable(&transaction(&[])).is_ok());
+        assert!(check_executable(&transaction(&[transfer.clone()])).is_ok());
+        assert!(check_executable(&transaction(&[transfer.clone(), advance.clone()])).is_ok());
+        assert!(matches!(
+            check_executable(&transaction(&[advance, transfer])),
+            Err(SwapError::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,486 @@
+//! Splits large exact-in orders across several pools of the same pair to reduce price impact.
+//!
+//! Pool accounts are loaded once and every candidate allocation is quoted offline with the same
+//! math the executors use, so the split reflects what each pool will actually pay out.
+
+use crate::amm::executor::{
+    MarketKeys, RaydiumAmm, RaydiumAmmAccounts, RaydiumAmmExecutorOpts,
+    RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID,
+};
+use crate::api_v3::response::ApiV3AnyPoolsPage;
+use crate::api_v3::{ApiV3Client, PoolFetchParams, PoolSort, PoolSortOrder, PoolType};
+use crate::builder::{SwapInstructionsBuilder, SwapPlan};
+use crate::cpmm::executor::{
+    RaydiumCpmm, RaydiumCpmmAccounts, RaydiumCpmmExecutorOpts, RAYDIUM_CPMM_PROGRAM_ID,
+};
+use crate::executor::SwapQuote;
+use crate::priority_fee::{PriorityFeeEstimator, RpcPriorityFeeEstimator};
+use crate::types::{
+    ComputeUnitLimits, DurableNonce, PriorityFeeConfig, SwapConfig, SwapConfigOverrides, SwapInput,
+};
+use std::sync::Arc;
+
+use crate::error::{Result, SwapError};
+use raydium_library::amm::AmmKeys;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::VersionedTransaction;
+
+/// The order is allocated to pools in this many increments
+const SPLIT_STEPS: u64 = 50;
+const DEFAULT_MAX_POOLS: usize = 4;
+
+pub struct RaydiumSplitter {
+    client: Arc<RpcClient>,
+    api: ApiV3Client,
+    amm: RaydiumAmm,
+    cpmm: RaydiumCpmm,
+    config: SwapConfig,
+    priority_fee_estimator: Arc<dyn PriorityFeeEstimator>,
+    max_pools: usize,
+}
+
+#[derive(Default)]
+pub struct RaydiumSplitterOpts {
+    pub priority_fee: Option<PriorityFeeConfig>,
+    pub cu_limits: Option<ComputeUnitLimits>,
+    pub wrap_and_unwrap_sol: Option<bool>,
+    /// Builds legacy transactions instead of v0 transactions. Defaults to false
+    pub as_legacy_transaction: Option<bool>,
+    /// Builds transactions with a durable nonce instead of a recent blockhash
+    pub durable_nonce: Option<DurableNonce>,
+    /// Estimates the cu price of dynamic priority fees. Defaults to the rpc node's recent
+    /// prioritization fees
+    pub priority_fee_estimator: Option<Arc<dyn PriorityFeeEstimator>>,
+    /// Fails exact-in swaps whose simulation pays less than the output threshold into the output
+    /// account, after transfer fees. Defaults to false
+    pub check_simulated_output: Option<bool>,
+    /// The most liquid pools considered for a split. Defaults to 4
+    pub max_pools: Option<usize>,
+}
+
+impl RaydiumSplitter {
+    pub fn new(client: Arc<RpcClient>, config: RaydiumSplitterOpts, api: ApiV3Client) -> Self {
+        let RaydiumSplitterOpts {
+            priority_fee,
+            cu_limits,
+            wrap_and_unwrap_sol,
+            as_legacy_transaction,
+            durable_nonce,
+            priority_fee_estimator,
+            check_simulated_output,
+            max_pools,
+        } = config;
+        Self {
+            priority_fee_estimator: priority_fee_estimator
+                .unwrap_or_else(|| Arc::new(RpcPriorityFeeEstimator::new(Arc::clone(&client)))),
+            amm: RaydiumAmm::new(
+                Arc::clone(&client),
+                RaydiumAmmExecutorOpts::default(),
+                api.clone(),
+            ),
+            cpmm: RaydiumCpmm::new(
+                Arc::clone(&client),
+                RaydiumCpmmExecutorOpts::default(),
+                api.clone(),
+            ),
+            client,
+            api,
+            max_pools: max_pools.unwrap_or(DEFAULT_MAX_POOLS),
+            config: SwapConfig {
+                priority_fee,
+                cu_limits,
+                wrap_and_unwrap_sol,
+                as_legacy_transaction,
+                durable_nonce,
+                check_simulated_output,
+            },
+        }
+    }
+
+    /// Splits an exact-in swap across the amm v4 and cpmm pools of the mint pair to maximize the
+    /// total output. `swap_input.market` is ignored.
+    pub async fn quote(&self, swap_input: &SwapInput) -> Result<SplitQuote> {
+        if swap_input.input_token_mint == swap_input.output_token_mint {
+            return Err(SwapError::InvalidInput(format!(
+                "Input token cannot equal output token {}",
+                swap_input.input_token_mint
+            )));
+        }
+        if !swap_input.mode.amount_specified_is_input() {
+            return Err(SwapError::Unsupported(
+                "Split orders only support exact-in swaps".to_string(),
+            ));
+        }
+
+        // cp-swap pools are listed as standard pools alongside amm v4 pools
+        let response: ApiV3AnyPoolsPage = self
+            .api
+            .fetch_pool_by_mints(
+                &swap_input.input_token_mint,
+                Some(&swap_input.output_token_mint),
+                &PoolFetchParams {
+                    pool_type: PoolType::Standard,
+                    pool_sort: PoolSort::Liquidity,
+                    sort_type: PoolSortOrder::Descending,
+                    page_size: 100,
+                    page: 1,
+                },
+            )
+            .await?;
+        let pools = response
+            .pools
+            .into_iter()
+            .filter(|pool| {
+                let matches_mints = (pool.mint_a.address == swap_input.input_token_mint
+                    && pool.mint_b.address == swap_input.output_token_mint)
+                    || (pool.mint_a.address == swap_input.output_token_mint
+                        && pool.mint_b.address == swap_input.input_token_mint);
+                matches_mints
+                    && (pool.program_id == RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID
+                        || pool.program_id == RAYDIUM_CPMM_PROGRAM_ID)
+            })
+            .take(self.max_pools)
+            .collect::<Vec<_>>();
+
+        let now = crate::utils::get_cluster_unix_timestamp(&self.client)
+            .await?
+            .max(0) as u64;
+        let snapshots = futures_util::future::join_all(
+            pools
+                .iter()
+                .map(|pool| self.load_snapshot(pool.program_id, pool.id, now)),
+        )
+        .await;
+        let snapshots = pools
+            .iter()
+            .zip(snapshots)
+            .filter_map(|(pool, snapshot)| match snapshot {
+                Ok(snapshot) => Some(snapshot),
+                Err(e) => {
+                    log::debug!("Failed to load pool {}: {:#}", pool.id, e);
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Self::quote_from_snapshots(swap_input, &snapshots)
+    }
+
+    /// Splits an exact-in swap across already loaded pools without any network requests.
+    pub fn quote_from_snapshots(
+        swap_input: &SwapInput,
+        pools: &[PoolSnapshot],
+    ) -> Result<SplitQuote> {
+        // the split is driven by raw outputs, slippage is applied per leg afterwards
+        let quote_output = |pool: usize, amount: u64| {
+            pools[pool]
+                .quote(&SwapInput {
+                    amount,
+                    slippage_bps: 0,
+                    ..*swap_input
+                })
+                .ok()
+                .map(|quote| quote.other_amount())
+        };
+
+        let best_single_pool = pools
+            .iter()
+            .filter_map(|pool| pool.quote(swap_input).ok())
+            .max_by_key(|quote| quote.other_amount())
+            .ok_or_else(|| {
+                SwapError::PoolNotFound("none of the pools could quote the swap".to_string())
+            })?;
+        let allocations = split_amount(swap_input.amount, SPLIT_STEPS, pools.len(), quote_output)
+            .ok_or_else(|| {
+            SwapError::InsufficientLiquidity(
+                "pools lack the liquidity to fill the order".to_string(),
+            )
+        })?;
+
+        let legs = pools
+            .iter()
+            .zip(allocations)
+            .filter(|(_, amount)| *amount > 0)
+            .map(|(pool, amount)| {
+                pool.quote(&SwapInput {
+                    amount,
+                    ..*swap_input
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let other_amount = legs
+            .iter()
+            .try_fold(0u64, |total, leg| total.checked_add(leg.other_amount()))
+            .ok_or_else(|| SwapError::Math("Split output overflow".to_string()))?;
+        let other_amount_threshold = legs
+            .iter()
+            .try_fold(0u64, |total, leg| {
+                total.checked_add(leg.other_amount_threshold())
+            })
+            .ok_or_else(|| SwapError::Math("Split output overflow".to_string()))?;
+
+        Ok(SplitQuote {
+            input_mint: swap_input.input_token_mint,
+            output_mint: swap_input.output_token_mint,
+            amount: swap_input.amount,
+            other_amount,
+            other_amount_threshold,
+            legs,
+            best_single_pool,
+        })
+    }
+
+    pub async fn swap_instructions(
+        &self,
+        input_pubkey: Pubkey,
+        output: SplitQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<Vec<Instruction>> {
+        let builder = self.make_swap(input_pubkey, output, overrides).await?;
+        builder.build_instructions()
+    }
+
+    pub async fn swap_transaction(
+        &self,
+        input_pubkey: Pubkey,
+        output: SplitQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<VersionedTransaction> {
+        let fee_payer = overrides.and_then(|o| o.fee_payer).unwrap_or(input_pubkey);
+        let builder = self.make_swap(input_pubkey, output, overrides).await?;
+        builder.build_transaction(Some(&fee_payer), None)
+    }
+
+    pub fn update_config(&mut self, config: &SwapConfig) {
+        self.config = *config;
+    }
+
+    async fn load_snapshot(
+        &self,
+        program_id: Pubkey,
+        pool_id: Pubkey,
+        now: u64,
+    ) -> Result<PoolSnapshot> {
+        if program_id == RAYDIUM_LIQUIDITY_POOL_V4_PROGRAM_ID {
+            let (amm_keys, market_keys, accounts) = self.amm.load_accounts(&pool_id).await?;
+            self.amm.check_tradable(&accounts, now).map_err(|reason| {
+                SwapError::PoolNotTradable {
+                    pool: pool_id,
+                    reason,
+                }
+            })?;
+            Ok(PoolSnapshot::Amm {
+                amm_keys,
+                market_keys,
+                accounts,
+            })
+        } else if program_id == RAYDIUM_CPMM_PROGRAM_ID {
+            Ok(PoolSnapshot::Cpmm {
+                pool_id,
+                accounts: self.cpmm.load_accounts(&pool_id).await?,
+            })
+        } else {
+            Err(SwapError::Unsupported(format!(
+                "Pools of program {} can't be split",
+                program_id
+            )))
+        }
+    }
+
+    async fn make_swap(
+        &self,
+        input_pubkey: Pubkey,
+        output: SplitQuote,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> Result<SwapInstructionsBuilder> {
+        let config = self.config.resolve(input_pubkey, overrides);
+        let (input_token_program, output_token_program) = output
+            .legs
+            .first()
+            .ok_or_else(|| SwapError::InvalidInput("Split has no legs".to_string()))?
+            .token_programs()?;
+        let pool_ids = output
+            .legs
+            .iter()
+            .map(|leg| leg.market())
+            .collect::<Vec<_>>();
+        let plan = SwapPlan {
+            user: input_pubkey,
+            input_mint: output.input_mint,
+            output_mint: output.output_mint,
+            input_token_program,
+            output_token_program,
+            max_amount_in: output.amount,
+            pool_ids: &pool_ids,
+            min_amount_out: Some(output.other_amount_threshold),
+        };
+        SwapInstructionsBuilder::build_swap(
+            &config,
+            plan,
+            &self.api,
+            &self.client,
+            self.priority_fee_estimator.as_ref(),
+            |builder, associated_accounts| {
+                for leg in &output.legs {
+                    builder.swap_instructions.push(leg.swap_instruction(
+                        &input_pubkey,
+                        &associated_accounts.input_ata,
+                        &associated_accounts.output_ata,
+                    )?);
+                }
+                Ok(())
+            },
+        )
+        .await
+    }
+}
+
+/// Loaded state of a pool that can take part in a split
+#[derive(Debug, Clone)]
+pub enum PoolSnapshot {
+    Amm {
+        amm_keys: AmmKeys,
+        market_keys: MarketKeys,
+        accounts: RaydiumAmmAccounts,
+    },
+    Cpmm {
+        pool_id: Pubkey,
+        accounts: RaydiumCpmmAccounts,
+    },
+}
+
+impl PoolSnapshot {
+    pub fn quote(&self, swap_input: &SwapInput) -> Result<SwapQuote> {
+        match self {
+            PoolSnapshot::Amm {
+                amm_keys,
+                market_keys,
+                accounts,
+            } => {
+                Ok(
+                    RaydiumAmm::quote_from_accounts(swap_input, *amm_keys, *market_keys, accounts)?
+                        .into(),
+                )
+            }
+            PoolSnapshot::Cpmm { pool_id, accounts } => {
+                Ok(RaydiumCpmm::quote_from_accounts(swap_input, *pool_id, accounts)?.into())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SplitQuote {
+    /// The input mint
+    pub input_mint: Pubkey,
+    /// The output mint
+    pub output_mint: Pubkey,
+    /// The total amount in
+    pub amount: u64,
+    /// The total amount out across all legs
+    pub other_amount: u64,
+    /// The total amount out with slippage
+    pub other_amount_threshold: u64,
+    /// One quote per pool receiving part of the order. Each leg's amount is its share of the input
+    pub legs: Vec<SwapQuote>,
+    /// Quote for the whole amount through the single best pool
+    pub best_single_pool: SwapQuote,
+}
+
+impl SplitQuote {
+    /// The additional output of the split over swapping through the single best pool
+    pub fn improvement(&self) -> u64 {
+        self.other_amount
+            .saturating_sub(self.best_single_pool.other_amount())
+    }
+
+    /// [`Self::improvement`] in basis points of the single best pool's output
+    pub fn improvement_bps(&self) -> u64 {
+        let best_single_amount = self.best_single_pool.other_amount();
+        if best_single_amount == 0 {
+            return 0;
+        }
+        (self.improvement() as u128 * 10_000 / best_single_amount as u128) as u64
+    }
+}
+
+/// Greedily splits `amount` into `steps` increments, giving each increment to the pool whose
+/// output increases the most. This is optimal for concave output curves such as constant
+/// product pools, up to the size of an increment.
+///
+/// `output(pool, amount)` returns the output of swapping `amount` through `pool`, or `None` if
+/// the pool can't take the swap. Returns `None` if the amount can't be fully allocated.
+fn split_amount(
+    amount: u64,
+    steps: u64,
+    pool_count: usize,
+    output: impl Fn(usize, u64) -> Option<u64>,
+) -> Option<Vec<u64>> {
+    let mut allocations = vec![0u64; pool_count];
+    let mut outputs = vec![0u64; pool_count];
+    let increment = (amount / steps.max(1)).max(1);
+    let mut remaining = amount;
+    while remaining > 0 {
+        let increment = increment.min(remaining);
+        let mut best: Option<(usize, u64, u64)> = None;
+        for pool in 0..pool_count {
+            let Some(pool_output) = output(pool, allocations[pool] + increment) else {
+                continue;
+            };
+            let marginal_output = pool_output.saturating_sub(outputs[pool]);
+            match best {
+                Some((_, _, best_marginal)) if best_marginal >= marginal_output => {}
+                _ => best = Some((pool, pool_output, marginal_output)),
+            }
+        }
+        let (pool, pool_output, _) = best?;
+        allocations[pool] += increment;
+        outputs[pool] = pool_output;
+        remaining -= increment;
+    }
+    Some(allocations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constant_product(input_reserve: u64, output_reserve: u64, amount_in: u64) -> Option<u64> {
+        let amount_in_with_fee = amount_in as u128 * 9975 / 10000;
+        let amount_out = output_reserve as u128 * amount_in_with_fee
+            / (input_reserve as u128 + amount_in_with_fee);
+        u64::try_from(amount_out).ok()
+    }
+
+    #[test]
+    pub fn split_amount_equal_pools() {
+        let allocations = split_amount(1_000_000, 50, 2, |_, amount| {
+            constant_product(10_000_000, 10_000_000, amount)
+        })
+        .unwrap();
+        assert_eq!(allocations, vec![500_000, 500_000]);
+    }
+
+    #[test]
+    pub fn split_amount_beats_single_pool() {
+        let reserves = [(10_000_000u64, 20_000_000u64), (5_000_000, 10_000_000)];
+        let output = |pool: usize, amount: u64| {
+            let (input_reserve, output_reserve) = reserves[pool];
+            constant_product(input_reserve, output_reserve, amount)
+        };
+        let amount = 3_000_000;
+        let allocations = split_amount(amount, 50, reserves.len(), output).unwrap();
+        assert_eq!(allocations.iter().sum::<u64>(), amount);
+        // the deeper pool takes the larger share
+        assert!(allocations[0] > allocations[1]);
+
+        let split_output: u64 = allocations
+            .iter()
+            .enumerate()
+            .map(|(pool, amount)| output(pool, *amount).unwrap())
+            .sum();
+        assert!(split_output > output(0, amount).unwrap());
+    }
+}
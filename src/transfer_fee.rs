@@ -0,0 +1,174 @@
+//! Token-2022 transfer fees.
+//!
+//! Mints with the transfer fee extension withhold part of every transfer from the recipient, so a
+//! pool receives less than the user sends and the user receives less than the pool sends. The fee
+//! schedule is read from the mint's extension data, so quotes use the schedule in effect on-chain.
+
+use crate::error::{Result, SwapError};
+
+use solana_sdk::account::Account;
+use spl_token_2022::extension::{BaseStateWithExtensions, StateWithExtensions};
+
+const MAX_FEE_BASIS_POINTS: u128 = 10_000;
+
+/// The fee a mint charges on transfers made from `epoch` onwards
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct TransferFeeRate {
+    pub epoch: u64,
+    pub maximum_fee: u64,
+    pub basis_points: u16,
+}
+
+impl TransferFeeRate {
+    /// The fee withheld when transferring `amount`, rounded up and capped at `maximum_fee`
+    pub fn fee(&self, amount: u64) -> Option<u64> {
+        if self.basis_points == 0 || amount == 0 {
+            return Some(0);
+        }
+        let fee = (amount as u128)
+            .checked_mul(self.basis_points as u128)?
+            .div_ceil(MAX_FEE_BASIS_POINTS);
+        Some(u64::try_from(fee).ok()?.min(self.maximum_fee))
+    }
+
+    /// The fee to add to `amount` so that `amount` is received after the fee is withheld
+    pub fn inverse_fee(&self, amount: u64) -> Option<u64> {
+        if self.basis_points == 0 {
+            return Some(0);
+        }
+        if self.basis_points as u128 >= MAX_FEE_BASIS_POINTS {
+            return Some(self.maximum_fee);
+        }
+        let pre_fee_amount = (amount as u128)
+            .checked_mul(MAX_FEE_BASIS_POINTS)?
+            .div_ceil(MAX_FEE_BASIS_POINTS - self.basis_points as u128);
+        let fee = pre_fee_amount.checked_sub(amount as u128)?;
+        Some(u64::try_from(fee).ok()?.min(self.maximum_fee))
+    }
+}
+
+/// A mint's transfer fee schedule. A fee update takes effect from `newer.epoch`, until then
+/// transfers are charged at the `older` rate.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MintTransferFee {
+    pub older: TransferFeeRate,
+    pub newer: TransferFeeRate,
+}
+
+impl MintTransferFee {
+    /// Reads the live fee schedule from a mint account. Returns `None` for spl-token mints and
+    /// token-2022 mints without the transfer fee extension.
+    pub fn from_mint(mint: &Account) -> Result<Option<Self>> {
+        if crate::utils::mint_token_program(mint)? == spl_token::ID {
+            return Ok(None);
+        }
+        let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&mint.data)
+            .map_err(|e| SwapError::DecodeError(format!("token-2022 mint: {}", e)))?;
+        let Ok(config) =
+            mint.get_extension::<spl_token_2022::extension::transfer_fee::TransferFeeConfig>()
+        else {
+            return Ok(None);
+        };
+        let rate = |fee: &spl_token_2022::extension::transfer_fee::TransferFee| TransferFeeRate {
+            epoch: u64::from(fee.epoch),
+            maximum_fee: u64::from(fee.maximum_fee),
+            basis_points: u16::from(fee.transfer_fee_basis_points),
+        };
+        Ok(Some(MintTransferFee {
+            older: rate(&config.older_transfer_fee),
+            newer: rate(&config.newer_transfer_fee),
+        }))
+    }
+
+    /// The rate charged on transfers in `epoch`
+    pub fn rate(&self, epoch: u64) -> &TransferFeeRate {
+        if epoch >= self.newer.epoch {
+            &self.newer
+        } else {
+            &self.older
+        }
+    }
+
+    /// The fee withheld when transferring `amount` in `epoch`
+    pub fn fee(&self, epoch: u64, amount: u64) -> Result<u64> {
+        self.rate(epoch)
+            .fee(amount)
+            .ok_or_else(|| SwapError::Math("Overflow while calculating transfer fee".to_string()))
+    }
+
+    /// The fee to add to `amount` so that `amount` is received after transferring it in `epoch`
+    pub fn inverse_fee(&self, epoch: u64, amount: u64) -> Result<u64> {
+        self.rate(epoch)
+            .inverse_fee(amount)
+            .ok_or_else(|| SwapError::Math("Overflow while calculating transfer fee".to_string()))
+    }
+}
+
+/// The fee withheld when transferring `amount`, zero for mints without a fee schedule
+pub(crate) fn fee(schedule: Option<&MintTransferFee>, epoch: u64, amount: u64) -> Result<u64> {
+    schedule.map_or(Ok(0), |schedule| schedule.fee(epoch, amount))
+}
+
+/// The fee to add so that `amount` is received, zero for mints without a fee schedule
+pub(crate) fn inverse_fee(
+    schedule: Option<&MintTransferFee>,
+    epoch: u64,
+    amount: u64,
+) -> Result<u64> {
+    schedule.map_or(Ok(0), |schedule| schedule.inverse_fee(epoch, amount))
+}
+
+/// The transfer fees withheld from a swap's input and output
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SwapTransferFees {
+    /// Withheld from the amount the user sends, before it reaches the pool
+    pub input: u64,
+    /// Withheld from the amount the pool sends, before it reaches the user
+    pub output: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(basis_points: u16, maximum_fee: u64) -> TransferFeeRate {
+        TransferFeeRate {
+            epoch: 0,
+            maximum_fee,
+            basis_points,
+        }
+    }
+
+    #[test]
+    pub fn fee_rounds_up_and_respects_maximum() {
+        assert_eq!(rate(100, u64::MAX).fee(1_000), Some(10));
+        assert_eq!(rate(100, u64::MAX).fee(1_001), Some(11));
+        assert_eq!(rate(100, 5).fee(1_000), Some(5));
+        assert_eq!(rate(0, u64::MAX).fee(1_000), Some(0));
+        assert_eq!(rate(100, u64::MAX).fee(0), Some(0));
+    }
+
+    #[test]
+    pub fn inverse_fee_delivers_the_amount() {
+        let fee_rate = rate(250, u64::MAX);
+        for amount in [1, 999, 1_000, 123_456_789] {
+            let fee = fee_rate.inverse_fee(amount).unwrap();
+            assert_eq!(amount + fee - fee_rate.fee(amount + fee).unwrap(), amount);
+        }
+        assert_eq!(rate(250, 3).inverse_fee(1_000_000), Some(3));
+    }
+
+    #[test]
+    pub fn schedule_switches_at_the_newer_epoch() {
+        let schedule = MintTransferFee {
+            older: rate(100, u64::MAX),
+            newer: TransferFeeRate {
+                epoch: 10,
+                ..rate(200, u64::MAX)
+            },
+        };
+        assert_eq!(schedule.fee(9, 1_000).unwrap(), 10);
+        assert_eq!(schedule.fee(10, 1_000).unwrap(), 20);
+        assert_eq!(fee(None, 10, 1_000).unwrap(), 0);
+    }
+}
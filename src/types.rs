@@ -2,16 +2,101 @@ use solana_sdk::pubkey::Pubkey;
 
 #[derive(Copy, Clone, Debug, Default)]
 pub enum ComputeUnitLimits {
+    /// The simulated compute units plus the default margin of 50_000 units
     #[default]
     Dynamic,
     Fixed(u64),
+    /// The simulated compute units plus `margin`
+    DynamicWithMargin(ComputeUnitMargin),
 }
 
+/// Headroom added to the simulated compute units, since the pool may take a costlier path by the
+/// time the transaction lands
+#[derive(Copy, Clone, Debug)]
+pub struct ComputeUnitMargin {
+    /// Units added after the percentage
+    pub absolute: u32,
+    /// Percentage of the simulated units added
+    pub percent: u16,
+}
+
+impl Default for ComputeUnitMargin {
+    fn default() -> Self {
+        ComputeUnitMargin {
+            absolute: 50_000,
+            percent: 0,
+        }
+    }
+}
+
+impl ComputeUnitMargin {
+    /// The compute unit limit for a transaction that used `units` in simulation, capped at the
+    /// most a transaction may request
+    pub fn apply(&self, units: u64) -> u32 {
+        let limit = units
+            .saturating_mul(100 + self.percent as u64)
+            .div_ceil(100)
+            .saturating_add(self.absolute as u64);
+        limit.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32
+    }
+}
+
+/// Protocol defined: The most compute units a transaction may request
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
 #[derive(Copy, Clone, Debug)]
 pub enum PriorityFeeConfig {
     DynamicMultiplier(u64),
     FixedCuPrice(u64),
     JitoTip(u64),
+    /// Sets the cu price from the fees recently paid to write the swap's accounts
+    Dynamic(DynamicPriorityFee),
+    /// Sets a cu price and tips jito
+    CuPriceAndJitoTip {
+        cu_price: CuPrice,
+        jito_tip: u64,
+    },
+}
+
+/// How the cu price of a [`PriorityFeeConfig::CuPriceAndJitoTip`] is set, as in the
+/// [`PriorityFeeConfig`] variants of the same names
+#[derive(Copy, Clone, Debug)]
+pub enum CuPrice {
+    DynamicMultiplier(u64),
+    Fixed(u64),
+    Dynamic(DynamicPriorityFee),
+}
+
+/// The cu price is a percentile of the prioritization fees paid in recent slots by transactions
+/// writing the same accounts as the swap, clamped to `min_cu_price..=max_cu_price`
+#[derive(Copy, Clone, Debug)]
+pub struct DynamicPriorityFee {
+    /// The percentile of recent per-slot fees to pay, from 0 to 100. Defaults to 75
+    pub percentile: u8,
+    /// The lowest cu price to set, in micro-lamports. Defaults to 0
+    pub min_cu_price: u64,
+    /// The highest cu price to set, in micro-lamports. Defaults to 10_000_000
+    pub max_cu_price: u64,
+}
+
+impl Default for DynamicPriorityFee {
+    fn default() -> Self {
+        DynamicPriorityFee {
+            percentile: 75,
+            min_cu_price: 0,
+            max_cu_price: 10_000_000,
+        }
+    }
+}
+
+/// A nonce account whose stored nonce replaces the recent blockhash, so a transaction stays valid
+/// until the nonce is advanced
+#[derive(Copy, Clone, Debug)]
+pub struct DurableNonce {
+    /// The nonce account
+    pub account: Pubkey,
+    /// The nonce authority, which signs the transaction
+    pub authority: Pubkey,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -20,6 +105,61 @@ pub struct SwapConfig {
     pub cu_limits: Option<ComputeUnitLimits>,
     pub wrap_and_unwrap_sol: Option<bool>,
     pub as_legacy_transaction: Option<bool>,
+    pub durable_nonce: Option<DurableNonce>,
+    pub check_simulated_output: Option<bool>,
+}
+
+impl SwapConfig {
+    /// The settings a swap by `user` is built with, `overrides` taking precedence over this
+    /// config
+    pub fn resolve(
+        &self,
+        user: Pubkey,
+        overrides: Option<&SwapConfigOverrides>,
+    ) -> ResolvedSwapConfig {
+        ResolvedSwapConfig {
+            priority_fee: overrides.and_then(|o| o.priority_fee).or(self.priority_fee),
+            cu_limits: overrides.and_then(|o| o.cu_limits).or(self.cu_limits),
+            wrap_and_unwrap_sol: overrides
+                .and_then(|o| o.wrap_and_unwrap_sol)
+                .or(self.wrap_and_unwrap_sol)
+                .unwrap_or(true),
+            source_token_account: overrides.and_then(|o| o.source_token_account),
+            destination_token_account: overrides.and_then(|o| o.destination_token_account),
+            as_legacy_transaction: overrides
+                .and_then(|o| o.as_legacy_transaction)
+                .or(self.as_legacy_transaction)
+                .unwrap_or(false),
+            fee_payer: overrides.and_then(|o| o.fee_payer).unwrap_or(user),
+            address_lookup_tables: overrides
+                .map(|o| o.address_lookup_tables.clone())
+                .unwrap_or_default(),
+            durable_nonce: overrides
+                .and_then(|o| o.durable_nonce)
+                .or(self.durable_nonce),
+            check_simulated_output: overrides
+                .and_then(|o| o.check_simulated_output)
+                .or(self.check_simulated_output)
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// An executor's [`SwapConfig`] with a swap's [`SwapConfigOverrides`] applied and the defaults
+/// filled in
+#[derive(Clone, Debug)]
+pub struct ResolvedSwapConfig {
+    pub priority_fee: Option<PriorityFeeConfig>,
+    pub cu_limits: Option<ComputeUnitLimits>,
+    pub wrap_and_unwrap_sol: bool,
+    pub source_token_account: Option<Pubkey>,
+    pub destination_token_account: Option<Pubkey>,
+    pub as_legacy_transaction: bool,
+    /// The swapping wallet unless overridden
+    pub fee_payer: Pubkey,
+    pub address_lookup_tables: Vec<Pubkey>,
+    pub durable_nonce: Option<DurableNonce>,
+    pub check_simulated_output: bool,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -27,8 +167,23 @@ pub struct SwapConfigOverrides {
     pub priority_fee: Option<PriorityFeeConfig>,
     pub cu_limits: Option<ComputeUnitLimits>,
     pub wrap_and_unwrap_sol: Option<bool>,
+    /// The token account to swap from instead of the user's ATA. Must be an initialized account
+    /// of the input mint, owned by or delegated to the user
+    pub source_token_account: Option<Pubkey>,
+    /// The token account to swap into instead of the user's ATA. Must be an initialized account
+    /// of the output mint, owned by or delegated to the user
     pub destination_token_account: Option<Pubkey>,
     pub as_legacy_transaction: Option<bool>,
+    /// Pays the transaction fee, priority fees and the rent of created token accounts in place
+    /// of the swapping wallet. The transaction then needs both signatures.
+    pub fee_payer: Option<Pubkey>,
+    /// Address lookup tables to compile v0 transactions with, in addition to the pools' own
+    pub address_lookup_tables: Vec<Pubkey>,
+    /// Builds the transaction with a durable nonce instead of a recent blockhash
+    pub durable_nonce: Option<DurableNonce>,
+    /// Fails exact-in swaps whose simulation pays less than the output threshold into the output
+    /// account, after transfer fees. Defaults to false
+    pub check_simulated_output: Option<bool>,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -51,3 +206,70 @@ impl SwapExecutionMode {
         matches!(self, SwapExecutionMode::ExactIn)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn compute_unit_margin_rounds_up_and_caps() {
+        let percent = ComputeUnitMargin {
+            absolute: 0,
+            percent: 10,
+        };
+        assert_eq!(percent.apply(101), 112);
+        assert_eq!(percent.apply(0), 0);
+
+        let margin = ComputeUnitMargin::default();
+        assert_eq!(margin.apply(200_000), 250_000);
+        assert_eq!(margin.apply(0), 50_000);
+        assert_eq!(margin.apply(1_390_000), MAX_COMPUTE_UNIT_LIMIT);
+        assert_eq!(
+            ComputeUnitMargin {
+                absolute: u32::MAX,
+                percent: u16::MAX,
+            }
+            .apply(u64::MAX),
+            MAX_COMPUTE_UNIT_LIMIT
+        );
+    }
+
+    #[test]
+    pub fn resolve_prefers_overrides_then_config_then_defaults() {
+        let user = Pubkey::new_unique();
+        let config = SwapConfig {
+            priority_fee: None,
+            cu_limits: Some(ComputeUnitLimits::Fixed(100_000)),
+            wrap_and_unwrap_sol: Some(false),
+            as_legacy_transaction: None,
+            durable_nonce: None,
+            check_simulated_output: Some(true),
+        };
+
+        let resolved = config.resolve(user, None);
+        assert!(matches!(
+            resolved.cu_limits,
+            Some(ComputeUnitLimits::Fixed(100_000))
+        ));
+        assert!(!resolved.wrap_and_unwrap_sol);
+        assert!(!resolved.as_legacy_transaction);
+        assert!(resolved.check_simulated_output);
+        assert_eq!(resolved.fee_payer, user);
+
+        let fee_payer = Pubkey::new_unique();
+        let overrides = SwapConfigOverrides {
+            cu_limits: Some(ComputeUnitLimits::Dynamic),
+            check_simulated_output: Some(false),
+            fee_payer: Some(fee_payer),
+            ..Default::default()
+        };
+        let resolved = config.resolve(user, Some(&overrides));
+        assert!(matches!(
+            resolved.cu_limits,
+            Some(ComputeUnitLimits::Dynamic)
+        ));
+        assert!(!resolved.wrap_and_unwrap_sol);
+        assert!(!resolved.check_simulated_output);
+        assert_eq!(resolved.fee_payer, fee_payer);
+    }
+}
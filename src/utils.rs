@@ -1,15 +1,20 @@
+use crate::error::{Result, SwapError};
 use futures_util::stream::FuturesOrdered;
 use futures_util::StreamExt;
 use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_client::rpc_config::RpcAccountInfoConfig;
 use solana_sdk::account::Account;
+use solana_sdk::clock::Clock;
 use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::program_option::COption;
 use solana_sdk::pubkey::Pubkey;
+use spl_token_2022::extension::StateWithExtensions;
+use spl_token_2022::state::AccountState;
 
 pub async fn get_multiple_account_data(
     rpc_client: &RpcClient,
     keys: &[Pubkey],
-) -> anyhow::Result<Vec<Option<Account>>> {
+) -> Result<Vec<Option<Account>>> {
     let mut tasks = FuturesOrdered::new();
     let mut accounts_vec = Vec::with_capacity(keys.len());
     for chunk in keys.chunks(100) {
@@ -25,7 +30,7 @@ pub async fn get_multiple_account_data(
                     },
                 )
                 .await?;
-            Ok::<_, anyhow::Error>(response.value)
+            Ok::<_, SwapError>(response.value)
         });
     }
 
@@ -34,3 +39,256 @@ pub async fn get_multiple_account_data(
     }
     Ok(accounts_vec)
 }
+
+/// Returns the cluster's unix timestamp from the Clock sysvar
+pub async fn get_cluster_unix_timestamp(rpc_client: &RpcClient) -> Result<i64> {
+    let account = rpc_client
+        .get_account(&solana_sdk::sysvar::clock::ID)
+        .await?;
+    Ok(decode_clock(&account)?.unix_timestamp)
+}
+
+/// Decodes the Clock sysvar account
+pub fn decode_clock(account: &Account) -> Result<Clock> {
+    solana_sdk::account::from_account(account)
+        .ok_or_else(|| SwapError::DecodeError("clock sysvar".to_string()))
+}
+
+/// Returns the token program owning a mint account
+pub fn mint_token_program(mint: &Account) -> Result<Pubkey> {
+    if mint.owner == spl_token::ID || mint.owner == spl_token_2022::ID {
+        Ok(mint.owner)
+    } else {
+        Err(SwapError::DecodeError(format!(
+            "mint account owned by unknown program {}",
+            mint.owner
+        )))
+    }
+}
+
+/// Loads token accounts given as (address, mint, token program) and checks that each is an
+/// initialized account of its mint and token program, owned by or delegated to `owner`. Entries
+/// without an address are skipped.
+pub async fn check_token_accounts(
+    rpc_client: &RpcClient,
+    owner: &Pubkey,
+    accounts: &[(Option<Pubkey>, Pubkey, Pubkey)],
+) -> Result<()> {
+    let accounts = accounts
+        .iter()
+        .filter_map(|(address, mint, token_program)| Some((address.as_ref()?, mint, token_program)))
+        .collect::<Vec<_>>();
+    if accounts.is_empty() {
+        return Ok(());
+    }
+    let keys = accounts
+        .iter()
+        .map(|(address, _, _)| **address)
+        .collect::<Vec<_>>();
+    let loaded = get_multiple_account_data(rpc_client, &keys).await?;
+    for ((address, mint, token_program), account) in accounts.into_iter().zip(loaded) {
+        let account = account
+            .ok_or_else(|| SwapError::AccountMissing(format!("token account {}", address)))?;
+        check_token_account(address, &account, mint, owner, token_program)?;
+    }
+    Ok(())
+}
+
+/// Checks that `account` is an initialized token account of `mint` and `token_program`, owned by
+/// or delegated to `owner`
+pub fn check_token_account(
+    address: &Pubkey,
+    account: &Account,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    token_program: &Pubkey,
+) -> Result<()> {
+    if account.owner != *token_program {
+        return Err(SwapError::InvalidInput(format!(
+            "Token account {} is owned by program {}, expected {}",
+            address, account.owner, token_program
+        )));
+    }
+    let state = StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+        .map_err(|e| SwapError::DecodeError(format!("token account {}: {}", address, e)))?
+        .base;
+    if state.state != AccountState::Initialized {
+        return Err(SwapError::InvalidInput(format!(
+            "Token account {} is {:?}",
+            address, state.state
+        )));
+    }
+    if state.mint != *mint {
+        return Err(SwapError::InvalidInput(format!(
+            "Token account {} holds mint {}, expected {}",
+            address, state.mint, mint
+        )));
+    }
+    if state.owner != *owner && state.delegate != COption::Some(*owner) {
+        return Err(SwapError::InvalidInput(format!(
+            "Token account {} is neither owned by nor delegated to {}",
+            address, owner
+        )));
+    }
+    Ok(())
+}
+
+/// The balance of a token account of either token program, zero if the account doesn't exist
+pub fn token_account_amount(address: &Pubkey, account: Option<&Account>) -> Result<u64> {
+    let Some(account) = account.filter(|account| !account.data.is_empty()) else {
+        return Ok(0);
+    };
+    Ok(
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+            .map_err(|e| SwapError::DecodeError(format!("token account {}: {}", address, e)))?
+            .base
+            .amount,
+    )
+}
+
+/// Applies `slippage_bps` to `amount`, rounding up to a maximum or down to a minimum
+pub fn amount_with_slippage(amount: u64, slippage_bps: u64, round_up: bool) -> Result<u64> {
+    const TEN_THOUSAND: u128 = 10_000;
+    let amount = amount as u128;
+    let slippage_bps = slippage_bps as u128;
+    let amount_with_slippage = if round_up {
+        amount
+            .checked_mul(TEN_THOUSAND + slippage_bps)
+            .map(|x| x.div_ceil(TEN_THOUSAND))
+    } else {
+        TEN_THOUSAND
+            .checked_sub(slippage_bps)
+            .and_then(|bps| amount.checked_mul(bps))
+            .map(|x| x / TEN_THOUSAND)
+    }
+    .ok_or_else(|| SwapError::Math("Overflow while applying slippage".to_string()))?;
+    u64::try_from(amount_with_slippage)
+        .map_err(|_| SwapError::Math("Amount with slippage exceeds u64::MAX".to_string()))
+}
+
+/// Account fixtures for quoting from accounts in tests
+#[cfg(test)]
+pub(crate) mod test_accounts {
+    use solana_sdk::account::Account;
+    use solana_sdk::clock::Clock;
+    use solana_sdk::program_pack::Pack;
+    use solana_sdk::pubkey::Pubkey;
+    use spl_token_2022::extension::transfer_fee::{TransferFee, TransferFeeConfig};
+    use spl_token_2022::extension::{ExtensionType, StateWithExtensionsMut};
+
+    /// A mint with `decimals`. With `transfer_fee_bps` it is a token-2022 mint charging that fee
+    /// from epoch 0, without it an spl-token mint.
+    pub fn mint(decimals: u8, transfer_fee_bps: Option<u16>) -> Account {
+        let Some(transfer_fee_bps) = transfer_fee_bps else {
+            let mut account = Account::new(0, spl_token::state::Mint::LEN, &spl_token::ID);
+            spl_token::state::Mint {
+                decimals,
+                is_initialized: true,
+                ..Default::default()
+            }
+            .pack_into_slice(&mut account.data);
+            return account;
+        };
+        let len = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(&[
+            ExtensionType::TransferFeeConfig,
+        ])
+        .unwrap();
+        let mut account = Account::new(0, len, &spl_token_2022::ID);
+        let mut mint = StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(
+            &mut account.data,
+        )
+        .unwrap();
+        let config = mint.init_extension::<TransferFeeConfig>(true).unwrap();
+        let transfer_fee = TransferFee {
+            epoch: 0.into(),
+            maximum_fee: u64::MAX.into(),
+            transfer_fee_basis_points: transfer_fee_bps.into(),
+        };
+        config.older_transfer_fee = transfer_fee;
+        config.newer_transfer_fee = transfer_fee;
+        mint.base = spl_token_2022::state::Mint {
+            decimals,
+            is_initialized: true,
+            ..Default::default()
+        };
+        mint.pack_base();
+        mint.init_account_type().unwrap();
+        account
+    }
+
+    /// A token account of `mint` holding `amount`, owned by `token_program`
+    pub fn token_account(mint: Pubkey, amount: u64, token_program: Pubkey) -> Account {
+        let mut account = Account::new(0, spl_token::state::Account::LEN, &token_program);
+        spl_token::state::Account {
+            mint,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut account.data);
+        account
+    }
+
+    /// The clock sysvar at `unix_timestamp` in epoch 0
+    pub fn clock(unix_timestamp: i64) -> Account {
+        solana_sdk::account::create_account_for_test(&Clock {
+            unix_timestamp,
+            ..Default::default()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::program_pack::Pack;
+
+    fn token_account(
+        mint: Pubkey,
+        owner: Pubkey,
+        state: spl_token::state::AccountState,
+    ) -> Account {
+        let mut account = Account::new(0, spl_token::state::Account::LEN, &spl_token::ID);
+        spl_token::state::Account {
+            mint,
+            owner,
+            state,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut account.data);
+        account
+    }
+
+    #[test]
+    pub fn check_token_account_validates_mint_owner_and_state() {
+        let (address, mint, owner) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let initialized = spl_token::state::AccountState::Initialized;
+        let account = token_account(mint, owner, initialized);
+        assert!(check_token_account(&address, &account, &mint, &owner, &spl_token::ID).is_ok());
+
+        for (account, token_program) in [
+            (
+                token_account(Pubkey::new_unique(), owner, initialized),
+                spl_token::ID,
+            ),
+            (
+                token_account(mint, Pubkey::new_unique(), initialized),
+                spl_token::ID,
+            ),
+            (
+                token_account(mint, owner, spl_token::state::AccountState::Frozen),
+                spl_token::ID,
+            ),
+            (token_account(mint, owner, initialized), spl_token_2022::ID),
+        ] {
+            assert!(matches!(
+                check_token_account(&address, &account, &mint, &owner, &token_program),
+                Err(SwapError::InvalidInput(_))
+            ));
+        }
+    }
+}